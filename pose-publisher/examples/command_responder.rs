@@ -0,0 +1,54 @@
+use clap::Clap;
+use pose_publisher::{
+    commands::{CommandKind, CommandState, CommandStatus},
+    CommandStatusPublisher, CommandSubscriber, PosePublisherError,
+};
+use std::net::SocketAddrV4;
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    #[clap(short, long, default_value = "239.0.0.22:7076")]
+    address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7083")]
+    status_address: SocketAddrV4,
+}
+
+/// Pretends to drive to every goal it receives
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+
+    let command_subscriber = CommandSubscriber::new_blocking(args.address)?;
+    let status_publisher = CommandStatusPublisher::new(args.status_address)?;
+
+    let mut last_command_id = None;
+    loop {
+        let command = command_subscriber.next()?;
+        // commands are retransmitted until acknowledged
+        if last_command_id == Some(command.id()) {
+            continue;
+        }
+        last_command_id = Some(command.id());
+        println!("received {:?}", command);
+        match command.kind() {
+            CommandKind::Goal { .. } | CommandKind::Waypoints { .. } => {
+                status_publisher
+                    .publish(&CommandStatus::new(command.id(), CommandState::Accepted))?;
+                for remaining in (0..5).rev() {
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                    let status = CommandStatus::new(command.id(), CommandState::Executing)
+                        .with_text(&format!("{}s remaining", remaining));
+                    status_publisher.publish(&status)?;
+                }
+                status_publisher
+                    .publish(&CommandStatus::new(command.id(), CommandState::Succeeded))?;
+            }
+            // velocity commands are streamed so they are not acknowledged
+            CommandKind::Velocity { .. } => (),
+            CommandKind::InitialPose { .. } | CommandKind::ClickedPoint { .. } => {
+                status_publisher
+                    .publish(&CommandStatus::new(command.id(), CommandState::Succeeded))?;
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use clap::Clap;
+use pose_publisher::{pose::Color, LaserScan, LaserScanPublisher, PosePublisherError};
+use std::{f32::consts::PI, net::SocketAddrV4};
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    #[clap(short, long, default_value = "239.0.0.22:7079")]
+    address: SocketAddrV4,
+}
+
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+
+    let laser_scan_publisher = LaserScanPublisher::new(args.address)?;
+
+    let samples = 360;
+    let angle_increment = 2.0 * PI / samples as f32;
+    let mut offset = 0.0_f32;
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs_f32(0.1));
+        offset += 0.05;
+        let mut ranges = vec![];
+        let mut intensities = vec![];
+        for i in 0..samples {
+            let angle = -PI + angle_increment * i as f32;
+            // every 30th sample is out of range and should not be drawn
+            let range = if i % 30 == 0 {
+                0.0
+            } else {
+                1.0 + 0.3 * (angle * 4.0 + offset).sin()
+            };
+            ranges.push(range);
+            intensities.push((angle * 2.0).cos().abs());
+        }
+
+        let laser_scan = LaserScan::new("example scan", -PI, angle_increment, 0.1, 5.0, ranges)
+            .with_intensities(intensities)
+            .with_color(Color::Green)
+            .with_parent_frame_id("rotated_object");
+        laser_scan_publisher.publish(&laser_scan)?;
+    }
+}
@@ -0,0 +1,29 @@
+use clap::Clap;
+use pose_publisher::{mcap::McapWriter, recording::LogReader, PosePublisherError};
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    /// Log file written by the recorder
+    #[clap(short, long, default_value = "rustviz.log")]
+    input: PathBuf,
+    #[clap(short, long, default_value = "rustviz.mcap")]
+    output: PathBuf,
+}
+
+/// Convert recorder log to MCAP for Foxglove Studio
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+
+    let mut reader = LogReader::open(&args.input)?;
+    let mut writer = McapWriter::new(BufWriter::new(File::create(&args.output)?))?;
+    let mut count = 0;
+    while let Some(entry) = reader.next_entry()? {
+        writer.write(&entry)?;
+        count += 1;
+    }
+    writer.finish()?;
+    println!("Exported {} messages to {}", count, args.output.display());
+    Ok(())
+}
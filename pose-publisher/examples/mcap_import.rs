@@ -0,0 +1,36 @@
+use clap::Clap;
+use pose_publisher::{mcap::McapReader, recording::LogWriter, PosePublisherError};
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    /// MCAP file with json channels named after rustviz topics like /pose
+    #[clap(short, long, default_value = "rustviz.mcap")]
+    input: PathBuf,
+    /// Log file that can be replayed with the player
+    #[clap(short, long, default_value = "rustviz.log")]
+    output: PathBuf,
+}
+
+/// Convert MCAP file to recorder log so it can be played back into rustviz
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+
+    let mut reader = McapReader::new(BufReader::new(File::open(&args.input)?))?;
+    let mut writer = LogWriter::create(&args.output)?;
+    let mut count = 0;
+    while let Some(entry) = reader.next_entry()? {
+        writer.write(&entry)?;
+        count += 1;
+    }
+    writer.finish()?;
+    println!("Imported {} messages to {}", count, args.output.display());
+    if reader.skipped() > 0 {
+        eprintln!(
+            "Skipped {} messages that could not be parsed",
+            reader.skipped()
+        );
+    }
+    Ok(())
+}
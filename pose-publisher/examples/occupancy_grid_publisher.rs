@@ -0,0 +1,68 @@
+use clap::Clap;
+use pose_publisher::{
+    header::timestamp_now, HeartbeatPublisher, OccupancyGrid, OccupancyGridPublisher,
+    PosePublisherError,
+};
+use std::{net::SocketAddrV4, time::Duration};
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    #[clap(short, long, default_value = "239.0.0.22:7080")]
+    address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7081")]
+    heartbeat_address: SocketAddrV4,
+}
+
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+
+    let occupancy_grid_publisher = OccupancyGridPublisher::new(args.address)?;
+    // map stays visible for as long as this process is running
+    let heartbeat_publisher =
+        HeartbeatPublisher::start(args.heartbeat_address, "map server", Duration::from_secs(1))?;
+
+    let size = 200;
+    let mut grid = OccupancyGrid::new("example map", 0.02, size, size)
+        .with_origin((-2., -2., 0.), (0., 0., 0., 1.))
+        .with_lifetime(heartbeat_publisher.lifetime());
+    // walls around the map
+    for i in 0..size {
+        grid.set(i, 0, 100);
+        grid.set(i, size - 1, 100);
+        grid.set(0, i, 100);
+        grid.set(size - 1, i, 100);
+    }
+    occupancy_grid_publisher.publish(&grid)?;
+
+    let mut iteration = 0;
+    loop {
+        std::thread::sleep(Duration::from_secs_f32(0.1));
+        let previous = grid.clone();
+        // reveal map as if explored by a robot driving in a circle
+        let angle = iteration as f32 * 0.05;
+        let center_x = (size / 2) as f32 + angle.cos() * 60.;
+        let center_y = (size / 2) as f32 + angle.sin() * 60.;
+        for x in 0..size {
+            for y in 0..size {
+                let distance =
+                    ((x as f32 - center_x).powi(2) + (y as f32 - center_y).powi(2)).sqrt();
+                if distance < 20. && grid.get(x, y) == Some(-1) {
+                    grid.set(x, y, (distance * 2.) as i8);
+                }
+            }
+        }
+        // patches carry the stamp of the grid so it has to follow the updates
+        grid.set_stamp(timestamp_now());
+        // resend everything now and then for late subscribers
+        if iteration % 50 == 0 {
+            occupancy_grid_publisher.publish(&grid)?;
+        } else {
+            occupancy_grid_publisher.publish_delta(&grid, &previous)?;
+        }
+        if let Some(error) = heartbeat_publisher.take_error() {
+            eprintln!("Failed to publish heartbeat {}", error);
+        }
+        iteration += 1;
+    }
+}
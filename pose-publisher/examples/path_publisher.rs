@@ -0,0 +1,36 @@
+use clap::Clap;
+use pose_publisher::{pose::Color, Path, PathPublisher, PosePublisherError};
+use std::net::SocketAddrV4;
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    #[clap(short, long, default_value = "239.0.0.22:7077")]
+    address: SocketAddrV4,
+}
+
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+
+    let path_publisher = PathPublisher::new(args.address)?;
+
+    let mut offset = 0.0_f32;
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs_f32(0.1));
+        offset += 0.05;
+        let mut poses = vec![];
+        for i in 0..50 {
+            let x = i as f32 * 0.04;
+            let y = (x * 3.0 + offset).sin() * 0.3;
+            let slope = (x * 3.0 + offset).cos() * 0.9;
+            let yaw = slope.atan();
+            let rotation = (0., 0., (yaw / 2.0).sin(), (yaw / 2.0).cos());
+            poses.push(((x, y, 0.02), rotation));
+        }
+
+        let path = Path::from_poses("example path", poses)
+            .with_color(Color::Yellow)
+            .with_width(0.02);
+        path_publisher.publish(&path)?;
+    }
+}
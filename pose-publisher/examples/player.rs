@@ -0,0 +1,135 @@
+use clap::Clap;
+use pose_publisher::{
+    header::timestamp_now,
+    recording::{LogMessage, LogReader, Topic},
+    CommandPublisher, CommandStatusPublisher, LaserScanPublisher, OccupancyGridPublisher,
+    PathPublisher, PointCloud3Publisher, PointCloudPublisher, PosePublisher, PosePublisherError,
+    RawPublisher, TransformPublisher,
+};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    /// Log file written by the recorder
+    #[clap(short, long, default_value = "rustviz.log")]
+    input: PathBuf,
+    /// Playback speed factor
+    #[clap(short, long, default_value = "1.0")]
+    speed: f64,
+    /// Start over when the end of the log is reached
+    #[clap(long)]
+    r#loop: bool,
+    /// Seconds into the log to start from
+    #[clap(long, default_value = "0.0")]
+    start: f64,
+    /// Only republish these topics. All recorded topics if not set
+    #[clap(short, long)]
+    topics: Vec<Topic>,
+    /// Publish with original stamps instead of shifting them to current time
+    #[clap(long)]
+    original_stamps: bool,
+}
+
+/// Publishers of every topic on their default addresses
+struct Publishers {
+    pose: PosePublisher,
+    point_cloud: PointCloudPublisher,
+    command: CommandPublisher,
+    path: PathPublisher,
+    point_cloud_3d: PointCloud3Publisher,
+    laser_scan: LaserScanPublisher,
+    occupancy_grid: OccupancyGridPublisher,
+    /// Heartbeat publisher sends its own heartbeats so recorded ones are sent raw
+    heartbeat: RawPublisher,
+    transform: TransformPublisher,
+    command_status: CommandStatusPublisher,
+}
+
+impl Publishers {
+    fn new() -> Result<Self, PosePublisherError> {
+        Ok(Self {
+            pose: PosePublisher::new(Topic::Pose.address())?,
+            point_cloud: PointCloudPublisher::new(Topic::PointCloud.address())?,
+            command: CommandPublisher::new(Topic::Command.address())?,
+            path: PathPublisher::new(Topic::Path.address())?,
+            point_cloud_3d: PointCloud3Publisher::new(Topic::PointCloud3.address())?,
+            laser_scan: LaserScanPublisher::new(Topic::LaserScan.address())?,
+            occupancy_grid: OccupancyGridPublisher::new(Topic::OccupancyGrid.address())?,
+            heartbeat: RawPublisher::new(Topic::Heartbeat.address())?,
+            transform: TransformPublisher::new(Topic::Transform.address())?,
+            command_status: CommandStatusPublisher::new(Topic::CommandStatus.address())?,
+        })
+    }
+
+    fn publish(&self, message: &LogMessage) -> Result<(), PosePublisherError> {
+        match message {
+            LogMessage::Pose(update) => self.pose.publish(update),
+            LogMessage::PointCloud(point_cloud) => self.point_cloud.publish(point_cloud),
+            LogMessage::Command(command) => self.command.publish(command),
+            LogMessage::Path(path) => self.path.publish(path),
+            LogMessage::PointCloud3(point_cloud) => self.point_cloud_3d.publish(point_cloud),
+            LogMessage::LaserScan(laser_scan) => self.laser_scan.publish(laser_scan),
+            LogMessage::OccupancyGrid(message) => self.occupancy_grid.publish_message(message),
+            LogMessage::Heartbeat(heartbeat) => {
+                let payload = serde_json::to_string(heartbeat)
+                    .map_err(|_| PosePublisherError::JsonParsingError)?;
+                self.heartbeat.publish(&payload)
+            }
+            LogMessage::Transform(update) => self.transform.publish(update),
+            LogMessage::CommandStatus(status) => self.command_status.publish(status),
+        }
+    }
+}
+
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+    let speed = args.speed.max(0.01);
+    let topics = if args.topics.is_empty() {
+        Topic::ALL.to_vec()
+    } else {
+        args.topics.clone()
+    };
+
+    let publishers = Publishers::new()?;
+
+    let mut reader = LogReader::open(&args.input)?;
+    let log_start = match reader.start_time() {
+        Some(start) => start + args.start,
+        None => {
+            println!("{} is empty", args.input.display());
+            return Ok(());
+        }
+    };
+
+    loop {
+        reader.seek(log_start)?;
+        let playback_start = Instant::now();
+        let stamp_offset = timestamp_now() - log_start;
+        let mut count = 0;
+        while let Some(entry) = reader.next_entry()? {
+            if !topics.contains(&entry.message.topic()) {
+                continue;
+            }
+            let due = Duration::from_secs_f64(((entry.received - log_start) / speed).max(0.));
+            if let Some(wait) = due.checked_sub(playback_start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+            let message = if args.original_stamps {
+                entry.message
+            } else {
+                // at other speeds stamps still advance at log rate
+                entry.message.shift_stamp(stamp_offset)
+            };
+            publishers.publish(&message)?;
+            count += 1;
+        }
+        println!("Played {} messages", count);
+        if !args.r#loop {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use clap::Clap;
+use pose_publisher::{
+    point_cloud::{Colormap, ColormapChannel, PointColoring},
+    PointCloud3, PointCloud3Publisher, PosePublisherError,
+};
+use std::net::SocketAddrV4;
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    #[clap(short, long, default_value = "239.0.0.22:7078")]
+    address: SocketAddrV4,
+}
+
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+
+    let point_cloud_publisher = PointCloud3Publisher::new(args.address)?;
+
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs_f32(0.2));
+        let mut points = vec![];
+        let mut intensities = vec![];
+        for i in 0..1500 {
+            let i = i as f32 * 0.02;
+            points.push((i.sin() * 0.5, i.cos() * 0.5, i * 0.03));
+            intensities.push((i * 2.0).sin().abs());
+        }
+
+        let point_cloud = PointCloud3::from_points("example 3d cloud", points)
+            .with_intensities(intensities)
+            .with_coloring(PointColoring::Colormap {
+                colormap: Colormap::Viridis,
+                channel: ColormapChannel::Z,
+                range: None,
+            });
+        point_cloud_publisher.publish(&point_cloud)?;
+    }
+}
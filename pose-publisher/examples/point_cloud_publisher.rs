@@ -26,6 +26,7 @@ fn main() -> Result<(), PosePublisherError> {
 
         let point_cloud = PointCloud2::from_points("example cloud", points)
             .with_color(Color::Cyan)
+            .with_point_size(3.0)
             .with_parent_frame_id("rotated_object");
         point_cloud_publisher.publish(&point_cloud).unwrap();
     }
@@ -47,5 +47,8 @@ fn main() -> Result<(), PosePublisherError> {
     let mut update = PoseClientUpdate::new();
     update.delete("obj_a");
     pose_publisher.publish(&update)?;
+    let mut update = PoseClientUpdate::new();
+    update.delete_matching("test *");
+    pose_publisher.publish(&update)?;
     Ok(())
 }
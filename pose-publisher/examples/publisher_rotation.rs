@@ -1,5 +1,8 @@
 use clap::Clap;
-use pose_publisher::{pose::Shape, PoseClientUpdate, PosePublisher, PosePublisherError};
+use pose_publisher::{
+    pose::{Covariance, Shape},
+    PoseClientUpdate, PosePublisher, PosePublisherError,
+};
 use std::net::SocketAddrV4;
 
 #[derive(Clap)]
@@ -24,7 +27,17 @@ fn main() -> Result<(), PosePublisherError> {
             update
                 .add("rotated_object", (0., 0., i))
                 .with_shape(Shape::Cube(0.3, 0.01, 0.01))
+                .with_rotation((w, 0., y, 0.))
+                .with_covariance(Covariance::planar([0.02, 0.005, 0.005, 0.01], 0.1));
+            update
+                .add("rotated_axes", (0.5, 0., i))
+                .with_shape(Shape::Axes(0.2))
                 .with_rotation((w, 0., y, 0.));
+            // sensor mounted on the end of the rotated object
+            update
+                .add("mounted_sensor", (0.15, 0., 0.))
+                .with_parent_frame_id("rotated_object")
+                .with_shape(Shape::Sphere(0.03));
             pose_publisher.publish(&update)?;
         }
         for i in (0..=100).rev() {
@@ -0,0 +1,134 @@
+use clap::Clap;
+use pose_publisher::{
+    header::timestamp_now,
+    recording::{LogEntry, LogMessage, LogWriter, Topic},
+    CommandStatusSubscriber, CommandSubscriber, HeartbeatSubscriber, LaserScanSubscriber,
+    OccupancyGridSubscriber, PathSubscriber, PointCloud3Subscriber, PointCloudSubscriber,
+    PosePublisherError, PoseSubscriber, TransformSubscriber,
+};
+use std::{
+    path::PathBuf,
+    sync::mpsc::{channel, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    /// Log file. Index is written next to it while recording
+    #[clap(short, long, default_value = "rustviz.log")]
+    output: PathBuf,
+    /// Stop after this many seconds. Records until killed if not set
+    #[clap(short, long)]
+    duration: Option<f32>,
+    /// Only record these topics. All topics if not set
+    #[clap(short, long)]
+    topics: Vec<Topic>,
+}
+
+fn forward<T, F>(sender: Sender<LogEntry>, next: F, to_message: fn(T) -> LogMessage)
+where
+    F: Fn() -> Result<T, PosePublisherError> + Send + 'static,
+    T: 'static,
+{
+    std::thread::spawn(move || loop {
+        match next() {
+            Ok(message) => {
+                let entry = LogEntry {
+                    received: timestamp_now(),
+                    message: to_message(message),
+                };
+                if sender.send(entry).is_err() {
+                    return;
+                }
+            }
+            Err(PosePublisherError::JsonParsingError) => eprintln!("Failed to parse message"),
+            Err(error) => {
+                eprintln!("Receiving failed {}", error);
+                return;
+            }
+        }
+    });
+}
+
+/// Subscribes to topic on its default address
+fn subscribe(topic: Topic, sender: Sender<LogEntry>) -> Result<(), PosePublisherError> {
+    let address = topic.address();
+    match topic {
+        Topic::Pose => {
+            let subscriber = PoseSubscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::Pose)
+        }
+        Topic::PointCloud => {
+            let subscriber = PointCloudSubscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::PointCloud)
+        }
+        Topic::Command => {
+            let subscriber = CommandSubscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::Command)
+        }
+        Topic::Path => {
+            let subscriber = PathSubscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::Path)
+        }
+        Topic::PointCloud3 => {
+            // chunks are joined so that the log holds whole clouds
+            let subscriber = PointCloud3Subscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::PointCloud3)
+        }
+        Topic::LaserScan => {
+            let subscriber = LaserScanSubscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::LaserScan)
+        }
+        Topic::OccupancyGrid => {
+            let subscriber = OccupancyGridSubscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::OccupancyGrid)
+        }
+        Topic::Heartbeat => {
+            let subscriber = HeartbeatSubscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::Heartbeat)
+        }
+        Topic::Transform => {
+            let subscriber = TransformSubscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::Transform)
+        }
+        Topic::CommandStatus => {
+            let subscriber = CommandStatusSubscriber::new_blocking(address)?;
+            forward(sender, move || subscriber.next(), LogMessage::CommandStatus)
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+    let topics = if args.topics.is_empty() {
+        Topic::ALL.to_vec()
+    } else {
+        args.topics.clone()
+    };
+
+    let (sender, receiver) = channel();
+    for topic in topics {
+        subscribe(topic, sender.clone())?;
+    }
+    drop(sender);
+
+    let mut writer = LogWriter::create(&args.output)?;
+    let start = Instant::now();
+    let end = args.duration.map(Duration::from_secs_f32);
+    let mut count = 0;
+    while end.is_none_or(|end| start.elapsed() < end) {
+        match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(entry) => {
+                writer.write(&entry)?;
+                count += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    writer.finish()?;
+    println!("Recorded {} messages to {}", count, args.output.display());
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use clap::Clap;
+use pose_publisher::{
+    pose::Shape, PoseClientUpdate, PosePublisher, PosePublisherError, Transform,
+    TransformPublisher, TransformUpdate,
+};
+use std::net::SocketAddrV4;
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    #[clap(short, long, default_value = "239.0.0.22:7082")]
+    address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7072")]
+    pose_address: SocketAddrV4,
+}
+
+fn main() -> Result<(), PosePublisherError> {
+    let args = Args::parse();
+
+    let transform_publisher = TransformPublisher::new(args.address)?;
+    let pose_publisher = PosePublisher::new(args.pose_address)?;
+
+    let mut angle = 0.0_f32;
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs_f32(0.05));
+        angle += 0.02;
+        // robot driving in a circle with a laser mounted on top
+        let yaw = angle + std::f32::consts::FRAC_PI_2;
+        let mut update = TransformUpdate::new();
+        update
+            .add(Transform::new(
+                "world",
+                "base_link",
+                (angle.cos(), angle.sin(), 0.),
+                (0., 0., (yaw / 2.0).sin(), (yaw / 2.0).cos()),
+            ))
+            .add(Transform::new_static(
+                "base_link",
+                "laser",
+                (0.1, 0., 0.2),
+                (0., 0., 0., 1.),
+            ));
+        transform_publisher.publish(&update)?;
+
+        let mut pose_update = PoseClientUpdate::new();
+        pose_update
+            .add("laser_housing", (0., 0., 0.))
+            .with_parent_frame_id("laser")
+            .with_shape(Shape::Cube(0.05, 0.05, 0.05));
+        pose_update
+            .add("robot_axes", (0., 0., 0.))
+            .with_parent_frame_id("base_link")
+            .with_shape(Shape::Axes(0.2));
+        pose_publisher.publish(&pose_update)?;
+    }
+}
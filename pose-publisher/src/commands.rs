@@ -1,39 +1,344 @@
-use serde::{Deserialize, Serialize};
+use crate::header::Header;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Serialize, Deserialize, Default, Debug)]
-pub struct Command {
-    id: u32,
+/// What the robot is asked to do. Positions are in the frame of the command header
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[serde(tag = "kind")]
+pub enum CommandKind {
+    /// Navigate to point and face angle. Angle is in range -Pi -> Pi
+    Goal { point: (f32, f32), angle: f32 },
+    /// Reset pose estimate to point and angle. Angle is in range -Pi -> Pi
+    InitialPose { point: (f32, f32), angle: f32 },
+    /// Single point selected in the scene
+    ClickedPoint { point: (f32, f32, f32) },
+    /// Points to visit in order
+    Waypoints { points: Vec<(f32, f32)> },
+    /// Drive with linear (x, y) velocity in m/s and angular velocity around z in rad/s.
+    /// Velocities are in the robot frame
+    Velocity { linear: (f32, f32), angular: f32 },
+}
+
+impl Default for CommandKind {
+    fn default() -> Self {
+        CommandKind::Goal {
+            point: (0., 0.),
+            angle: 0.,
+        }
+    }
+}
+
+impl CommandKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandKind::Goal { .. } => "goal",
+            CommandKind::InitialPose { .. } => "initial pose",
+            CommandKind::ClickedPoint { .. } => "clicked point",
+            CommandKind::Waypoints { .. } => "waypoints",
+            CommandKind::Velocity { .. } => "velocity",
+        }
+    }
+}
+
+/// Goal as sent by older viewers, without a kind
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LegacyGoal {
     point: (f32, f32),
     angle: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyCommandKind {
+    Kind(CommandKind),
+    Goal(LegacyGoal),
+}
+
+/// Reads a command kind or falls back to a goal for commands without one
+fn deserialize_kind<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CommandKind, D::Error> {
+    Ok(match LegacyCommandKind::deserialize(deserializer)? {
+        LegacyCommandKind::Kind(kind) => kind,
+        LegacyCommandKind::Goal(LegacyGoal { point, angle }) => CommandKind::Goal { point, angle },
+    })
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, JsonSchema)]
+pub struct Command {
+    #[serde(default)]
+    header: Header,
+    id: u32,
+    #[serde(flatten, deserialize_with = "deserialize_kind")]
+    kind: CommandKind,
+    /// Length of the drag that set a goal. Kept for receivers that require it
+    #[serde(default)]
     length: f32,
 }
 
 impl Command {
-    pub fn new(id: u32, point: (f32, f32), angle: f32, length: f32) -> Self {
+    pub fn from_kind(id: u32, kind: CommandKind) -> Self {
         Self {
+            header: Header::new(),
             id,
-            point,
-            angle,
+            kind,
+            length: 0.,
+        }
+    }
+
+    /// Goal with the length of the drag that set it
+    #[deprecated(note = "use Command::goal instead")]
+    pub fn new(id: u32, point: (f32, f32), angle: f32, length: f32) -> Self {
+        Self {
             length,
+            ..Self::goal(id, point, angle)
         }
     }
 
-    /// Monotonically incremental ID
+    /// Angle in range -Pi -> Pi
+    pub fn goal(id: u32, point: (f32, f32), angle: f32) -> Self {
+        Self::from_kind(id, CommandKind::Goal { point, angle })
+    }
+
+    /// Angle in range -Pi -> Pi
+    pub fn initial_pose(id: u32, point: (f32, f32), angle: f32) -> Self {
+        Self::from_kind(id, CommandKind::InitialPose { point, angle })
+    }
+
+    pub fn clicked_point(id: u32, point: (f32, f32, f32)) -> Self {
+        Self::from_kind(id, CommandKind::ClickedPoint { point })
+    }
+
+    pub fn waypoints(id: u32, points: Vec<(f32, f32)>) -> Self {
+        Self::from_kind(id, CommandKind::Waypoints { points })
+    }
+
+    /// Linear (x, y) in m/s and angular in rad/s
+    pub fn velocity(id: u32, linear: (f32, f32), angular: f32) -> Self {
+        Self::from_kind(id, CommandKind::Velocity { linear, angular })
+    }
+
+    /// Seconds since unix epoch at which the command was issued
+    pub fn with_stamp(mut self, stamp: f64) -> Self {
+        self.header.stamp = stamp;
+        self
+    }
+
+    /// Frame the positions are expressed in
+    pub fn with_frame_id(mut self, frame_id: &str) -> Self {
+        self.header.frame_id = Some(frame_id.to_owned());
+        self
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Monotonically incremental ID shared by every kind of command from a publisher
     pub fn id(&self) -> u32 {
         self.id
     }
 
-    /// in x, y
+    pub fn kind(&self) -> &CommandKind {
+        &self.kind
+    }
+
+    /// Point of a goal or initial pose in x, y. Origin for other kinds
+    #[deprecated(note = "use kind() instead")]
     pub fn point(&self) -> (f32, f32) {
-        self.point
+        match &self.kind {
+            CommandKind::Goal { point, .. } | CommandKind::InitialPose { point, .. } => *point,
+            CommandKind::ClickedPoint { point } => (point.0, point.1),
+            CommandKind::Waypoints { .. } | CommandKind::Velocity { .. } => (0., 0.),
+        }
     }
 
-    /// In range -Pi -> Pi
+    /// Angle of a goal or initial pose in range -Pi -> Pi. Zero for other kinds
+    #[deprecated(note = "use kind() instead")]
     pub fn angle(&self) -> f32 {
-        self.angle
+        match &self.kind {
+            CommandKind::Goal { angle, .. } | CommandKind::InitialPose { angle, .. } => *angle,
+            _ => 0.,
+        }
     }
 
+    #[deprecated(note = "use kind() instead")]
     pub fn length(&self) -> f32 {
         self.length
     }
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+pub enum CommandState {
+    Accepted,
+    Rejected,
+    Executing,
+    Succeeded,
+    Failed,
+}
+
+impl CommandState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandState::Accepted => "accepted",
+            CommandState::Rejected => "rejected",
+            CommandState::Executing => "executing",
+            CommandState::Succeeded => "succeeded",
+            CommandState::Failed => "failed",
+        }
+    }
+
+    /// No further updates are expected
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            CommandState::Rejected | CommandState::Succeeded | CommandState::Failed
+        )
+    }
+}
+
+/// Feedback published by the robot. Any status acknowledges the command
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CommandStatus {
+    #[serde(default)]
+    header: Header,
+    command_id: u32,
+    state: CommandState,
+    /// Human readable progress, for example remaining distance
+    #[serde(default)]
+    text: String,
+}
+
+impl CommandStatus {
+    pub fn new(command_id: u32, state: CommandState) -> Self {
+        Self {
+            header: Header::new(),
+            command_id,
+            state,
+            text: String::new(),
+        }
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = text.to_owned();
+        self
+    }
+
+    /// Seconds since unix epoch at which the state was reached
+    pub fn with_stamp(mut self, stamp: f64) -> Self {
+        self.header.stamp = stamp;
+        self
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn command_id(&self) -> u32 {
+        self.command_id
+    }
+
+    pub fn state(&self) -> CommandState {
+        self.state
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn kind_is_tagged_next_to_command_fields() {
+        let command = serde_json::to_value(Command::goal(3, (1., 2.), 0.5)).unwrap();
+        assert_eq!(command["id"], json!(3));
+        assert_eq!(command["kind"], json!("Goal"));
+        assert_eq!(command["point"], json!([1., 2.]));
+        assert_eq!(command["angle"], json!(0.5));
+    }
+
+    #[test]
+    fn all_kinds_survive_serialization() {
+        let commands = vec![
+            Command::goal(0, (1., 2.), 0.5),
+            Command::initial_pose(1, (-1., 0.), -0.5),
+            Command::clicked_point(2, (1., 2., 3.)),
+            Command::waypoints(3, vec![(0., 0.), (1., 1.)]),
+            Command::velocity(4, (0.5, 0.), 0.1),
+        ];
+        for command in commands {
+            let json = serde_json::to_string(&command).unwrap();
+            let deserialized: Command = serde_json::from_str(&json).unwrap();
+            assert_eq!(deserialized.id(), command.id());
+            assert_eq!(deserialized.kind(), command.kind());
+        }
+    }
+
+    #[test]
+    fn header_defaults_when_missing() {
+        let command: Command = serde_json::from_value(json!({
+            "id": 1,
+            "kind": "ClickedPoint",
+            "point": [1.0, 2.0, 3.0],
+        }))
+        .unwrap();
+        assert_eq!(command.header().frame_id, None);
+        assert_eq!(
+            command.kind(),
+            &CommandKind::ClickedPoint {
+                point: (1., 2., 3.)
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_kind_is_rejected() {
+        let command: Value = json!({"id": 1, "kind": "Dance"});
+        assert!(serde_json::from_value::<Command>(command).is_err());
+        let command: Value = json!({"id": 1, "kind": "Dance", "point": [1.0, 2.0], "angle": 0.5});
+        assert!(serde_json::from_value::<Command>(command).is_err());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn legacy_command_is_read_as_goal() {
+        let command: Command = serde_json::from_value(json!({
+            "id": 4,
+            "point": [1.0, 2.0],
+            "angle": 0.5,
+            "length": 1.5,
+        }))
+        .unwrap();
+        assert_eq!(
+            command.kind(),
+            &CommandKind::Goal {
+                point: (1., 2.),
+                angle: 0.5
+            }
+        );
+        assert_eq!(command.point(), (1., 2.));
+        assert_eq!(command.angle(), 0.5);
+        assert_eq!(command.length(), 1.5);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn legacy_constructor_builds_goal_readable_by_older_receivers() {
+        let command = serde_json::to_value(Command::new(2, (1., 2.), 0.5, 1.5)).unwrap();
+        assert_eq!(command["kind"], json!("Goal"));
+        assert_eq!(command["point"], json!([1., 2.]));
+        assert_eq!(command["angle"], json!(0.5));
+        assert_eq!(command["length"], json!(1.5));
+    }
+
+    #[test]
+    fn default_is_goal_at_origin() {
+        let command = Command::default();
+        assert_eq!(command.id(), 0);
+        assert_eq!(command.kind(), &CommandKind::default());
+        assert_eq!(command.kind().name(), "goal");
+    }
+}
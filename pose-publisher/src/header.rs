@@ -0,0 +1,105 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since unix epoch
+pub fn timestamp_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or(0.)
+}
+
+/// Builders and accessors for messages with a `header` field
+macro_rules! header_methods {
+    () => {
+        /// Seconds since unix epoch at which the data was captured
+        pub fn with_stamp(mut self, stamp: f64) -> Self {
+            self.header.stamp = stamp;
+            self
+        }
+
+        pub fn with_parent_frame_id(mut self, frame_id: &str) -> Self {
+            self.header.frame_id = Some(frame_id.to_owned());
+            self
+        }
+
+        pub fn parent_frame_id(&self) -> &Option<String> {
+            &self.header.frame_id
+        }
+
+        pub fn header(&self) -> &$crate::header::Header {
+            &self.header
+        }
+    };
+}
+
+pub(crate) use header_methods;
+
+/// Metadata carried by every message
+///
+/// Messages without a header are treated as captured when they arrive
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, JsonSchema)]
+pub struct Header {
+    /// Seconds since unix epoch at which the data was captured. Zero if unknown
+    pub stamp: f64,
+    /// Frame the data is expressed in. World if None
+    pub frame_id: Option<String>,
+}
+
+impl Header {
+    /// Stamped with current time
+    pub fn new() -> Self {
+        Self {
+            stamp: timestamp_now(),
+            frame_id: None,
+        }
+    }
+
+    pub fn has_stamp(&self) -> bool {
+        self.stamp > 0.
+    }
+}
+
+/// Serde adapter for messages that carried their frame in a top level
+/// `parent_frame_id` before they had a header
+///
+/// Reads either field and writes both
+pub(crate) mod legacy_parent_frame_id {
+    use super::Header;
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    pub(crate) struct HeaderWithParentFrameId {
+        #[serde(default)]
+        header: Header,
+        /// Same as the header frame. Used if the header has none
+        #[serde(default)]
+        parent_frame_id: Option<String>,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        header: &Header,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        HeaderWithParentFrameId {
+            header: header.clone(),
+            parent_frame_id: header.frame_id.clone(),
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Header, D::Error> {
+        let HeaderWithParentFrameId {
+            mut header,
+            parent_frame_id,
+        } = HeaderWithParentFrameId::deserialize(deserializer)?;
+        if header.frame_id.is_none() {
+            header.frame_id = parent_frame_id;
+        }
+        Ok(header)
+    }
+}
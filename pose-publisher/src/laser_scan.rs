@@ -0,0 +1,288 @@
+use crate::header::{header_methods, Header};
+use crate::lifetime::{default_lifetime, entity_methods, Lifetime};
+use crate::point_cloud::{Colormap, ColormapChannel, PointCloud3, PointColoring};
+use crate::pose::Color;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+const DEFAULT_RED_COLOR: Color = Color::Red;
+
+/// Json has no NaN or infinity, serde_json writes them as null
+fn deserialize_ranges<'de, D>(deserializer: D) -> Result<Vec<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ranges: Vec<Option<f32>> = Deserialize::deserialize(deserializer)?;
+    Ok(ranges
+        .into_iter()
+        .map(|range| range.unwrap_or(f32::NAN))
+        .collect())
+}
+
+/// Single planar scan from a range sensor
+///
+/// Angles are in radians counter clockwise around the z axis of the parent frame
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct LaserScan {
+    id: String,
+    #[serde(default)]
+    namespace: String,
+    #[serde(default)]
+    header: Header,
+    angle_min: f32,
+    angle_increment: f32,
+    range_min: f32,
+    range_max: f32,
+    #[serde(deserialize_with = "deserialize_ranges")]
+    ranges: Vec<f32>,
+    intensities: Option<Vec<f32>>,
+    #[serde(
+        alias = "timeout",
+        default = "default_lifetime",
+        deserialize_with = "crate::lifetime::deserialize_lifetime"
+    )]
+    lifetime: Lifetime,
+    color: Color,
+}
+
+impl LaserScan {
+    pub fn new(
+        id: &str,
+        angle_min: f32,
+        angle_increment: f32,
+        range_min: f32,
+        range_max: f32,
+        ranges: Vec<f32>,
+    ) -> Self {
+        Self {
+            id: id.to_owned(),
+            namespace: String::new(),
+            header: Header::new(),
+            angle_min,
+            angle_increment,
+            range_min,
+            range_max,
+            ranges,
+            intensities: None,
+            lifetime: default_lifetime(),
+            color: DEFAULT_RED_COLOR,
+        }
+    }
+
+    entity_methods!();
+
+    header_methods!();
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// One intensity per range
+    pub fn with_intensities(mut self, intensities: Vec<f32>) -> Self {
+        self.intensities = Some(intensities);
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn angle_min(&self) -> f32 {
+        self.angle_min
+    }
+
+    pub fn angle_increment(&self) -> f32 {
+        self.angle_increment
+    }
+
+    pub fn range_min(&self) -> f32 {
+        self.range_min
+    }
+
+    pub fn range_max(&self) -> f32 {
+        self.range_max
+    }
+
+    pub fn ranges(&self) -> &Vec<f32> {
+        &self.ranges
+    }
+
+    pub fn intensities(&self) -> &Option<Vec<f32>> {
+        &self.intensities
+    }
+
+    pub fn color(&self) -> &Color {
+        &self.color
+    }
+
+    fn is_valid_range(&self, range: f32) -> bool {
+        range.is_finite() && range >= self.range_min && range <= self.range_max
+    }
+
+    /// Cartesian (x, y) points of all ranges within range_min and range_max
+    pub fn points(&self) -> Vec<(f32, f32)> {
+        self.ranges
+            .iter()
+            .enumerate()
+            .filter(|(_, range)| self.is_valid_range(**range))
+            .map(|(index, range)| {
+                let angle = self.angle_min + self.angle_increment * index as f32;
+                (range * angle.cos(), range * angle.sin())
+            })
+            .collect()
+    }
+
+    /// Convert into a point cloud in the same parent frame
+    ///
+    /// Points are colored by intensity if intensities are present
+    pub fn to_point_cloud(&self) -> PointCloud3 {
+        let points = self
+            .points()
+            .into_iter()
+            .map(|(x, y)| (x, y, 0.0))
+            .collect();
+        let mut point_cloud = PointCloud3::from_points(&self.id, points)
+            .with_namespace(&self.namespace)
+            .with_color(self.color)
+            .with_lifetime(self.lifetime.clone())
+            .with_stamp(self.header.stamp);
+        if let Some(parent_frame_id) = &self.header.frame_id {
+            point_cloud = point_cloud.with_parent_frame_id(parent_frame_id);
+        }
+        match &self.intensities {
+            Some(intensities) if intensities.len() == self.ranges.len() => {
+                let valid_intensities = self
+                    .ranges
+                    .iter()
+                    .zip(intensities)
+                    .filter(|(range, _)| self.is_valid_range(**range))
+                    .map(|(_, intensity)| *intensity)
+                    .collect();
+                point_cloud
+                    .with_intensities(valid_intensities)
+                    .with_coloring(PointColoring::Colormap {
+                        colormap: Colormap::Jet,
+                        channel: ColormapChannel::Intensity,
+                        range: None,
+                    })
+            }
+            _ => point_cloud,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_points_eq(actual: &[(f32, f32)], expected: &[(f32, f32)]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (actual_point, expected_point) in actual.iter().zip(expected) {
+            assert!(
+                (actual_point.0 - expected_point.0).abs() < 1e-5
+                    && (actual_point.1 - expected_point.1).abs() < 1e-5,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn points_step_by_angle_increment() {
+        let scan = LaserScan::new("scan", 0.0, FRAC_PI_2, 0.1, 10.0, vec![1.0, 2.0, 3.0]);
+        assert_points_eq(&scan.points(), &[(1.0, 0.0), (0.0, 2.0), (-3.0, 0.0)]);
+    }
+
+    #[test]
+    fn points_start_at_angle_min() {
+        let scan = LaserScan::new("scan", -FRAC_PI_2, FRAC_PI_2, 0.1, 10.0, vec![1.0, 1.0]);
+        assert_points_eq(&scan.points(), &[(0.0, -1.0), (1.0, 0.0)]);
+    }
+
+    #[test]
+    fn ranges_outside_limits_are_skipped_without_shifting_angles() {
+        let scan = LaserScan::new(
+            "scan",
+            0.0,
+            FRAC_PI_2,
+            0.5,
+            5.0,
+            vec![0.2, 2.0, 6.0, 5.0, 0.5],
+        );
+        // limits are inclusive
+        assert_points_eq(&scan.points(), &[(0.0, 2.0), (0.0, -5.0), (0.5, 0.0)]);
+    }
+
+    #[test]
+    fn nan_and_infinite_ranges_are_skipped() {
+        let scan = LaserScan::new(
+            "scan",
+            0.0,
+            FRAC_PI_2,
+            0.0,
+            f32::INFINITY,
+            vec![f32::NAN, 1.0, f32::INFINITY],
+        );
+        assert_points_eq(&scan.points(), &[(0.0, 1.0)]);
+    }
+
+    #[test]
+    fn null_ranges_deserialize_as_nan() {
+        let scan = LaserScan::new("scan", 0.0, FRAC_PI_2, 0.1, 10.0, vec![f32::NAN, 1.0]);
+        let json = serde_json::to_string(&scan).unwrap();
+        assert!(json.contains("[null,1.0]"));
+        let scan: LaserScan = serde_json::from_str(&json).unwrap();
+        assert!(scan.ranges()[0].is_nan());
+        assert_points_eq(&scan.points(), &[(0.0, 1.0)]);
+    }
+
+    #[test]
+    fn point_cloud_keeps_intensities_of_valid_ranges() {
+        let scan = LaserScan::new(
+            "scan",
+            0.0,
+            FRAC_PI_2,
+            0.1,
+            10.0,
+            vec![1.0, f32::NAN, 20.0, 2.0],
+        )
+        .with_namespace("lidar")
+        .with_stamp(12.5)
+        .with_parent_frame_id("laser")
+        .with_intensities(vec![0.1, 0.2, 0.3, 0.4]);
+        let point_cloud = scan.to_point_cloud();
+        assert_eq!(point_cloud.id(), "scan");
+        assert_eq!(point_cloud.namespace(), "lidar");
+        assert_eq!(point_cloud.header(), scan.header());
+        assert_eq!(point_cloud.points().len(), 2);
+        assert!(point_cloud.points().iter().all(|point| point.2 == 0.0));
+        assert_eq!(point_cloud.intensities(), &Some(vec![0.1, 0.4]));
+        assert!(matches!(
+            point_cloud.coloring(),
+            PointColoring::Colormap {
+                channel: ColormapChannel::Intensity,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn point_cloud_ignores_intensities_of_wrong_length() {
+        let scan = LaserScan::new("scan", 0.0, FRAC_PI_2, 0.1, 10.0, vec![1.0, 2.0])
+            .with_color(Color::Blue)
+            .with_intensities(vec![0.1]);
+        let point_cloud = scan.to_point_cloud();
+        assert_eq!(point_cloud.intensities(), &None);
+        assert_eq!(point_cloud.coloring(), &PointColoring::Uniform);
+        assert_eq!(point_cloud.color(), &Color::Blue);
+    }
+}
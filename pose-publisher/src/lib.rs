@@ -1,14 +1,37 @@
 pub mod commands;
+pub mod header;
+pub mod laser_scan;
+pub mod lifetime;
+pub mod mcap;
 mod multicast;
+pub mod occupancy_grid;
+pub mod path;
 pub mod point_cloud;
 pub mod pose;
+pub mod recording;
+pub mod topics;
+pub mod transform;
 
-use commands::Command;
+use commands::{Command, CommandStatus};
+pub use header::Header;
+pub use laser_scan::LaserScan;
+pub use lifetime::{Heartbeat, Lifetime};
 use multicast::MulticastMessenger;
-pub use point_cloud::PointCloud2;
+pub use multicast::MAX_PAYLOAD_SIZE;
+pub use occupancy_grid::{OccupancyGrid, OccupancyGridMessage};
+pub use path::Path;
+pub use point_cloud::{PointCloud2, PointCloud3, PointCloud3Assembler};
 pub use pose::{ObjectPose, PoseClientUpdate};
-use std::{net::SocketAddrV4, sync::Arc};
+use std::{
+    net::SocketAddrV4,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 use thiserror::Error;
+pub use transform::{Transform, TransformTree, TransformUpdate};
 
 #[derive(Error, Debug)]
 pub enum PosePublisherError {
@@ -20,6 +43,15 @@ pub enum PosePublisherError {
     StringConversionError(#[from] std::str::Utf8Error),
     #[error("failed to parse json")]
     JsonParsingError,
+    #[error("invalid mcap file: {0}")]
+    McapError(String),
+    #[error(
+        "message of {0} bytes is larger than the maximum of {} bytes",
+        MAX_PAYLOAD_SIZE
+    )]
+    PayloadTooLarge(usize),
+    #[error("invalid occupancy grid: {0}")]
+    InvalidOccupancyGrid(String),
 }
 
 type Result<T> = std::result::Result<T, PosePublisherError>;
@@ -97,6 +129,62 @@ impl PointCloudSubscriber {
     }
 }
 
+pub struct PointCloud3Publisher {
+    messenger: MulticastMessenger,
+}
+
+impl PointCloud3Publisher {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    /// Large clouds are sent in several chunks
+    pub fn publish(&self, point_cloud: &PointCloud3) -> Result<()> {
+        for chunk in point_cloud.chunks() {
+            self.messenger.send(&chunk)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct PointCloud3Subscriber {
+    messenger: MulticastMessenger,
+    assembler: Mutex<PointCloud3Assembler>,
+}
+
+impl PointCloud3Subscriber {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self {
+            messenger,
+            assembler: Mutex::new(PointCloud3Assembler::new()),
+        })
+    }
+
+    pub fn new_blocking(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new_blocking(multicast_address)?;
+        Ok(Self {
+            messenger,
+            assembler: Mutex::new(PointCloud3Assembler::new()),
+        })
+    }
+
+    /// Next whole cloud. Chunks of large clouds are joined together
+    pub fn next(&self) -> Result<PointCloud3> {
+        loop {
+            let chunk = self.messenger.receive()?;
+            let mut assembler = self
+                .assembler
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            if let Some(point_cloud) = assembler.add(chunk) {
+                return Ok(point_cloud);
+            }
+        }
+    }
+}
+
 pub struct CommandPublisher {
     messenger: MulticastMessenger,
 }
@@ -132,3 +220,314 @@ impl CommandSubscriber {
         self.messenger.receive()
     }
 }
+
+pub struct CommandStatusPublisher {
+    messenger: MulticastMessenger,
+}
+
+impl CommandStatusPublisher {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn publish(&self, status: &CommandStatus) -> Result<()> {
+        self.messenger.send(status)?;
+        Ok(())
+    }
+}
+
+pub struct CommandStatusSubscriber {
+    messenger: MulticastMessenger,
+}
+
+impl CommandStatusSubscriber {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn new_blocking(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new_blocking(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn next(&self) -> Result<CommandStatus> {
+        self.messenger.receive()
+    }
+}
+
+pub struct PathPublisher {
+    messenger: MulticastMessenger,
+}
+
+impl PathPublisher {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn publish(&self, path: &Path) -> Result<()> {
+        self.messenger.send(path)?;
+        Ok(())
+    }
+}
+
+pub struct PathSubscriber {
+    messenger: MulticastMessenger,
+}
+
+impl PathSubscriber {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn new_blocking(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new_blocking(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn next(&self) -> Result<Path> {
+        self.messenger.receive()
+    }
+}
+
+pub struct LaserScanPublisher {
+    messenger: MulticastMessenger,
+}
+
+impl LaserScanPublisher {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn publish(&self, laser_scan: &LaserScan) -> Result<()> {
+        self.messenger.send(laser_scan)?;
+        Ok(())
+    }
+}
+
+pub struct LaserScanSubscriber {
+    messenger: MulticastMessenger,
+}
+
+impl LaserScanSubscriber {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn new_blocking(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new_blocking(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn next(&self) -> Result<LaserScan> {
+        self.messenger.receive()
+    }
+}
+
+pub struct OccupancyGridPublisher {
+    messenger: MulticastMessenger,
+}
+
+impl OccupancyGridPublisher {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    /// Send whole grid split into chunks
+    pub fn publish(&self, grid: &OccupancyGrid) -> Result<()> {
+        for message in grid.to_messages() {
+            self.messenger.send(&message)?;
+        }
+        Ok(())
+    }
+
+    /// Send only cells that changed since `previous`
+    pub fn publish_delta(&self, grid: &OccupancyGrid, previous: &OccupancyGrid) -> Result<()> {
+        for message in grid.delta_messages(previous) {
+            self.messenger.send(&message)?;
+        }
+        Ok(())
+    }
+
+    pub fn publish_message(&self, message: &OccupancyGridMessage) -> Result<()> {
+        self.messenger.send(message)?;
+        Ok(())
+    }
+}
+
+pub struct OccupancyGridSubscriber {
+    messenger: MulticastMessenger,
+}
+
+impl OccupancyGridSubscriber {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn new_blocking(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new_blocking(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn next(&self) -> Result<OccupancyGridMessage> {
+        self.messenger.receive()
+    }
+}
+
+/// Announces that a publisher is alive from a background thread until dropped
+///
+/// Entities published with [`HeartbeatPublisher::lifetime`] expire once heartbeats stop
+pub struct HeartbeatPublisher {
+    publisher_id: String,
+    running: Arc<AtomicBool>,
+    last_error: Arc<Mutex<Option<PosePublisherError>>>,
+}
+
+impl HeartbeatPublisher {
+    pub fn start(
+        multicast_address: SocketAddrV4,
+        publisher_id: &str,
+        period: Duration,
+    ) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_publisher_id = publisher_id.to_owned();
+        let thread_running = running.clone();
+        let last_error = Arc::new(Mutex::new(None));
+        let thread_last_error = last_error.clone();
+        std::thread::spawn(move || {
+            while thread_running.load(Ordering::Relaxed) {
+                let heartbeat = Heartbeat::new(&thread_publisher_id, period.as_secs_f32());
+                if let Err(error) = messenger.send(&heartbeat) {
+                    *thread_last_error.lock().unwrap() = Some(error);
+                }
+                std::thread::sleep(period);
+            }
+        });
+        Ok(Self {
+            publisher_id: publisher_id.to_owned(),
+            running,
+            last_error,
+        })
+    }
+
+    pub fn publisher_id(&self) -> &str {
+        &self.publisher_id
+    }
+
+    /// Error of the latest failed heartbeat since the last call. Publishing keeps retrying
+    pub fn take_error(&self) -> Option<PosePublisherError> {
+        self.last_error.lock().unwrap().take()
+    }
+
+    /// Lifetime tied to this publisher
+    pub fn lifetime(&self) -> Lifetime {
+        Lifetime::UntilPublisherGone(self.publisher_id.clone())
+    }
+}
+
+impl Drop for HeartbeatPublisher {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+pub struct HeartbeatSubscriber {
+    messenger: MulticastMessenger,
+}
+
+impl HeartbeatSubscriber {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn new_blocking(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new_blocking(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn next(&self) -> Result<Heartbeat> {
+        self.messenger.receive()
+    }
+}
+
+pub struct TransformPublisher {
+    messenger: MulticastMessenger,
+}
+
+impl TransformPublisher {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn publish(&self, update: &TransformUpdate) -> Result<()> {
+        self.messenger.send(update)?;
+        Ok(())
+    }
+}
+
+pub struct TransformSubscriber {
+    messenger: MulticastMessenger,
+}
+
+impl TransformSubscriber {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn new_blocking(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new_blocking(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn next(&self) -> Result<TransformUpdate> {
+        self.messenger.receive()
+    }
+}
+
+/// Publishes json payloads on any topic without knowing their type
+pub struct RawPublisher {
+    messenger: MulticastMessenger,
+}
+
+impl RawPublisher {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn publish(&self, payload: &str) -> Result<()> {
+        self.messenger.send_raw(payload)
+    }
+}
+
+/// Receives payloads of any topic as they were sent
+pub struct RawSubscriber {
+    messenger: MulticastMessenger,
+}
+
+impl RawSubscriber {
+    pub fn new(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn new_blocking(multicast_address: SocketAddrV4) -> Result<Self> {
+        let messenger = MulticastMessenger::new_blocking(multicast_address)?;
+        Ok(Self { messenger })
+    }
+
+    pub fn next(&self) -> Result<String> {
+        self.messenger.receive_raw()
+    }
+}
@@ -0,0 +1,275 @@
+use crate::header::Header;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// How long an entity stays visible after its last update
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub enum Lifetime {
+    /// Only removed by explicit deletion
+    Forever,
+    /// Seconds since last update
+    Duration(f32),
+    /// Until heartbeats from publisher with given id stop
+    UntilPublisherGone(String),
+}
+
+impl Lifetime {
+    /// Seconds of a duration lifetime, infinite for the others
+    pub fn timeout(&self) -> f32 {
+        match self {
+            Lifetime::Duration(seconds) => *seconds,
+            Lifetime::Forever | Lifetime::UntilPublisherGone(_) => f32::INFINITY,
+        }
+    }
+}
+
+/// Seconds an entity stays visible after its last update unless given a lifetime
+pub(crate) const DEFAULT_TIMEOUT: f32 = 5.;
+
+pub(crate) fn default_lifetime() -> Lifetime {
+    Lifetime::Duration(DEFAULT_TIMEOUT)
+}
+
+/// Builders and accessors for entities with `namespace` and `lifetime` fields
+macro_rules! entity_methods {
+    () => {
+        /// Group with other entities so that they can be deleted together
+        pub fn with_namespace(mut self, namespace: &str) -> Self {
+            self.namespace = namespace.to_owned();
+            self
+        }
+
+        /// Seconds after last update before the entity expires
+        pub fn with_timeout(self, timeout: f32) -> Self {
+            self.with_lifetime($crate::lifetime::Lifetime::Duration(timeout))
+        }
+
+        pub fn with_lifetime(mut self, lifetime: $crate::lifetime::Lifetime) -> Self {
+            self.lifetime = lifetime;
+            self
+        }
+
+        pub fn namespace(&self) -> &str {
+            &self.namespace
+        }
+
+        pub fn lifetime(&self) -> &$crate::lifetime::Lifetime {
+            &self.lifetime
+        }
+    };
+}
+
+pub(crate) use entity_methods;
+
+/// Older messages carry a `timeout` in seconds instead of a lifetime
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyLifetime {
+    Timeout(f32),
+    Lifetime(Lifetime),
+}
+
+/// Reads a lifetime or a legacy timeout in seconds
+pub(crate) fn deserialize_lifetime<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Lifetime, D::Error> {
+    Ok(match LegacyLifetime::deserialize(deserializer)? {
+        LegacyLifetime::Timeout(seconds) => Lifetime::Duration(seconds),
+        LegacyLifetime::Lifetime(lifetime) => lifetime,
+    })
+}
+
+/// Serde adapter for messages that older receivers read a `timeout` in seconds from
+///
+/// Writes the lifetime and its timeout. Reads the lifetime or else the timeout
+pub(crate) mod legacy_timeout {
+    use super::{default_lifetime, Lifetime, DEFAULT_TIMEOUT};
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    pub(crate) struct LifetimeWithTimeout {
+        #[serde(default)]
+        lifetime: Option<Lifetime>,
+        /// Seconds of a duration lifetime. Used if lifetime is missing
+        #[serde(default)]
+        timeout: Option<f32>,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        lifetime: &Lifetime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let timeout = match lifetime {
+            Lifetime::Duration(seconds) => *seconds,
+            Lifetime::Forever | Lifetime::UntilPublisherGone(_) => DEFAULT_TIMEOUT,
+        };
+        LifetimeWithTimeout {
+            lifetime: Some(lifetime.clone()),
+            timeout: Some(timeout),
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Lifetime, D::Error> {
+        let LifetimeWithTimeout { lifetime, timeout } =
+            LifetimeWithTimeout::deserialize(deserializer)?;
+        Ok(lifetime
+            .or_else(|| timeout.map(Lifetime::Duration))
+            .unwrap_or_else(default_lifetime))
+    }
+}
+
+/// Periodic message announcing that a publisher is alive
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct Heartbeat {
+    #[serde(default)]
+    header: Header,
+    publisher_id: String,
+    /// Seconds between heartbeats
+    period: f32,
+}
+
+impl Heartbeat {
+    pub fn new(publisher_id: &str, period: f32) -> Self {
+        Self {
+            header: Header::new(),
+            publisher_id: publisher_id.to_owned(),
+            period,
+        }
+    }
+
+    /// Seconds since unix epoch at which the heartbeat was sent
+    pub fn with_stamp(mut self, stamp: f64) -> Self {
+        self.header.stamp = stamp;
+        self
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn publisher_id(&self) -> &str {
+        &self.publisher_id
+    }
+
+    pub fn period(&self) -> f32 {
+        self.period
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point_cloud::{PointCloud2, PointCloud3};
+    use crate::pose::{ObjectPose, PoseClientUpdate};
+
+    fn lifetime_of(json: &str) -> Lifetime {
+        serde_json::from_str::<ObjectPose>(json)
+            .unwrap()
+            .effective_lifetime()
+    }
+
+    const POSE: &str = r#""id": "a", "pose": [0, 0, 0], "shape": {"Sphere": 0.1}, "color": "Red", "rotation": [0, 0, 0, 1]"#;
+
+    #[test]
+    fn legacy_timeout_becomes_duration() {
+        let lifetime = lifetime_of(&format!(r#"{{{}, "timeout": 2.5}}"#, POSE));
+        assert_eq!(lifetime, Lifetime::Duration(2.5));
+    }
+
+    #[test]
+    fn missing_lifetime_defaults_to_duration() {
+        let lifetime = lifetime_of(&format!("{{{}}}", POSE));
+        assert_eq!(lifetime, Lifetime::Duration(5.));
+    }
+
+    #[test]
+    fn lifetime_is_read() {
+        let lifetime = lifetime_of(&format!(r#"{{{}, "lifetime": "Forever"}}"#, POSE));
+        assert_eq!(lifetime, Lifetime::Forever);
+        let lifetime = lifetime_of(&format!(
+            r#"{{{}, "lifetime": {{"UntilPublisherGone": "robot"}}}}"#,
+            POSE
+        ));
+        assert_eq!(lifetime, Lifetime::UntilPublisherGone("robot".to_owned()));
+    }
+
+    #[test]
+    fn lifetime_round_trips() {
+        let json = format!(r#"{{{}, "lifetime": {{"Duration": 1.0}}}}"#, POSE);
+        let pose: ObjectPose = serde_json::from_str(&json).unwrap();
+        let json = serde_json::to_string(&pose).unwrap();
+        assert_eq!(lifetime_of(&json), Lifetime::Duration(1.));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn timeout_field_stays_readable_by_older_receivers() {
+        let mut update = PoseClientUpdate::new();
+        update.add("a", (0., 0., 0.)).with_timeout(2.);
+        update
+            .add("b", (0., 0., 0.))
+            .with_lifetime(Lifetime::Duration(3.));
+        update
+            .add("c", (0., 0., 0.))
+            .with_lifetime(Lifetime::Forever);
+        let json = serde_json::to_value(&update).unwrap();
+        let objects = json["objects"].as_array().unwrap();
+        assert_eq!(objects[0]["timeout"], 2.);
+        assert!(objects[0].get("lifetime").is_none());
+        assert_eq!(objects[1]["timeout"], 3.);
+        assert_eq!(objects[2]["timeout"], 5.);
+        assert_eq!(objects[2]["lifetime"], "Forever");
+        assert_eq!(update.updates()[2].effective_lifetime(), Lifetime::Forever);
+    }
+
+    #[test]
+    fn cloud_timeout_field_stays_readable_by_older_receivers() {
+        let clouds = [
+            PointCloud2::from_points("a", vec![]).with_timeout(2.),
+            PointCloud2::from_points("b", vec![]).with_lifetime(Lifetime::Forever),
+        ];
+        let json = serde_json::to_value(&clouds).unwrap();
+        assert_eq!(json[0]["timeout"], 2.);
+        assert_eq!(json[0]["lifetime"]["Duration"], 2.);
+        assert_eq!(json[1]["timeout"], 5.);
+        assert_eq!(json[1]["lifetime"], "Forever");
+
+        let json =
+            serde_json::to_value(PointCloud3::from_points("c", vec![]).with_timeout(3.)).unwrap();
+        assert_eq!(json["timeout"], 3.);
+    }
+
+    #[test]
+    fn cloud_lifetime_is_read_before_legacy_timeout() {
+        let cloud = |fields: &str| {
+            serde_json::from_str::<PointCloud2>(&format!(
+                r#"{{"id": "a", "points": [], "color": "Red"{}}}"#,
+                fields
+            ))
+            .unwrap()
+            .lifetime()
+            .clone()
+        };
+        assert_eq!(cloud(r#", "timeout": 2.5"#), Lifetime::Duration(2.5));
+        assert_eq!(
+            cloud(r#", "timeout": 5.0, "lifetime": "Forever""#),
+            Lifetime::Forever
+        );
+        assert_eq!(cloud(""), Lifetime::Duration(5.));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn timeout_field_is_used_without_lifetime() {
+        let mut update = PoseClientUpdate::new();
+        update.add("a", (0., 0., 0.)).timeout = 7.;
+        assert_eq!(
+            update.updates()[0].effective_lifetime(),
+            Lifetime::Duration(7.)
+        );
+    }
+}
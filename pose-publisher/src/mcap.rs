@@ -0,0 +1,817 @@
+//! Conversion of recorded topics to and from [MCAP](https://mcap.dev) files
+//!
+//! Messages are stored as json with a json schema per channel so that Foxglove Studio
+//! and other MCAP tooling can read them. Only uncompressed chunks are supported when reading
+use crate::{
+    commands::{Command, CommandStatus},
+    recording::{LogEntry, LogMessage, Topic},
+    Heartbeat, LaserScan, OccupancyGridMessage, Path, PointCloud2, PointCloud3, PoseClientUpdate,
+    PosePublisherError, Result, TransformUpdate,
+};
+use crc32fast::Hasher;
+use schemars::{schema::RootSchema, schema_for};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{ErrorKind, Read, Write},
+};
+
+const MAGIC: &[u8; 8] = b"\x89MCAP0\r\n";
+
+const OP_HEADER: u8 = 0x01;
+const OP_FOOTER: u8 = 0x02;
+const OP_SCHEMA: u8 = 0x03;
+const OP_CHANNEL: u8 = 0x04;
+const OP_MESSAGE: u8 = 0x05;
+const OP_CHUNK: u8 = 0x06;
+const OP_MESSAGE_INDEX: u8 = 0x07;
+const OP_CHUNK_INDEX: u8 = 0x08;
+const OP_STATISTICS: u8 = 0x0B;
+const OP_SUMMARY_OFFSET: u8 = 0x0E;
+const OP_DATA_END: u8 = 0x0F;
+
+/// Uncompressed size at which a chunk is written
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+const NANOS_PER_SECOND: f64 = 1e9;
+
+fn mcap_error(message: &str) -> PosePublisherError {
+    PosePublisherError::McapError(message.to_owned())
+}
+
+/// Channel topic of a rustviz topic
+fn channel_topic(topic: Topic) -> String {
+    format!("/{}", topic.name())
+}
+
+/// Named like [`crate::topics::validate`] so that every recordable topic has a schema
+fn schema(topic: Topic) -> (&'static str, RootSchema) {
+    match topic.name() {
+        "pose" => ("rustviz.PoseClientUpdate", schema_for!(PoseClientUpdate)),
+        "point_cloud" => ("rustviz.PointCloud2", schema_for!(PointCloud2)),
+        "command" => ("rustviz.Command", schema_for!(Command)),
+        "path" => ("rustviz.Path", schema_for!(Path)),
+        "point_cloud_3d" => ("rustviz.PointCloud3", schema_for!(PointCloud3)),
+        "laser_scan" => ("rustviz.LaserScan", schema_for!(LaserScan)),
+        "occupancy_grid" => (
+            "rustviz.OccupancyGridMessage",
+            schema_for!(OccupancyGridMessage),
+        ),
+        "heartbeat" => ("rustviz.Heartbeat", schema_for!(Heartbeat)),
+        "transform" => ("rustviz.TransformUpdate", schema_for!(TransformUpdate)),
+        "command_status" => ("rustviz.CommandStatus", schema_for!(CommandStatus)),
+        _ => ("rustviz.Json", schema_for!(Value)),
+    }
+}
+
+/// Message as sent on its topic, without the topic tag of the log
+fn message_data(message: &LogMessage) -> Result<Vec<u8>> {
+    let mut tagged =
+        serde_json::to_value(message).map_err(|_| PosePublisherError::JsonParsingError)?;
+    serde_json::to_vec(&tagged["message"].take()).map_err(|_| PosePublisherError::JsonParsingError)
+}
+
+/// Reads a message as sent on topic
+fn message_from_data(topic: Topic, data: &[u8]) -> Result<LogMessage> {
+    let message: Value =
+        serde_json::from_slice(data).map_err(|_| PosePublisherError::JsonParsingError)?;
+    serde_json::from_value(json!({ "topic": topic, "message": message }))
+        .map_err(|_| PosePublisherError::JsonParsingError)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut hasher = Hasher::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+fn to_nanos(seconds: f64) -> u64 {
+    (seconds.max(0.) * NANOS_PER_SECOND) as u64
+}
+
+/// Builds record content in MCAP field encoding
+#[derive(Default, Clone)]
+struct RecordBuilder {
+    content: Vec<u8>,
+}
+
+impl RecordBuilder {
+    fn u8(mut self, value: u8) -> Self {
+        self.content.push(value);
+        self
+    }
+
+    fn u16(mut self, value: u16) -> Self {
+        self.content.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u32(mut self, value: u32) -> Self {
+        self.content.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u64(mut self, value: u64) -> Self {
+        self.content.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Length prefixed bytes
+    fn bytes(self, value: &[u8]) -> Self {
+        let mut builder = self.u32(value.len() as u32);
+        builder.content.extend_from_slice(value);
+        builder
+    }
+
+    fn string(self, value: &str) -> Self {
+        self.bytes(value.as_bytes())
+    }
+
+    /// Bytes without length prefix. Only allowed as last field
+    fn rest(mut self, value: &[u8]) -> Self {
+        self.content.extend_from_slice(value);
+        self
+    }
+
+    fn write<W: Write>(self, writer: &mut W, opcode: u8) -> Result<()> {
+        writer.write_all(&[opcode])?;
+        writer.write_all(&(self.content.len() as u64).to_le_bytes())?;
+        writer.write_all(&self.content)?;
+        Ok(())
+    }
+}
+
+/// Counts bytes and keeps a checksum of everything written since the last reset
+struct ChecksumWriter<W: Write> {
+    writer: W,
+    offset: u64,
+    crc: Hasher,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    /// Checksum of bytes written since the last call
+    fn take_crc(&mut self) -> u32 {
+        std::mem::take(&mut self.crc).finalize()
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.crc.update(&buf[..written]);
+        self.offset += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Messages collected for the next chunk
+#[derive(Default)]
+struct ChunkBuilder {
+    records: Vec<u8>,
+    start_time: u64,
+    end_time: u64,
+    /// Log time and offset within the chunk of messages per channel
+    message_indexes: BTreeMap<u16, Vec<(u64, u64)>>,
+}
+
+impl ChunkBuilder {
+    fn add_message(&mut self, channel_id: u16, log_time: u64, record: RecordBuilder) -> Result<()> {
+        if self.records.is_empty() {
+            self.start_time = log_time;
+            self.end_time = log_time;
+        }
+        self.start_time = self.start_time.min(log_time);
+        self.end_time = self.end_time.max(log_time);
+        self.message_indexes
+            .entry(channel_id)
+            .or_default()
+            .push((log_time, self.records.len() as u64));
+        record.write(&mut self.records, OP_MESSAGE)
+    }
+}
+
+/// Writes log entries as a chunked MCAP file with one channel per topic
+///
+/// Chunks are uncompressed. The summary repeats schemas and channels and holds statistics
+/// and a chunk index so that readers can seek without scanning the file
+pub struct McapWriter<W: Write> {
+    writer: ChecksumWriter<W>,
+    channels: HashMap<Topic, u16>,
+    /// Schema and channel records to repeat in the summary
+    summary_schemas: Vec<RecordBuilder>,
+    summary_channels: Vec<RecordBuilder>,
+    chunk: ChunkBuilder,
+    chunk_indexes: Vec<RecordBuilder>,
+    message_count: u64,
+    channel_message_counts: BTreeMap<u16, u64>,
+    /// Log time range of all messages
+    time_range: Option<(u64, u64)>,
+    sequence: u32,
+}
+
+impl<W: Write> McapWriter<W> {
+    pub fn new(writer: W) -> Result<Self> {
+        let mut writer = ChecksumWriter {
+            writer,
+            offset: 0,
+            crc: Hasher::new(),
+        };
+        writer.write_all(MAGIC)?;
+        RecordBuilder::default()
+            .string("")
+            .string("rustviz")
+            .write(&mut writer, OP_HEADER)?;
+        Ok(Self {
+            writer,
+            channels: HashMap::new(),
+            summary_schemas: vec![],
+            summary_channels: vec![],
+            chunk: ChunkBuilder::default(),
+            chunk_indexes: vec![],
+            message_count: 0,
+            channel_message_counts: BTreeMap::new(),
+            time_range: None,
+            sequence: 0,
+        })
+    }
+
+    /// Schema and channel are written before the first chunk with a message of the topic
+    fn channel(&mut self, topic: Topic) -> Result<u16> {
+        if let Some(channel_id) = self.channels.get(&topic) {
+            return Ok(*channel_id);
+        }
+        // zero is not a valid schema id
+        let id = self.channels.len() as u16 + 1;
+        let (name, schema) = schema(topic);
+        let schema =
+            serde_json::to_vec(&schema).map_err(|_| PosePublisherError::JsonParsingError)?;
+        let schema = RecordBuilder::default()
+            .u16(id)
+            .string(name)
+            .string("jsonschema")
+            .bytes(&schema);
+        let channel = RecordBuilder::default()
+            .u16(id)
+            .u16(id)
+            .string(&channel_topic(topic))
+            .string("json")
+            // empty metadata map
+            .u32(0);
+        schema.clone().write(&mut self.writer, OP_SCHEMA)?;
+        channel.clone().write(&mut self.writer, OP_CHANNEL)?;
+        self.summary_schemas.push(schema);
+        self.summary_channels.push(channel);
+        self.channels.insert(topic, id);
+        Ok(id)
+    }
+
+    /// Log time is the receive time. Publish time is the header stamp if the message has one
+    pub fn write(&mut self, entry: &LogEntry) -> Result<()> {
+        let channel_id = self.channel(entry.message.topic())?;
+        let data = message_data(&entry.message)?;
+        let header = entry.message.header();
+        let publish_time = if header.has_stamp() {
+            header.stamp
+        } else {
+            entry.received
+        };
+        let log_time = to_nanos(entry.received);
+        let record = RecordBuilder::default()
+            .u16(channel_id)
+            .u32(self.sequence)
+            .u64(log_time)
+            .u64(to_nanos(publish_time))
+            .rest(&data);
+        self.chunk.add_message(channel_id, log_time, record)?;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.message_count += 1;
+        *self.channel_message_counts.entry(channel_id).or_default() += 1;
+        self.time_range = Some(match self.time_range {
+            Some((start, end)) => (start.min(log_time), end.max(log_time)),
+            None => (log_time, log_time),
+        });
+        if self.chunk.records.len() >= CHUNK_SIZE {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Writes collected messages as a chunk followed by its message indexes
+    fn flush_chunk(&mut self) -> Result<()> {
+        if self.chunk.records.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::take(&mut self.chunk);
+        let chunk_start = self.writer.offset;
+        RecordBuilder::default()
+            .u64(chunk.start_time)
+            .u64(chunk.end_time)
+            .u64(chunk.records.len() as u64)
+            .u32(crc32(&chunk.records))
+            // uncompressed
+            .string("")
+            .u64(chunk.records.len() as u64)
+            .rest(&chunk.records)
+            .write(&mut self.writer, OP_CHUNK)?;
+        let message_index_start = self.writer.offset;
+        let mut message_index_offsets = RecordBuilder::default();
+        for (channel_id, messages) in &chunk.message_indexes {
+            message_index_offsets = message_index_offsets
+                .u16(*channel_id)
+                .u64(self.writer.offset);
+            let mut entries = RecordBuilder::default();
+            for (log_time, offset) in messages {
+                entries = entries.u64(*log_time).u64(*offset);
+            }
+            RecordBuilder::default()
+                .u16(*channel_id)
+                .bytes(&entries.content)
+                .write(&mut self.writer, OP_MESSAGE_INDEX)?;
+        }
+        self.chunk_indexes.push(
+            RecordBuilder::default()
+                .u64(chunk.start_time)
+                .u64(chunk.end_time)
+                .u64(chunk_start)
+                .u64(message_index_start - chunk_start)
+                .bytes(&message_index_offsets.content)
+                .u64(self.writer.offset - message_index_start)
+                .string("")
+                .u64(chunk.records.len() as u64)
+                .u64(chunk.records.len() as u64),
+        );
+        Ok(())
+    }
+
+    /// Writes the summary with a checksum of the data section and the summary
+    pub fn finish(mut self) -> Result<W> {
+        self.flush_chunk()?;
+        // data section checksum covers everything from the magic up to data end
+        let data_section_crc = self.writer.take_crc();
+        RecordBuilder::default()
+            .u32(data_section_crc)
+            .write(&mut self.writer, OP_DATA_END)?;
+
+        let summary_start = self.writer.offset;
+        self.writer.take_crc();
+        let (start_time, end_time) = self.time_range.unwrap_or((0, 0));
+        let mut channel_message_counts = RecordBuilder::default();
+        for (channel_id, count) in &self.channel_message_counts {
+            channel_message_counts = channel_message_counts.u16(*channel_id).u64(*count);
+        }
+        let statistics = RecordBuilder::default()
+            .u64(self.message_count)
+            .u16(self.summary_schemas.len() as u16)
+            .u32(self.summary_channels.len() as u32)
+            // attachments and metadata
+            .u32(0)
+            .u32(0)
+            .u32(self.chunk_indexes.len() as u32)
+            .u64(start_time)
+            .u64(end_time)
+            .bytes(&channel_message_counts.content);
+        let groups = vec![
+            (OP_SCHEMA, std::mem::take(&mut self.summary_schemas)),
+            (OP_CHANNEL, std::mem::take(&mut self.summary_channels)),
+            (OP_STATISTICS, vec![statistics]),
+            (OP_CHUNK_INDEX, std::mem::take(&mut self.chunk_indexes)),
+        ];
+        let mut summary_offsets = vec![];
+        for (opcode, records) in groups {
+            if records.is_empty() {
+                continue;
+            }
+            let group_start = self.writer.offset;
+            for record in records {
+                record.write(&mut self.writer, opcode)?;
+            }
+            summary_offsets.push((opcode, group_start, self.writer.offset - group_start));
+        }
+        let summary_offset_start = self.writer.offset;
+        for (opcode, group_start, group_length) in summary_offsets {
+            RecordBuilder::default()
+                .u8(opcode)
+                .u64(group_start)
+                .u64(group_length)
+                .write(&mut self.writer, OP_SUMMARY_OFFSET)?;
+        }
+        // summary checksum includes the footer up to the checksum itself
+        self.writer.write_all(&[OP_FOOTER])?;
+        self.writer.write_all(&20_u64.to_le_bytes())?;
+        self.writer.write_all(&summary_start.to_le_bytes())?;
+        self.writer.write_all(&summary_offset_start.to_le_bytes())?;
+        let summary_crc = self.writer.take_crc();
+        self.writer.write_all(&summary_crc.to_le_bytes())?;
+        self.writer.write_all(MAGIC)?;
+        self.writer.flush()?;
+        Ok(self.writer.writer)
+    }
+}
+
+/// Reads fields of a single record
+struct RecordParser<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RecordParser<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(mcap_error("truncated record"));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        Ok(std::str::from_utf8(self.bytes()?)?)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.data)
+    }
+}
+
+/// Reads rustviz topics from an MCAP file. Channels of other topics are skipped
+pub struct McapReader<R: Read> {
+    reader: R,
+    /// Records of the chunk being read and position in it
+    chunk: Vec<u8>,
+    chunk_offset: usize,
+    /// Channel id to topic and message encoding
+    channels: HashMap<u16, (Topic, String)>,
+    finished: bool,
+    skipped: usize,
+}
+
+impl<R: Read> McapReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(mcap_error("not an mcap file"));
+        }
+        Ok(Self {
+            reader,
+            chunk: vec![],
+            chunk_offset: 0,
+            channels: HashMap::new(),
+            finished: false,
+            skipped: 0,
+        })
+    }
+
+    /// Number of messages skipped so far because they could not be parsed
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    fn next_record(&mut self) -> Result<Option<(u8, Vec<u8>)>> {
+        if self.chunk_offset < self.chunk.len() {
+            let mut parser = RecordParser {
+                data: &self.chunk[self.chunk_offset..],
+            };
+            let opcode = parser.take(1)?[0];
+            let len = parser.u64()? as usize;
+            let content = parser.take(len)?.to_vec();
+            self.chunk_offset += 9 + len;
+            return Ok(Some((opcode, content)));
+        }
+        if self.finished {
+            return Ok(None);
+        }
+        let mut opcode = [0; 1];
+        match self.reader.read_exact(&mut opcode) {
+            Ok(()) => (),
+            // files of interrupted recordings end without footer
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error.into()),
+        }
+        let mut len = [0; 8];
+        self.reader.read_exact(&mut len)?;
+        let mut content = vec![];
+        (&mut self.reader)
+            .take(u64::from_le_bytes(len))
+            .read_to_end(&mut content)?;
+        Ok(Some((opcode[0], content)))
+    }
+
+    pub fn next_entry(&mut self) -> Result<Option<LogEntry>> {
+        while let Some((opcode, content)) = self.next_record()? {
+            let mut parser = RecordParser { data: &content };
+            match opcode {
+                OP_CHANNEL => {
+                    let id = parser.u16()?;
+                    let _schema_id = parser.u16()?;
+                    let topic = parser.string()?;
+                    let encoding = parser.string()?;
+                    if let Ok(topic) = topic.trim_start_matches('/').parse() {
+                        self.channels.insert(id, (topic, encoding.to_owned()));
+                    }
+                }
+                OP_CHUNK => {
+                    let _start_time = parser.u64()?;
+                    let _end_time = parser.u64()?;
+                    let _uncompressed_size = parser.u64()?;
+                    let uncompressed_crc = parser.u32()?;
+                    let compression = parser.string()?;
+                    if !compression.is_empty() {
+                        return Err(PosePublisherError::McapError(format!(
+                            "unsupported chunk compression {}. Only uncompressed chunks can be read",
+                            compression
+                        )));
+                    }
+                    let len = parser.u64()? as usize;
+                    let records = parser.take(len)?;
+                    // zero means the writer did not calculate a checksum
+                    if uncompressed_crc != 0 && crc32(records) != uncompressed_crc {
+                        return Err(mcap_error("chunk checksum mismatch"));
+                    }
+                    self.chunk = records.to_vec();
+                    self.chunk_offset = 0;
+                }
+                OP_MESSAGE => {
+                    let channel_id = parser.u16()?;
+                    let _sequence = parser.u32()?;
+                    let log_time = parser.u64()?;
+                    let _publish_time = parser.u64()?;
+                    let data = parser.rest();
+                    let (topic, encoding) = match self.channels.get(&channel_id) {
+                        Some(channel) => channel,
+                        None => continue,
+                    };
+                    if encoding != "json" {
+                        return Err(PosePublisherError::McapError(format!(
+                            "unsupported message encoding {} on {}",
+                            encoding,
+                            channel_topic(*topic)
+                        )));
+                    }
+                    match message_from_data(*topic, data) {
+                        Ok(message) => {
+                            return Ok(Some(LogEntry {
+                                received: log_time as f64 / NANOS_PER_SECOND,
+                                message,
+                            }))
+                        }
+                        // one malformed message doesn't spoil the rest of the file
+                        Err(_) => self.skipped += 1,
+                    }
+                }
+                OP_FOOTER => self.finished = true,
+                // header, schemas, indexes and summary are not needed for reading in order
+                _ => (),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stamps are exact in binary so that they survive the json round trip
+    fn entries() -> Vec<LogEntry> {
+        let mut update = PoseClientUpdate::new();
+        update.add("robot", (1., 2., 3.));
+        update.set_stamp(99.5);
+        vec![
+            LogEntry {
+                received: 100.,
+                message: LogMessage::Pose(update),
+            },
+            LogEntry {
+                received: 100.25,
+                message: LogMessage::PointCloud(
+                    PointCloud2::from_points("cloud", vec![(0., 1.), (2., 3.)]).with_stamp(100.125),
+                ),
+            },
+            LogEntry {
+                received: 101.,
+                message: LogMessage::Command(Command::goal(7, (1., 2.), 0.5).with_stamp(100.75)),
+            },
+        ]
+    }
+
+    fn write(entries: &[LogEntry]) -> McapWriter<Vec<u8>> {
+        let mut writer = McapWriter::new(vec![]).unwrap();
+        for entry in entries {
+            writer.write(entry).unwrap();
+        }
+        writer
+    }
+
+    fn read_all(data: &[u8]) -> (Vec<LogEntry>, usize) {
+        let mut reader = McapReader::new(data).unwrap();
+        let entries = std::iter::from_fn(|| reader.next_entry().unwrap()).collect();
+        (entries, reader.skipped())
+    }
+
+    fn assert_same(read: &[LogEntry], written: &[LogEntry]) {
+        assert_eq!(read.len(), written.len());
+        for (read, written) in read.iter().zip(written) {
+            assert!((read.received - written.received).abs() < 1e-6);
+            assert_eq!(
+                serde_json::to_value(&read.message).unwrap(),
+                serde_json::to_value(&written.message).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn written_entries_are_read_back() {
+        let written = entries();
+        let data = write(&written).finish().unwrap();
+        let (read, skipped) = read_all(&data);
+        assert_same(&read, &written);
+        assert_eq!(skipped, 0);
+    }
+
+    #[test]
+    fn file_without_summary_is_read() {
+        let written = entries();
+        let mut writer = write(&written);
+        writer.flush_chunk().unwrap();
+        let (read, _) = read_all(&writer.writer.writer);
+        assert_same(&read, &written);
+    }
+
+    fn u64_at(data: &[u8], offset: usize) -> u64 {
+        let mut parser = RecordParser {
+            data: &data[offset..],
+        };
+        parser.u64().unwrap()
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        let mut parser = RecordParser {
+            data: &data[offset..],
+        };
+        parser.u32().unwrap()
+    }
+
+    /// Opcodes and content of records between start and end
+    fn records(data: &[u8], start: usize, end: usize) -> Vec<(u8, &[u8])> {
+        let mut parser = RecordParser {
+            data: &data[start..end],
+        };
+        let mut records = vec![];
+        while !parser.data.is_empty() {
+            let opcode = parser.take(1).unwrap()[0];
+            let len = parser.u64().unwrap() as usize;
+            records.push((opcode, parser.take(len).unwrap()));
+        }
+        records
+    }
+
+    #[test]
+    fn summary_indexes_chunks_and_has_checksums() {
+        let data = write(&entries()).finish().unwrap();
+        assert_eq!(&data[data.len() - 8..], MAGIC);
+        // footer is opcode, length and 20 bytes of content
+        let footer = data.len() - 8 - 29;
+        assert_eq!(data[footer], OP_FOOTER);
+        let summary_start = u64_at(&data, footer + 9) as usize;
+        let summary_offset_start = u64_at(&data, footer + 17) as usize;
+        let summary_crc = u32_at(&data, footer + 25);
+        assert_eq!(summary_crc, crc32(&data[summary_start..footer + 25]));
+
+        // data end record holds the checksum of everything before it
+        let data_end = summary_start - 13;
+        assert_eq!(data[data_end], OP_DATA_END);
+        assert_eq!(u32_at(&data, data_end + 9), crc32(&data[..data_end]));
+
+        let summary = records(&data, summary_start, summary_offset_start);
+        let opcodes: Vec<_> = summary.iter().map(|(opcode, _)| *opcode).collect();
+        assert_eq!(
+            opcodes,
+            vec![
+                OP_SCHEMA,
+                OP_SCHEMA,
+                OP_SCHEMA,
+                OP_CHANNEL,
+                OP_CHANNEL,
+                OP_CHANNEL,
+                OP_STATISTICS,
+                OP_CHUNK_INDEX
+            ]
+        );
+        let (_, statistics) = summary[6];
+        assert_eq!(u64_at(statistics, 0), 3);
+        let (_, chunk_index) = summary[7];
+        let chunk_start = u64_at(chunk_index, 16) as usize;
+        let chunk_length = u64_at(chunk_index, 24) as usize;
+        let chunk = records(&data, chunk_start, chunk_start + chunk_length);
+        assert_eq!(chunk[0].0, OP_CHUNK);
+        let uncompressed_crc = u32_at(chunk[0].1, 24);
+        let records_start = chunk_start + 9 + 24 + 4 + 4 + 8;
+        assert_eq!(
+            uncompressed_crc,
+            crc32(&data[records_start..chunk_start + chunk_length])
+        );
+
+        let offsets = records(&data, summary_offset_start, footer);
+        assert_eq!(offsets.len(), 4);
+        assert!(offsets
+            .iter()
+            .all(|(opcode, _)| *opcode == OP_SUMMARY_OFFSET));
+    }
+
+    fn file_with_chunk(compression: &str, records: &[u8], crc: u32) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        RecordBuilder::default()
+            .u64(0)
+            .u64(0)
+            .u64(records.len() as u64)
+            .u32(crc)
+            .string(compression)
+            .u64(records.len() as u64)
+            .rest(records)
+            .write(&mut data, OP_CHUNK)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn compressed_chunks_are_rejected() {
+        let data = file_with_chunk("zstd", b"compressed", 0);
+        let mut reader = McapReader::new(&data[..]).unwrap();
+        match reader.next_entry() {
+            Err(PosePublisherError::McapError(message)) => assert!(message.contains("zstd")),
+            other => panic!("expected compression error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn corrupted_chunks_are_rejected() {
+        let data = file_with_chunk("", b"records", crc32(b"other records"));
+        let mut reader = McapReader::new(&data[..]).unwrap();
+        assert!(reader.next_entry().is_err());
+    }
+
+    #[test]
+    fn malformed_message_is_skipped() {
+        let written = entries();
+        let mut writer = write(&written[..1]);
+        let channel_id = writer.channel(Topic::Pose).unwrap();
+        let record = RecordBuilder::default()
+            .u16(channel_id)
+            .u32(1)
+            .u64(0)
+            .u64(0)
+            .rest(b"{not json");
+        writer.chunk.add_message(channel_id, 0, record).unwrap();
+        for entry in &written[1..] {
+            writer.write(entry).unwrap();
+        }
+        let (read, skipped) = read_all(&writer.finish().unwrap());
+        assert_same(&read, &written);
+        assert_eq!(skipped, 1);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(McapReader::new(&b"not an mcap file"[..]).is_err());
+    }
+
+    #[test]
+    fn messages_are_stored_as_sent_on_their_topic() {
+        for entry in entries() {
+            let topic = entry.message.topic();
+            let data = message_data(&entry.message).unwrap();
+            let payload = std::str::from_utf8(&data).unwrap();
+            assert!(crate::topics::validate(topic.name(), payload).is_ok());
+            assert!(!payload.contains("\"topic\""));
+            let message = message_from_data(topic, &data).unwrap();
+            assert_eq!(message.topic(), topic);
+        }
+    }
+}
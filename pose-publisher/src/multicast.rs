@@ -24,6 +24,8 @@ fn bind_multicast(
 }
 
 const ALL_INTERFACES: [u8; 4] = [0, 0, 0, 0];
+/// Size of the receive buffer. Larger messages can't be received
+pub const MAX_PAYLOAD_SIZE: usize = 65000;
 
 pub struct MulticastMessenger {
     socket: UdpSocket,
@@ -54,15 +56,26 @@ impl MulticastMessenger {
 
     pub fn send<T: Serialize>(&self, message: &T) -> Result<()> {
         let payload = serde_json::to_string(message).unwrap();
+        self.send_raw(&payload)
+    }
+
+    pub fn send_raw(&self, payload: &str) -> Result<()> {
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(PosePublisherError::PayloadTooLarge(payload.len()));
+        }
         self.socket
             .send_to(payload.as_bytes(), self.multicast_address)?;
         Ok(())
     }
 
     pub fn receive<T: DeserializeOwned>(&self) -> Result<T> {
-        let mut buf = [0; 65000];
+        let payload = self.receive_raw()?;
+        serde_json::from_str::<T>(&payload).map_err(|_| PosePublisherError::JsonParsingError)
+    }
+
+    pub fn receive_raw(&self) -> Result<String> {
+        let mut buf = [0; MAX_PAYLOAD_SIZE];
         let len = self.socket.recv(&mut buf)?;
-        let payload = str::from_utf8(&buf[..len])?;
-        serde_json::from_str::<T>(payload).map_err(|_| PosePublisherError::JsonParsingError)
+        Ok(str::from_utf8(&buf[..len])?.to_owned())
     }
 }
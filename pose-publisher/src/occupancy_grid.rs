@@ -0,0 +1,540 @@
+use crate::header::Header;
+use crate::lifetime::Lifetime;
+use crate::pose::Quaternion;
+use crate::{PosePublisherError, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_TIMEOUT: f32 = 60.;
+const IDENTITY_QUATERNION: Quaternion = (0., 0., 0., 1.);
+/// Upper bound of cells in a single patch so that messages fit into one datagram
+pub const MAX_PATCH_CELLS: usize = 12_000;
+pub const UNKNOWN_CELL: i8 = -1;
+/// Larger grids are rejected so that a bad message can't exhaust memory
+pub const MAX_GRID_CELLS: usize = 8192 * 8192;
+
+fn default_lifetime() -> Lifetime {
+    Lifetime::Duration(DEFAULT_TIMEOUT)
+}
+
+/// Grid metadata. Cell (0, 0) is located at origin and x grows along the origin's x axis
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct OccupancyGridInfo {
+    id: String,
+    #[serde(default)]
+    header: Header,
+    /// Meters per cell
+    resolution: f32,
+    width: u32,
+    height: u32,
+    origin: (f32, f32, f32),
+    origin_rotation: Quaternion,
+    #[serde(
+        alias = "timeout",
+        default = "default_lifetime",
+        deserialize_with = "crate::lifetime::deserialize_lifetime"
+    )]
+    lifetime: Lifetime,
+}
+
+impl OccupancyGridInfo {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn parent_frame_id(&self) -> &Option<String> {
+        &self.header.frame_id
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn resolution(&self) -> f32 {
+        self.resolution
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn origin(&self) -> (f32, f32, f32) {
+        self.origin
+    }
+
+    /// in form (x, y, z, w)
+    pub fn origin_rotation(&self) -> Quaternion {
+        self.origin_rotation
+    }
+
+    pub fn lifetime(&self) -> &Lifetime {
+        &self.lifetime
+    }
+
+    /// Number of cells. None if the grid is larger than [`MAX_GRID_CELLS`]
+    pub fn cell_count(&self) -> Option<usize> {
+        (self.width as usize)
+            .checked_mul(self.height as usize)
+            .filter(|cells| *cells <= MAX_GRID_CELLS)
+    }
+
+    /// Whether grids described by both infos share the same cell layout
+    pub fn same_layout(&self, other: &OccupancyGridInfo) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && (self.resolution - other.resolution).abs() < f32::EPSILON
+    }
+}
+
+/// Rectangular region of cell values in row major order
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct OccupancyGridPatch {
+    id: String,
+    #[serde(default)]
+    header: Header,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    data: Vec<i8>,
+}
+
+impl OccupancyGridPatch {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &Vec<i8> {
+        &self.data
+    }
+
+    /// Whether data holds exactly one value per cell
+    pub fn is_valid(&self) -> bool {
+        (self.width as usize).checked_mul(self.height as usize) == Some(self.data.len())
+    }
+}
+
+/// Grids are sent as an info message followed by patches
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub enum OccupancyGridMessage {
+    Info(OccupancyGridInfo),
+    Patch(OccupancyGridPatch),
+}
+
+impl OccupancyGridMessage {
+    pub fn id(&self) -> &str {
+        match self {
+            OccupancyGridMessage::Info(info) => info.id(),
+            OccupancyGridMessage::Patch(patch) => patch.id(),
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        match self {
+            OccupancyGridMessage::Info(info) => info.header(),
+            OccupancyGridMessage::Patch(patch) => patch.header(),
+        }
+    }
+
+    /// Seconds since unix epoch at which the grid was captured
+    pub fn with_stamp(mut self, stamp: f64) -> Self {
+        match &mut self {
+            OccupancyGridMessage::Info(info) => info.header.stamp = stamp,
+            OccupancyGridMessage::Patch(patch) => patch.header.stamp = stamp,
+        }
+        self
+    }
+}
+
+/// 2D occupancy map
+///
+/// Cell values are occupancy probability in range 0 -> 100 or -1 for unknown
+#[derive(Debug, Clone)]
+pub struct OccupancyGrid {
+    info: OccupancyGridInfo,
+    data: Vec<i8>,
+}
+
+impl OccupancyGrid {
+    /// Creates grid with all cells unknown
+    ///
+    /// Panics if the grid has more than [`MAX_GRID_CELLS`] cells
+    pub fn new(id: &str, resolution: f32, width: u32, height: u32) -> Self {
+        Self::from_info(OccupancyGridInfo {
+            id: id.to_owned(),
+            header: Header::new(),
+            resolution,
+            width,
+            height,
+            origin: (0., 0., 0.),
+            origin_rotation: IDENTITY_QUATERNION,
+            lifetime: Lifetime::Duration(DEFAULT_TIMEOUT),
+        })
+        .expect("occupancy grid is too large")
+    }
+
+    /// Creates grid with all cells unknown. Fails if the grid is too large
+    pub fn from_info(info: OccupancyGridInfo) -> Result<Self> {
+        let cells = info.cell_count().ok_or_else(|| {
+            PosePublisherError::InvalidOccupancyGrid(format!(
+                "{} x {} cells is more than the maximum of {}",
+                info.width, info.height, MAX_GRID_CELLS
+            ))
+        })?;
+        Ok(Self {
+            info,
+            data: vec![UNKNOWN_CELL; cells],
+        })
+    }
+
+    /// Seconds after last update before the grid expires
+    pub fn with_timeout(self, timeout: f32) -> Self {
+        self.with_lifetime(Lifetime::Duration(timeout))
+    }
+
+    pub fn with_lifetime(mut self, lifetime: Lifetime) -> Self {
+        self.info.lifetime = lifetime;
+        self
+    }
+
+    pub fn with_parent_frame_id(mut self, frame_id: &str) -> Self {
+        self.info.header.frame_id = Some(frame_id.to_owned());
+        self
+    }
+
+    /// Seconds since unix epoch at which the grid was captured. Sent with all messages
+    pub fn set_stamp(&mut self, stamp: f64) {
+        self.info.header.stamp = stamp;
+    }
+
+    /// Rotation in form (x, y, z, w)
+    pub fn with_origin(mut self, origin: (f32, f32, f32), rotation: Quaternion) -> Self {
+        self.info.origin = origin;
+        self.info.origin_rotation = rotation;
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        self.info.id()
+    }
+
+    pub fn info(&self) -> &OccupancyGridInfo {
+        &self.info
+    }
+
+    /// Replace metadata. Cells are kept if the layout is unchanged and reset to unknown otherwise
+    ///
+    /// Grid is left unchanged if the new layout is too large
+    pub fn set_info(&mut self, info: OccupancyGridInfo) -> Result<()> {
+        if self.info.same_layout(&info) {
+            self.info = info;
+        } else {
+            *self = Self::from_info(info)?;
+        }
+        Ok(())
+    }
+
+    /// Cells in row major order
+    pub fn data(&self) -> &Vec<i8> {
+        &self.data
+    }
+
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.info.width && y < self.info.height {
+            Some(y as usize * self.info.width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<i8> {
+        self.index(x, y).map(|index| self.data[index])
+    }
+
+    /// Values outside of grid are ignored
+    pub fn set(&mut self, x: u32, y: u32, value: i8) {
+        if let Some(index) = self.index(x, y) {
+            self.data[index] = value;
+        }
+    }
+
+    /// Copy patch into grid. Parts of the patch outside of the grid are ignored
+    ///
+    /// Fails without changing the grid if the patch data doesn't match its size
+    pub fn apply_patch(&mut self, patch: &OccupancyGridPatch) -> Result<()> {
+        if !patch.is_valid() {
+            return Err(PosePublisherError::InvalidOccupancyGrid(format!(
+                "patch of {} x {} cells has {} values",
+                patch.width,
+                patch.height,
+                patch.data.len()
+            )));
+        }
+        // rows are only as wide as the part of the patch inside of the grid
+        let width = patch.width.min(self.info.width.saturating_sub(patch.x));
+        let height = patch.height.min(self.info.height.saturating_sub(patch.y));
+        for (row, values) in patch
+            .data
+            .chunks(patch.width.max(1) as usize)
+            .take(height as usize)
+            .enumerate()
+        {
+            for (column, value) in values.iter().take(width as usize).enumerate() {
+                self.set(patch.x + column as u32, patch.y + row as u32, *value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Region of the grid as a single patch. Region is clipped to the grid
+    pub fn patch(&self, x: u32, y: u32, width: u32, height: u32) -> OccupancyGridPatch {
+        let x = x.min(self.info.width);
+        let y = y.min(self.info.height);
+        let width = width.min(self.info.width - x);
+        let height = height.min(self.info.height - y);
+        let mut data = Vec::with_capacity((width * height) as usize);
+        for row in y..y + height {
+            let start = (row * self.info.width + x) as usize;
+            data.extend_from_slice(&self.data[start..start + width as usize]);
+        }
+        OccupancyGridPatch {
+            id: self.info.id.clone(),
+            header: self.info.header.clone(),
+            x,
+            y,
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Region of the grid split into patches of at most [`MAX_PATCH_CELLS`]
+    pub fn patches(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<OccupancyGridPatch> {
+        let block_width = width.min(MAX_PATCH_CELLS as u32).max(1);
+        let block_height = (MAX_PATCH_CELLS as u32 / block_width).max(1);
+        let mut patches = vec![];
+        for block_y in (y..y + height).step_by(block_height as usize) {
+            for block_x in (x..x + width).step_by(block_width as usize) {
+                let patch_width = block_width.min(x + width - block_x);
+                let patch_height = block_height.min(y + height - block_y);
+                patches.push(self.patch(block_x, block_y, patch_width, patch_height));
+            }
+        }
+        patches
+    }
+
+    /// Info followed by patches covering the whole grid
+    pub fn to_messages(&self) -> Vec<OccupancyGridMessage> {
+        let mut messages = vec![OccupancyGridMessage::Info(self.info.clone())];
+        messages.extend(
+            self.patches(0, 0, self.info.width, self.info.height)
+                .into_iter()
+                .map(OccupancyGridMessage::Patch),
+        );
+        messages
+    }
+
+    /// Messages needed to turn `previous` into this grid
+    ///
+    /// Only the bounding box of changed cells is sent if the layout is unchanged
+    pub fn delta_messages(&self, previous: &OccupancyGrid) -> Vec<OccupancyGridMessage> {
+        if !self.info.same_layout(&previous.info) {
+            return self.to_messages();
+        }
+        let mut messages = vec![];
+        // a new stamp alone is carried by the patches
+        let mut previous_info = previous.info.clone();
+        previous_info.header.stamp = self.info.header.stamp;
+        if self.info != previous_info {
+            messages.push(OccupancyGridMessage::Info(self.info.clone()));
+        }
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for (index, (current, old)) in self.data.iter().zip(&previous.data).enumerate() {
+            if current != old {
+                let x = index as u32 % self.info.width;
+                let y = index as u32 / self.info.width;
+                bounds = Some(match bounds {
+                    Some((min_x, min_y, max_x, max_y)) => {
+                        (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                    }
+                    None => (x, y, x, y),
+                });
+            }
+        }
+        if let Some((min_x, min_y, max_x, max_y)) = bounds {
+            messages.extend(
+                self.patches(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+                    .into_iter()
+                    .map(OccupancyGridMessage::Patch),
+            );
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(x: u32, y: u32, width: u32, height: u32, data: Vec<i8>) -> OccupancyGridPatch {
+        OccupancyGridPatch {
+            id: "map".to_owned(),
+            header: Header::new(),
+            x,
+            y,
+            width,
+            height,
+            data,
+        }
+    }
+
+    fn apply(grid: &mut OccupancyGrid, messages: Vec<OccupancyGridMessage>) {
+        for message in messages {
+            match message {
+                OccupancyGridMessage::Info(info) => grid.set_info(info).unwrap(),
+                OccupancyGridMessage::Patch(patch) => grid.apply_patch(&patch).unwrap(),
+            }
+        }
+    }
+
+    #[test]
+    fn patch_is_copied_and_clipped_to_grid() {
+        let mut grid = OccupancyGrid::new("map", 1., 3, 3);
+        grid.apply_patch(&patch(2, 1, 2, 2, vec![1, 2, 3, 4]))
+            .unwrap();
+        assert_eq!(grid.get(2, 1), Some(1));
+        assert_eq!(grid.get(2, 2), Some(3));
+        assert_eq!(grid.get(1, 1), Some(UNKNOWN_CELL));
+        assert_eq!(
+            grid.data()
+                .iter()
+                .filter(|cell| **cell != UNKNOWN_CELL)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn patch_outside_of_grid_is_ignored() {
+        let mut grid = OccupancyGrid::new("map", 1., 3, 3);
+        grid.apply_patch(&patch(u32::MAX, u32::MAX, 1, 1, vec![50]))
+            .unwrap();
+        assert!(grid.data().iter().all(|cell| *cell == UNKNOWN_CELL));
+    }
+
+    #[test]
+    fn malformed_patch_is_rejected() {
+        let mut grid = OccupancyGrid::new("map", 1., 3, 3);
+        assert!(grid.apply_patch(&patch(0, 0, 2, 2, vec![1, 2, 3])).is_err());
+        assert!(grid
+            .apply_patch(&patch(0, 0, u32::MAX, u32::MAX, vec![1]))
+            .is_err());
+        assert!(grid.data().iter().all(|cell| *cell == UNKNOWN_CELL));
+    }
+
+    #[test]
+    fn oversized_grid_is_rejected() {
+        let info = OccupancyGrid::new("map", 1., 1, 1).info().clone();
+        let info = OccupancyGridInfo {
+            width: u32::MAX,
+            height: u32::MAX,
+            ..info
+        };
+        assert!(OccupancyGrid::from_info(info).is_err());
+    }
+
+    #[test]
+    fn region_is_split_into_bounded_patches() {
+        let grid = OccupancyGrid::new("map", 1., 200, 200);
+        let patches = grid.patches(0, 0, 200, 200);
+        assert!(patches
+            .iter()
+            .all(|patch| patch.data().len() <= MAX_PATCH_CELLS && patch.is_valid()));
+        let cells: usize = patches.iter().map(|patch| patch.data().len()).sum();
+        assert_eq!(cells, 200 * 200);
+    }
+
+    #[test]
+    fn delta_turns_previous_into_current() {
+        let previous = OccupancyGrid::new("map", 1., 10, 10);
+        let mut current = previous.clone();
+        current.set(2, 3, 100);
+        current.set(5, 7, 0);
+        let messages = current.delta_messages(&previous);
+        // only the bounding box of changes is sent
+        assert_eq!(messages.len(), 1);
+        match &messages[0] {
+            OccupancyGridMessage::Patch(patch) => {
+                assert_eq!(
+                    (patch.x(), patch.y(), patch.width(), patch.height()),
+                    (2, 3, 4, 5)
+                )
+            }
+            message => panic!("expected patch but got {:?}", message),
+        }
+        let mut received = previous.clone();
+        apply(&mut received, messages);
+        assert_eq!(received.data(), current.data());
+    }
+
+    #[test]
+    fn delta_of_unchanged_grid_is_empty() {
+        let grid = OccupancyGrid::new("map", 1., 10, 10);
+        assert!(grid.delta_messages(&grid.clone()).is_empty());
+    }
+
+    #[test]
+    fn delta_patches_carry_new_stamp() {
+        let mut previous = OccupancyGrid::new("map", 1., 10, 10);
+        previous.set_stamp(10.);
+        let mut current = previous.clone();
+        current.set(2, 3, 100);
+        current.set_stamp(11.);
+        let messages = current.delta_messages(&previous);
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(&messages[0], OccupancyGridMessage::Patch(_)));
+        assert_eq!(messages[0].header().stamp, 11.);
+    }
+
+    #[test]
+    fn delta_with_new_layout_resends_grid() {
+        let previous = OccupancyGrid::new("map", 1., 10, 10);
+        let mut current = OccupancyGrid::new("map", 1., 20, 5);
+        current.set(19, 4, 100);
+        let mut received = previous.clone();
+        apply(&mut received, current.delta_messages(&previous));
+        assert_eq!(received.info(), current.info());
+        assert_eq!(received.data(), current.data());
+    }
+
+    #[test]
+    fn messages_of_both_kinds_can_be_restamped() {
+        for message in OccupancyGrid::new("map", 0.1, 2, 2).to_messages() {
+            assert_eq!(message.with_stamp(12.5).header().stamp, 12.5);
+        }
+    }
+}
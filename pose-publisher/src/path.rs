@@ -0,0 +1,186 @@
+use crate::header::{header_methods, Header};
+use crate::lifetime::{default_lifetime, entity_methods, Lifetime};
+use crate::pose::{Color, Quaternion};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_GREEN_COLOR: Color = Color::Green;
+const DEFAULT_WIDTH: f32 = 0.;
+
+type Point3 = (f32, f32, f32);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, JsonSchema)]
+pub struct PathPoint {
+    position: Point3,
+    orientation: Option<Quaternion>,
+}
+
+impl PathPoint {
+    pub fn new(position: Point3) -> Self {
+        Self {
+            position,
+            orientation: None,
+        }
+    }
+
+    /// Orientation in form (x, y, z, w)
+    pub fn with_orientation(position: Point3, orientation: Quaternion) -> Self {
+        Self {
+            position,
+            orientation: Some(orientation),
+        }
+    }
+
+    pub fn position(&self) -> Point3 {
+        self.position
+    }
+
+    pub fn orientation(&self) -> Option<Quaternion> {
+        self.orientation
+    }
+}
+
+/// Connected polyline, for example a planned trajectory
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct Path {
+    id: String,
+    #[serde(default)]
+    namespace: String,
+    #[serde(default)]
+    header: Header,
+    points: Vec<PathPoint>,
+    #[serde(
+        alias = "timeout",
+        default = "default_lifetime",
+        deserialize_with = "crate::lifetime::deserialize_lifetime"
+    )]
+    lifetime: Lifetime,
+    color: Color,
+    width: f32,
+}
+
+impl Path {
+    pub fn from_points(id: &str, points: Vec<Point3>) -> Self {
+        Self::from_path_points(id, points.into_iter().map(PathPoint::new).collect())
+    }
+
+    /// Poses in form (position, (x, y, z, w))
+    pub fn from_poses(id: &str, poses: Vec<(Point3, Quaternion)>) -> Self {
+        Self::from_path_points(
+            id,
+            poses
+                .into_iter()
+                .map(|(position, orientation)| PathPoint::with_orientation(position, orientation))
+                .collect(),
+        )
+    }
+
+    pub fn from_path_points(id: &str, points: Vec<PathPoint>) -> Self {
+        Self {
+            id: id.to_owned(),
+            namespace: String::new(),
+            header: Header::new(),
+            points,
+            lifetime: default_lifetime(),
+            color: DEFAULT_GREEN_COLOR,
+            width: DEFAULT_WIDTH,
+        }
+    }
+
+    entity_methods!();
+
+    header_methods!();
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Width of the path in meters. Zero draws a thin line
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn points(&self) -> &Vec<PathPoint> {
+        &self.points
+    }
+
+    pub fn color(&self) -> &Color {
+        &self.color
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_all_fields() {
+        let path = Path::from_points("plan", vec![(0., 0., 0.), (1., 0., 0.)])
+            .with_namespace("planner")
+            .with_color(Color::Blue)
+            .with_width(0.1)
+            .with_lifetime(Lifetime::Forever)
+            .with_stamp(12.5)
+            .with_parent_frame_id("map");
+        assert_eq!(path.id(), "plan");
+        assert_eq!(path.namespace(), "planner");
+        assert_eq!(path.color(), &Color::Blue);
+        assert_eq!(path.width(), 0.1);
+        assert_eq!(path.lifetime(), &Lifetime::Forever);
+        assert_eq!(path.header().stamp, 12.5);
+        assert_eq!(path.parent_frame_id(), &Some("map".to_owned()));
+        assert_eq!(path.points().len(), 2);
+        assert_eq!(path.points()[1].position(), (1., 0., 0.));
+        assert_eq!(path.points()[1].orientation(), None);
+    }
+
+    #[test]
+    fn poses_keep_their_orientation() {
+        let path = Path::from_poses("trajectory", vec![((1., 2., 3.), (0., 0., 0., 1.))]);
+        assert_eq!(path.points()[0].position(), (1., 2., 3.));
+        assert_eq!(path.points()[0].orientation(), Some((0., 0., 0., 1.)));
+        assert_eq!(path.namespace(), "");
+    }
+
+    #[test]
+    fn survives_serialization() {
+        let path = Path::from_poses("trajectory", vec![((1., 2., 3.), (0., 0., 1., 0.))])
+            .with_namespace("planner")
+            .with_width(0.5)
+            .with_stamp(12.5)
+            .with_parent_frame_id("map");
+        let json = serde_json::to_string(&path).unwrap();
+        let deserialized: Path = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.id(), "trajectory");
+        assert_eq!(deserialized.namespace(), "planner");
+        assert_eq!(deserialized.width(), 0.5);
+        assert_eq!(deserialized.header(), path.header());
+        assert_eq!(deserialized.points()[0].position(), (1., 2., 3.));
+        assert_eq!(
+            deserialized.points()[0].orientation(),
+            Some((0., 0., 1., 0.))
+        );
+    }
+
+    #[test]
+    fn namespace_and_header_default_when_missing() {
+        let path: Path = serde_json::from_str(
+            r#"{"id": "plan", "points": [{"position": [1.0, 2.0, 3.0], "orientation": null}],
+                "timeout": 1.0, "color": "Green", "width": 0.0}"#,
+        )
+        .unwrap();
+        assert_eq!(path.namespace(), "");
+        assert_eq!(path.parent_frame_id(), &None);
+        assert_eq!(path.lifetime(), &Lifetime::Duration(1.0));
+    }
+}
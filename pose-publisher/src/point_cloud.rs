@@ -1,43 +1,210 @@
+use crate::header::{header_methods, Header};
+use crate::lifetime::{default_lifetime, entity_methods, Lifetime};
 use crate::pose::Color;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr};
 
-const DEFAULT_TIMEOUT: f32 = 5.;
 const DEFAULT_RED_COLOR: Color = Color::Red;
+const DEFAULT_POINT_SIZE: f32 = 1.;
+/// Points of a 3D cloud per message so that a chunk with colors and intensities fits in a datagram
+pub const MAX_CHUNK_POINTS: usize = 500;
+
+fn default_point_size() -> f32 {
+    DEFAULT_POINT_SIZE
+}
 
 type Point2 = (f32, f32);
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct PointCloud2 {
     id: String,
-    parent_frame_id: Option<String>,
+    #[serde(default)]
+    namespace: String,
+    #[serde(flatten, with = "crate::header::legacy_parent_frame_id")]
+    #[schemars(with = "crate::header::legacy_parent_frame_id::HeaderWithParentFrameId")]
+    header: Header,
     points: Vec<Point2>,
-    timeout: f32,
+    #[serde(flatten, with = "crate::lifetime::legacy_timeout")]
+    #[schemars(with = "crate::lifetime::legacy_timeout::LifetimeWithTimeout")]
+    lifetime: Lifetime,
     color: Color,
+    #[serde(default = "default_point_size")]
+    point_size: f32,
+    #[serde(default)]
+    style: PointStyle,
 }
 
 impl PointCloud2 {
     pub fn from_points(id: &str, points: Vec<Point2>) -> Self {
         Self {
             id: id.to_owned(),
-            parent_frame_id: None,
+            namespace: String::new(),
+            header: Header::new(),
             points,
-            timeout: DEFAULT_TIMEOUT,
+            lifetime: default_lifetime(),
             color: DEFAULT_RED_COLOR,
+            point_size: DEFAULT_POINT_SIZE,
+            style: PointStyle::default(),
         }
     }
 
-    pub fn with_timeout(mut self, timeout: f32) -> Self {
-        self.timeout = timeout;
+    entity_methods!();
+
+    header_methods!();
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Size in pixels for [`PointStyle::Points`] and in meters for other styles
+    pub fn with_point_size(mut self, point_size: f32) -> Self {
+        self.point_size = point_size;
+        self
+    }
+
+    pub fn with_style(mut self, style: PointStyle) -> Self {
+        self.style = style;
         self
     }
 
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn points(&self) -> &Vec<Point2> {
+        &self.points
+    }
+
+    pub fn color(&self) -> &Color {
+        &self.color
+    }
+
+    pub fn point_size(&self) -> f32 {
+        self.point_size
+    }
+
+    pub fn style(&self) -> &PointStyle {
+        &self.style
+    }
+
+    #[deprecated(note = "use lifetime() instead")]
+    pub fn timeout(&self) -> f32 {
+        self.lifetime.timeout()
+    }
+}
+
+type Point3 = (f32, f32, f32);
+
+/// 8 bit per channel color
+pub type Rgb = (u8, u8, u8);
+
+/// Position of a message within a cloud split into several messages
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+pub struct PointCloudChunk {
+    index: u32,
+    count: u32,
+}
+
+impl Default for PointCloudChunk {
+    fn default() -> Self {
+        Self { index: 0, count: 1 }
+    }
+}
+
+impl PointCloudChunk {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Point cloud in 3D with optional per point channels
+///
+/// Clouds larger than [`MAX_CHUNK_POINTS`] are published in chunks that
+/// [`PointCloud3Assembler`] joins back together
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PointCloud3 {
+    id: String,
+    #[serde(default)]
+    namespace: String,
+    #[serde(flatten, with = "crate::header::legacy_parent_frame_id")]
+    #[schemars(with = "crate::header::legacy_parent_frame_id::HeaderWithParentFrameId")]
+    header: Header,
+    points: Vec<Point3>,
+    colors: Option<Vec<Rgb>>,
+    intensities: Option<Vec<f32>>,
+    coloring: PointColoring,
+    #[serde(flatten, with = "crate::lifetime::legacy_timeout")]
+    #[schemars(with = "crate::lifetime::legacy_timeout::LifetimeWithTimeout")]
+    lifetime: Lifetime,
+    color: Color,
+    #[serde(default = "default_point_size")]
+    point_size: f32,
+    #[serde(default)]
+    style: PointStyle,
+    #[serde(default)]
+    chunk: PointCloudChunk,
+}
+
+impl PointCloud3 {
+    pub fn from_points(id: &str, points: Vec<Point3>) -> Self {
+        Self {
+            id: id.to_owned(),
+            namespace: String::new(),
+            header: Header::new(),
+            points,
+            colors: None,
+            intensities: None,
+            coloring: PointColoring::Uniform,
+            lifetime: default_lifetime(),
+            color: DEFAULT_RED_COLOR,
+            point_size: DEFAULT_POINT_SIZE,
+            style: PointStyle::default(),
+            chunk: PointCloudChunk::default(),
+        }
+    }
+
+    entity_methods!();
+
+    header_methods!();
+
+    /// Color used with [`PointColoring::Uniform`]
     pub fn with_color(mut self, color: Color) -> Self {
         self.color = color;
         self
     }
 
-    pub fn with_parent_frame_id(mut self, frame_id: &str) -> Self {
-        self.parent_frame_id = Some(frame_id.to_owned());
+    /// Size in pixels for [`PointStyle::Points`] and in meters for other styles
+    pub fn with_point_size(mut self, point_size: f32) -> Self {
+        self.point_size = point_size;
+        self
+    }
+
+    pub fn with_style(mut self, style: PointStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// One color per point. Switches coloring to [`PointColoring::PerPoint`]
+    pub fn with_colors(mut self, colors: Vec<Rgb>) -> Self {
+        self.colors = Some(colors);
+        self.coloring = PointColoring::PerPoint;
+        self
+    }
+
+    /// One intensity per point
+    pub fn with_intensities(mut self, intensities: Vec<f32>) -> Self {
+        self.intensities = Some(intensities);
+        self
+    }
+
+    pub fn with_coloring(mut self, coloring: PointColoring) -> Self {
+        self.coloring = coloring;
         self
     }
 
@@ -45,19 +212,514 @@ impl PointCloud2 {
         &self.id
     }
 
-    pub fn parent_frame_id(&self) -> &Option<String> {
-        &self.parent_frame_id
+    pub fn points(&self) -> &Vec<Point3> {
+        &self.points
     }
 
-    pub fn points(&self) -> &Vec<Point2> {
-        &self.points
+    pub fn colors(&self) -> &Option<Vec<Rgb>> {
+        &self.colors
+    }
+
+    pub fn intensities(&self) -> &Option<Vec<f32>> {
+        &self.intensities
+    }
+
+    pub fn coloring(&self) -> &PointColoring {
+        &self.coloring
     }
 
     pub fn color(&self) -> &Color {
         &self.color
     }
 
-    pub fn timeout(&self) -> f32 {
-        self.timeout
+    pub fn point_size(&self) -> f32 {
+        self.point_size
+    }
+
+    pub fn style(&self) -> &PointStyle {
+        &self.style
+    }
+
+    pub fn chunk(&self) -> &PointCloudChunk {
+        &self.chunk
+    }
+
+    /// Cloud split into messages of at most [`MAX_CHUNK_POINTS`] points
+    pub fn chunks(&self) -> Vec<PointCloud3> {
+        let count = self.points.len().div_ceil(MAX_CHUNK_POINTS).max(1);
+        (0..count)
+            .map(|index| {
+                let start = index * MAX_CHUNK_POINTS;
+                let end = (start + MAX_CHUNK_POINTS).min(self.points.len());
+                PointCloud3 {
+                    points: self.points[start..end].to_vec(),
+                    colors: chunk_channel(&self.colors, self.points.len(), start, end),
+                    intensities: chunk_channel(&self.intensities, self.points.len(), start, end),
+                    chunk: PointCloudChunk {
+                        index: index as u32,
+                        count: count as u32,
+                    },
+                    ..self.clone_without_points()
+                }
+            })
+            .collect()
+    }
+
+    fn clone_without_points(&self) -> PointCloud3 {
+        PointCloud3 {
+            id: self.id.clone(),
+            namespace: self.namespace.clone(),
+            header: self.header.clone(),
+            points: vec![],
+            colors: None,
+            intensities: None,
+            coloring: self.coloring,
+            lifetime: self.lifetime.clone(),
+            color: self.color,
+            point_size: self.point_size,
+            style: self.style,
+            chunk: self.chunk,
+        }
+    }
+
+    /// Resolve the color of every point according to the coloring mode
+    ///
+    /// Falls back to the uniform color if the required channel is missing
+    pub fn point_colors(&self) -> Vec<(f32, f32, f32)> {
+        let uniform = || vec![self.color.to_rgb(); self.points.len()];
+        match &self.coloring {
+            PointColoring::Uniform => uniform(),
+            PointColoring::PerPoint => match &self.colors {
+                Some(colors) if colors.len() == self.points.len() => colors
+                    .iter()
+                    .map(|(r, g, b)| (*r as f32 / 255., *g as f32 / 255., *b as f32 / 255.))
+                    .collect(),
+                _ => uniform(),
+            },
+            PointColoring::Colormap {
+                colormap,
+                channel,
+                range,
+            } => {
+                let values: Vec<f32> = match channel {
+                    ColormapChannel::Z => self.points.iter().map(|point| point.2).collect(),
+                    ColormapChannel::Intensity => match &self.intensities {
+                        Some(intensities) if intensities.len() == self.points.len() => {
+                            intensities.clone()
+                        }
+                        _ => return uniform(),
+                    },
+                };
+                let (min, max) = range.unwrap_or_else(|| {
+                    values
+                        .iter()
+                        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                            (min.min(*value), max.max(*value))
+                        })
+                });
+                let span = max - min;
+                values
+                    .iter()
+                    .map(|value| {
+                        let normalized = if span > f32::EPSILON {
+                            (value - min) / span
+                        } else {
+                            0.5
+                        };
+                        colormap.to_rgb(normalized)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Values of a per point channel for points `start..end`
+///
+/// Channels with a different length than the points are passed along whole with the first chunk
+fn chunk_channel<T: Clone>(
+    values: &Option<Vec<T>>,
+    points: usize,
+    start: usize,
+    end: usize,
+) -> Option<Vec<T>> {
+    match values {
+        Some(values) if values.len() == points => Some(values[start..end].to_vec()),
+        Some(values) if start == 0 => Some(values.clone()),
+        _ => None,
+    }
+}
+
+/// Joins chunks of 3D clouds back into whole clouds
+///
+/// Chunks have to arrive in order. A chunk that doesn't continue the cloud being assembled
+/// drops the incomplete cloud
+#[derive(Default)]
+pub struct PointCloud3Assembler {
+    /// Incomplete clouds by id
+    pending: HashMap<String, PointCloud3>,
+}
+
+impl PointCloud3Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the whole cloud once its last chunk arrived
+    pub fn add(&mut self, chunk: PointCloud3) -> Option<PointCloud3> {
+        let PointCloudChunk { index, count } = chunk.chunk;
+        if index == 0 {
+            self.pending.remove(&chunk.id);
+            if count <= 1 {
+                return Some(chunk);
+            }
+            self.pending.insert(chunk.id.clone(), chunk);
+            return None;
+        }
+        let mut point_cloud = self.pending.remove(&chunk.id)?;
+        let continues = point_cloud.chunk.index + 1 == index
+            && point_cloud.chunk.count == count
+            && point_cloud.header == chunk.header;
+        if !continues {
+            return None;
+        }
+        point_cloud.points.extend(chunk.points);
+        if let (Some(colors), Some(chunk_colors)) = (&mut point_cloud.colors, chunk.colors) {
+            colors.extend(chunk_colors);
+        }
+        if let (Some(intensities), Some(chunk_intensities)) =
+            (&mut point_cloud.intensities, chunk.intensities)
+        {
+            intensities.extend(chunk_intensities);
+        }
+        point_cloud.chunk.index = index;
+        if index + 1 < count {
+            self.pending.insert(chunk.id, point_cloud);
+            return None;
+        }
+        point_cloud.chunk = PointCloudChunk::default();
+        Some(point_cloud)
+    }
+}
+
+/// How individual points of a cloud are drawn
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, JsonSchema)]
+pub enum PointStyle {
+    /// Screen space dots. Size in pixels
+    #[default]
+    Points,
+    /// Flat squares in the plane of the parent frame. Size in meters
+    Squares,
+    /// Size in meters
+    Cubes,
+    /// Size in meters
+    Spheres,
+}
+
+impl PointStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PointStyle::Points => "Points",
+            PointStyle::Squares => "Squares",
+            PointStyle::Cubes => "Cubes",
+            PointStyle::Spheres => "Spheres",
+        }
+    }
+}
+
+impl FromStr for PointStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "points" => Ok(PointStyle::Points),
+            "squares" => Ok(PointStyle::Squares),
+            "cubes" => Ok(PointStyle::Cubes),
+            "spheres" => Ok(PointStyle::Spheres),
+            _ => Err(format!("unknown point style {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+pub enum PointColoring {
+    /// Whole cloud in a single color
+    Uniform,
+    /// Use per point RGB channel
+    PerPoint,
+    /// Map a channel through a colormap.
+    /// Range of the channel is computed from the cloud if not set
+    Colormap {
+        colormap: Colormap,
+        channel: ColormapChannel,
+        range: Option<(f32, f32)>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+pub enum ColormapChannel {
+    Z,
+    Intensity,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+pub enum Colormap {
+    Viridis,
+    Jet,
+}
+
+const VIRIDIS_STOPS: [(f32, f32, f32); 5] = [
+    (0.267, 0.005, 0.329),
+    (0.230, 0.322, 0.546),
+    (0.128, 0.567, 0.551),
+    (0.369, 0.789, 0.383),
+    (0.993, 0.906, 0.144),
+];
+
+impl Colormap {
+    /// Value is clamped to range 0.0 -> 1.0
+    pub fn to_rgb(&self, value: f32) -> (f32, f32, f32) {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            Colormap::Viridis => {
+                let scaled = value * (VIRIDIS_STOPS.len() - 1) as f32;
+                let index = (scaled.floor() as usize).min(VIRIDIS_STOPS.len() - 2);
+                let t = scaled - index as f32;
+                let (r0, g0, b0) = VIRIDIS_STOPS[index];
+                let (r1, g1, b1) = VIRIDIS_STOPS[index + 1];
+                (r0 + (r1 - r0) * t, g0 + (g1 - g0) * t, b0 + (b1 - b0) * t)
+            }
+            Colormap::Jet => {
+                let channel = |offset: f32| (1.5 - (4.0 * value - offset).abs()).clamp(0.0, 1.0);
+                (channel(3.0), channel(2.0), channel(1.0))
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Colormap::Viridis => "Viridis",
+            Colormap::Jet => "Jet",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rgb_eq(actual: (f32, f32, f32), expected: (f32, f32, f32)) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert!(
+            close(actual.0, expected.0)
+                && close(actual.1, expected.1)
+                && close(actual.2, expected.2),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn viridis_hits_stops_at_ends() {
+        assert_rgb_eq(Colormap::Viridis.to_rgb(0.0), VIRIDIS_STOPS[0]);
+        assert_rgb_eq(Colormap::Viridis.to_rgb(0.5), VIRIDIS_STOPS[2]);
+        assert_rgb_eq(Colormap::Viridis.to_rgb(1.0), VIRIDIS_STOPS[4]);
+    }
+
+    #[test]
+    fn colormap_clamps_values_out_of_range() {
+        for colormap in &[Colormap::Viridis, Colormap::Jet] {
+            assert_rgb_eq(colormap.to_rgb(-1.0), colormap.to_rgb(0.0));
+            assert_rgb_eq(colormap.to_rgb(2.0), colormap.to_rgb(1.0));
+        }
+    }
+
+    #[test]
+    fn jet_goes_from_blue_to_red() {
+        assert_rgb_eq(Colormap::Jet.to_rgb(0.0), (0.0, 0.0, 0.5));
+        assert_rgb_eq(Colormap::Jet.to_rgb(0.5), (0.5, 1.0, 0.5));
+        assert_rgb_eq(Colormap::Jet.to_rgb(1.0), (0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn colormap_range_is_computed_from_cloud() {
+        let point_cloud =
+            PointCloud3::from_points("cloud", vec![(0., 0., 1.), (0., 0., 2.), (0., 0., 3.)])
+                .with_coloring(PointColoring::Colormap {
+                    colormap: Colormap::Viridis,
+                    channel: ColormapChannel::Z,
+                    range: None,
+                });
+        let colors = point_cloud.point_colors();
+        assert_rgb_eq(colors[0], Colormap::Viridis.to_rgb(0.0));
+        assert_rgb_eq(colors[1], Colormap::Viridis.to_rgb(0.5));
+        assert_rgb_eq(colors[2], Colormap::Viridis.to_rgb(1.0));
+    }
+
+    #[test]
+    fn missing_intensities_fall_back_to_uniform_color() {
+        let point_cloud = PointCloud3::from_points("cloud", vec![(0., 0., 1.), (0., 0., 2.)])
+            .with_color(Color::Blue)
+            .with_coloring(PointColoring::Colormap {
+                colormap: Colormap::Jet,
+                channel: ColormapChannel::Intensity,
+                range: None,
+            });
+        assert_eq!(point_cloud.point_colors(), vec![Color::Blue.to_rgb(); 2]);
+    }
+
+    #[test]
+    fn point_size_and_style_default_when_missing() {
+        let point_cloud: PointCloud2 = serde_json::from_str(
+            r#"{"id": "cloud", "points": [[1.0, 2.0]], "lifetime": {"Duration": 1.0},
+                "color": "Red"}"#,
+        )
+        .unwrap();
+        assert_eq!(point_cloud.point_size(), DEFAULT_POINT_SIZE);
+        assert_eq!(point_cloud.style(), &PointStyle::Points);
+
+        let point_cloud: PointCloud3 = serde_json::from_str(
+            r#"{"id": "cloud", "points": [[1.0, 2.0, 3.0]], "colors": null,
+                "intensities": null, "coloring": "Uniform", "lifetime": {"Duration": 1.0},
+                "color": "Red"}"#,
+        )
+        .unwrap();
+        assert_eq!(point_cloud.point_size(), DEFAULT_POINT_SIZE);
+        assert_eq!(point_cloud.style(), &PointStyle::Points);
+    }
+
+    #[test]
+    fn point_size_and_style_survive_serialization() {
+        let point_cloud = PointCloud2::from_points("cloud", vec![(1., 2.)])
+            .with_point_size(0.25)
+            .with_style(PointStyle::Spheres);
+        let point_cloud: PointCloud2 =
+            serde_json::from_str(&serde_json::to_string(&point_cloud).unwrap()).unwrap();
+        assert_eq!(point_cloud.point_size(), 0.25);
+        assert_eq!(point_cloud.style(), &PointStyle::Spheres);
+    }
+
+    #[test]
+    fn clouds_without_header_keep_their_parent_frame() {
+        let point_cloud: PointCloud2 = serde_json::from_str(
+            r#"{"id": "cloud", "parent_frame_id": "lidar", "points": [[1.0, 2.0]],
+                "timeout": 2.0, "color": "Red"}"#,
+        )
+        .unwrap();
+        assert_eq!(point_cloud.parent_frame_id(), &Some("lidar".to_owned()));
+        assert_eq!(point_cloud.points(), &vec![(1., 2.)]);
+
+        let json = serde_json::to_value(&point_cloud).unwrap();
+        assert_eq!(json["parent_frame_id"], "lidar");
+        assert_eq!(json["header"]["frame_id"], "lidar");
+    }
+
+    #[test]
+    fn header_frame_wins_over_parent_frame() {
+        let point_cloud: PointCloud3 = serde_json::from_str(
+            r#"{"id": "cloud", "parent_frame_id": "old", "header": {"stamp": 1.0, "frame_id": "new"},
+                "points": [], "colors": null, "intensities": null, "coloring": "Uniform",
+                "color": "Red"}"#,
+        )
+        .unwrap();
+        assert_eq!(point_cloud.parent_frame_id(), &Some("new".to_owned()));
+        assert_eq!(point_cloud.header().stamp, 1.);
+    }
+
+    #[test]
+    fn point_style_parses_names_ignoring_case() {
+        for style in &[
+            PointStyle::Points,
+            PointStyle::Squares,
+            PointStyle::Cubes,
+            PointStyle::Spheres,
+        ] {
+            assert_eq!(style.name().parse::<PointStyle>(), Ok(*style));
+            assert_eq!(
+                style.name().to_uppercase().parse::<PointStyle>(),
+                Ok(*style)
+            );
+        }
+        assert!("dots".parse::<PointStyle>().is_err());
+    }
+
+    fn large_cloud() -> PointCloud3 {
+        let count = MAX_CHUNK_POINTS * 5 + 7;
+        let points = (0..count)
+            .map(|i| {
+                let i = i as f32 * 0.001234567;
+                (-i.sin() * 1234.5678, i.cos() * 1234.5678, -i * 0.001234567)
+            })
+            .collect();
+        let colors = (0..count)
+            .map(|i| (i as u8, (i / 2) as u8, 255 - i as u8))
+            .collect();
+        let intensities = (0..count).map(|i| -(i as f32) * 1.234567e-5).collect();
+        PointCloud3::from_points("cloud", points)
+            .with_colors(colors)
+            .with_intensities(intensities)
+            // exactly representable so that it survives serialization
+            .with_stamp(12.5)
+            .with_parent_frame_id("lidar")
+    }
+
+    #[test]
+    fn cloud_larger_than_a_datagram_gets_through_in_chunks() {
+        let point_cloud = large_cloud();
+        assert!(serde_json::to_string(&point_cloud).unwrap().len() > crate::MAX_PAYLOAD_SIZE);
+        let mut assembler = PointCloud3Assembler::new();
+        let mut assembled = None;
+        for chunk in point_cloud.chunks() {
+            let payload = serde_json::to_string(&chunk).unwrap();
+            assert!(payload.len() <= crate::MAX_PAYLOAD_SIZE);
+            assert!(assembled.is_none());
+            assembled = assembler.add(serde_json::from_str(&payload).unwrap());
+        }
+        let assembled = assembled.unwrap();
+        assert_eq!(assembled.points(), point_cloud.points());
+        assert_eq!(assembled.colors(), point_cloud.colors());
+        assert_eq!(assembled.intensities(), point_cloud.intensities());
+        assert_eq!(assembled.header(), point_cloud.header());
+        assert_eq!(assembled.chunk(), &PointCloudChunk::default());
+    }
+
+    #[test]
+    fn small_cloud_is_a_single_chunk() {
+        let point_cloud = PointCloud3::from_points("cloud", vec![(0., 0., 1.)]);
+        let chunks = point_cloud.chunks();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].chunk(), &PointCloudChunk::default());
+        let assembled = PointCloud3Assembler::new()
+            .add(chunks.into_iter().next().unwrap())
+            .unwrap();
+        assert_eq!(assembled.points(), point_cloud.points());
+    }
+
+    #[test]
+    fn missing_chunk_drops_cloud() {
+        let chunks = large_cloud().chunks();
+        let mut assembler = PointCloud3Assembler::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            if index != 2 {
+                assert!(assembler.add(chunk.clone()).is_none());
+            }
+        }
+        // next cloud is assembled again
+        let mut assembled = None;
+        for chunk in chunks {
+            assembled = assembler.add(chunk);
+        }
+        assert_eq!(assembled.unwrap().points().len(), MAX_CHUNK_POINTS * 5 + 7);
+    }
+
+    #[test]
+    fn chunks_without_header_default_to_whole_cloud() {
+        let point_cloud: PointCloud3 = serde_json::from_str(
+            r#"{"id": "cloud", "points": [[1.0, 2.0, 3.0]], "colors": null,
+                "intensities": null, "coloring": "Uniform", "color": "Red"}"#,
+        )
+        .unwrap();
+        assert_eq!(point_cloud.chunk(), &PointCloudChunk::default());
     }
 }
@@ -1,16 +1,42 @@
+use crate::header::Header;
+use crate::lifetime::{Lifetime, DEFAULT_TIMEOUT};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+/// Batch of object updates and deletions
+///
+/// Deletions are applied after updates so an object updated and deleted by the same
+/// message is removed
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct PoseClientUpdate {
+    /// Frame of objects without their own parent frame
+    #[serde(default)]
+    header: Header,
     objects: Vec<ObjectPose>,
     delete: Vec<String>,
+    #[serde(default)]
+    delete_namespaces: Vec<String>,
+    #[serde(default)]
+    delete_patterns: Vec<String>,
+    #[serde(default)]
+    delete_all: bool,
+}
+
+impl Default for PoseClientUpdate {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PoseClientUpdate {
     pub fn new() -> Self {
         PoseClientUpdate {
+            header: Header::new(),
             objects: vec![],
             delete: vec![],
+            delete_namespaces: vec![],
+            delete_patterns: vec![],
+            delete_all: false,
         }
     }
 
@@ -21,10 +47,40 @@ impl PoseClientUpdate {
         self.objects.get_mut(index).unwrap()
     }
 
+    /// Seconds since unix epoch at which the poses were captured
+    pub fn set_stamp(&mut self, stamp: f64) {
+        self.header.stamp = stamp;
+    }
+
+    /// Frame of objects without their own parent frame
+    pub fn set_frame_id(&mut self, frame_id: &str) {
+        self.header.frame_id = Some(frame_id.to_owned());
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
     pub fn delete(&mut self, id: &str) {
         self.delete.push(id.to_owned());
     }
 
+    /// Delete every object and point cloud in namespace
+    pub fn delete_namespace(&mut self, namespace: &str) {
+        self.delete_namespaces.push(namespace.to_owned());
+    }
+
+    /// Delete every object and point cloud with id matching a glob pattern.
+    /// `*` matches any sequence of characters and `?` matches a single character
+    pub fn delete_matching(&mut self, pattern: &str) {
+        self.delete_patterns.push(pattern.to_owned());
+    }
+
+    /// Delete every object and point cloud
+    pub fn delete_all(&mut self) {
+        self.delete_all = true;
+    }
+
     pub fn updates(&self) -> &Vec<ObjectPose> {
         &self.objects
     }
@@ -32,43 +88,165 @@ impl PoseClientUpdate {
     pub fn deletions(&self) -> &Vec<String> {
         &self.delete
     }
+
+    pub fn namespace_deletions(&self) -> &Vec<String> {
+        &self.delete_namespaces
+    }
+
+    pub fn pattern_deletions(&self) -> &Vec<String> {
+        &self.delete_patterns
+    }
+
+    pub fn deletes_all(&self) -> bool {
+        self.delete_all
+    }
+
+    pub fn has_deletions(&self) -> bool {
+        self.delete_all
+            || !self.delete.is_empty()
+            || !self.delete_namespaces.is_empty()
+            || !self.delete_patterns.is_empty()
+    }
+
+    /// Whether an entity with given id and namespace is removed by this update
+    pub fn should_delete(&self, id: &str, namespace: &str) -> bool {
+        self.delete_all
+            || self.delete.iter().any(|deleted| deleted == id)
+            || self
+                .delete_namespaces
+                .iter()
+                .any(|deleted| deleted == namespace)
+            || self
+                .delete_patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, id))
+    }
+}
+
+/// Match text against pattern where `*` matches any sequence and `?` any single character
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut pattern_index, mut text_index) = (0, 0);
+    // position of last star in pattern and text index it was matched against
+    let mut backtrack: Option<(usize, usize)> = None;
+    while text_index < text.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                backtrack = Some((pattern_index, text_index));
+                pattern_index += 1;
+            }
+            Some(character) if *character == '?' || *character == text[text_index] => {
+                pattern_index += 1;
+                text_index += 1;
+            }
+            _ => match backtrack {
+                Some((star_index, star_text_index)) => {
+                    pattern_index = star_index + 1;
+                    text_index = star_text_index + 1;
+                    backtrack = Some((star_index, star_text_index + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_index..]
+        .iter()
+        .all(|character| *character == '*')
 }
 
 /// in form (x, y, z, w)
-type Quaternion = (f32, f32, f32, f32);
+pub type Quaternion = (f32, f32, f32, f32);
 
-const DEFAULT_TIMEOUT: f32 = 5.;
 const DEFAULT_RED_COLOR: Color = Color::Red;
 const DEFAULT_SHAPE: Shape = Shape::Sphere(0.05);
 const IDENTITY_QUATERNION: Quaternion = (0., 0., 0., 1.);
 
-#[derive(Serialize, Deserialize, Debug)]
+fn default_timeout() -> f32 {
+    DEFAULT_TIMEOUT
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct ObjectPose {
     pub id: String,
+    #[serde(default)]
+    pub namespace: String,
+    /// Id of the object whose frame pose and rotation are expressed in. World if None
+    #[serde(default)]
+    pub parent_frame_id: Option<String>,
     pub pose: (f32, f32, f32),
+    /// Seconds after last update before the object expires. Ignored if lifetime is set
+    #[deprecated(note = "use lifetime instead")]
+    #[serde(default = "default_timeout")]
     pub timeout: f32,
+    /// Overrides timeout. Kept out of messages without it so that older receivers can read them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifetime: Option<Lifetime>,
     pub shape: Shape,
     pub color: Color,
     pub rotation: Quaternion,
+    #[serde(default)]
+    pub covariance: Option<Covariance>,
+    /// Scale along (x, y, z) applied on top of the shape
+    #[serde(default)]
+    pub scale: Option<(f32, f32, f32)>,
 }
 
 impl ObjectPose {
+    #[allow(deprecated)]
     fn new(id: &str, pose: (f32, f32, f32)) -> Self {
         ObjectPose {
             id: id.to_owned(),
+            namespace: String::new(),
+            parent_frame_id: None,
             pose,
             timeout: DEFAULT_TIMEOUT,
+            lifetime: None,
             shape: DEFAULT_SHAPE,
             color: DEFAULT_RED_COLOR,
             rotation: IDENTITY_QUATERNION,
+            covariance: None,
+            scale: None,
         }
     }
 
+    /// Group objects so that they can be deleted together
+    pub fn with_namespace(&mut self, namespace: &str) -> &mut Self {
+        self.namespace = namespace.to_owned();
+        self
+    }
+
+    pub fn with_parent_frame_id(&mut self, frame_id: &str) -> &mut Self {
+        self.parent_frame_id = Some(frame_id.to_owned());
+        self
+    }
+
+    /// Seconds after last update before the object expires
+    #[allow(deprecated)]
     pub fn with_timeout(&mut self, timeout: f32) -> &mut Self {
         self.timeout = timeout;
+        self.lifetime = None;
+        self
+    }
+
+    /// Durations are also written to timeout for receivers that don't know lifetimes
+    #[allow(deprecated)]
+    pub fn with_lifetime(&mut self, lifetime: Lifetime) -> &mut Self {
+        if let Lifetime::Duration(timeout) = lifetime {
+            self.timeout = timeout;
+        }
+        self.lifetime = Some(lifetime);
         self
     }
 
+    /// Lifetime if set and timeout otherwise
+    #[allow(deprecated)]
+    pub fn effective_lifetime(&self) -> Lifetime {
+        self.lifetime
+            .clone()
+            .unwrap_or(Lifetime::Duration(self.timeout))
+    }
+
     pub fn with_color(&mut self, color: Color) -> &mut Self {
         self.color = color;
         self
@@ -84,16 +262,79 @@ impl ObjectPose {
         self.rotation = rotation;
         self
     }
+
+    /// Scale along (x, y, z). Cheaper than changing shape dimensions
+    pub fn with_scale(&mut self, scale: (f32, f32, f32)) -> &mut Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Uncertainty of the pose expressed in the same frame as the pose
+    pub fn with_covariance(&mut self, covariance: Covariance) -> &mut Self {
+        self.covariance = Some(covariance);
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub enum Covariance {
+    /// Row major 2x2 covariance of (x, y) and variance of yaw
+    Planar { xy: [f32; 4], yaw_variance: f32 },
+    /// Row major 6x6 covariance of (x, y, z, roll, pitch, yaw)
+    Full(Vec<f32>),
+}
+
+impl Covariance {
+    pub fn planar(xy: [f32; 4], yaw_variance: f32) -> Self {
+        Covariance::Planar { xy, yaw_variance }
+    }
+
+    /// Returns `None` if the matrix doesn't have 36 elements
+    pub fn full(matrix: Vec<f32>) -> Option<Self> {
+        if matrix.len() == 36 {
+            Some(Covariance::Full(matrix))
+        } else {
+            None
+        }
+    }
+
+    /// Row major 3x3 covariance of (x, y, z). Planar covariance has no z variance
+    pub fn position(&self) -> Option<[f32; 9]> {
+        match self {
+            Covariance::Planar { xy, .. } => Some([xy[0], xy[1], 0., xy[2], xy[3], 0., 0., 0., 0.]),
+            Covariance::Full(matrix) if matrix.len() == 36 => {
+                let mut position = [0.; 9];
+                for row in 0..3 {
+                    for column in 0..3 {
+                        position[row * 3 + column] = matrix[row * 6 + column];
+                    }
+                }
+                Some(position)
+            }
+            Covariance::Full(_) => None,
+        }
+    }
+
+    /// Returns `None` for a full matrix without 36 elements like [`Covariance::position`]
+    pub fn yaw_variance(&self) -> Option<f32> {
+        match self {
+            Covariance::Planar { yaw_variance, .. } => Some(*yaw_variance),
+            Covariance::Full(matrix) if matrix.len() == 36 => Some(matrix[35]),
+            Covariance::Full(_) => None,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
 pub enum Shape {
     Sphere(f32),
     Cube(f32, f32, f32),
     Line((f32, f32, f32)),
+    /// Coordinate axes with given length. x is red, y green and z blue
+    Axes(f32),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
 pub enum Color {
     Red,
     Green,
@@ -126,3 +367,97 @@ impl Color {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_literal_text() {
+        assert!(glob_match("robot", "robot"));
+        assert!(!glob_match("robot", "robots"));
+        assert!(!glob_match("robots", "robot"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "robot"));
+    }
+
+    #[test]
+    fn glob_star_matches_any_sequence() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("lidar/*", "lidar/0"));
+        assert!(glob_match("lidar/*", "lidar/"));
+        assert!(!glob_match("lidar/*", "lidar"));
+        assert!(glob_match("*/goal", "robot/goal"));
+        assert!(glob_match("a*b*c", "a_b_b_c"));
+        assert!(!glob_match("a*b*c", "a_c_b"));
+    }
+
+    #[test]
+    fn glob_question_mark_matches_one_character() {
+        assert!(glob_match("wheel_?", "wheel_1"));
+        assert!(!glob_match("wheel_?", "wheel_"));
+        assert!(!glob_match("wheel_?", "wheel_12"));
+        assert!(glob_match("wheel_?*", "wheel_12"));
+    }
+
+    #[test]
+    fn glob_backtracks_after_partial_match() {
+        assert!(glob_match("*ab", "aab"));
+        assert!(glob_match("*aab", "aaab"));
+        assert!(!glob_match("*ab", "aba"));
+    }
+
+    #[test]
+    fn deletion_kinds_match_ids_and_namespaces() {
+        let mut update = PoseClientUpdate::new();
+        assert!(!update.has_deletions());
+        update.delete("robot");
+        update.delete_namespace("map");
+        update.delete_matching("path/*");
+        assert!(update.has_deletions());
+        assert!(update.should_delete("robot", ""));
+        assert!(update.should_delete("wall", "map"));
+        assert!(update.should_delete("path/3", "planner"));
+        assert!(!update.should_delete("goal", "planner"));
+
+        let mut update = PoseClientUpdate::new();
+        update.delete_all();
+        assert!(update.should_delete("goal", "planner"));
+    }
+
+    #[test]
+    fn default_update_is_stamped_like_new() {
+        assert!(PoseClientUpdate::default().header().has_stamp());
+        assert!(crate::TransformUpdate::default().header().has_stamp());
+    }
+
+    #[test]
+    fn planar_covariance_has_no_z_variance() {
+        let covariance = Covariance::planar([1., 2., 3., 4.], 0.5);
+        assert_eq!(
+            covariance.position(),
+            Some([1., 2., 0., 3., 4., 0., 0., 0., 0.])
+        );
+        assert_eq!(covariance.yaw_variance(), Some(0.5));
+    }
+
+    #[test]
+    fn full_covariance_position_is_upper_left_block() {
+        let covariance = Covariance::full((0..36).map(|i| i as f32).collect()).unwrap();
+        assert_eq!(
+            covariance.position(),
+            Some([0., 1., 2., 6., 7., 8., 12., 13., 14.])
+        );
+        assert_eq!(covariance.yaw_variance(), Some(35.));
+    }
+
+    #[test]
+    fn full_covariance_needs_36_elements() {
+        assert_eq!(Covariance::full(vec![0.; 9]), None);
+        assert_eq!(Covariance::Full(vec![0.; 9]).position(), None);
+        assert_eq!(Covariance::Full(vec![0.; 9]).yaw_variance(), None);
+        assert_eq!(Covariance::Full(vec![0.; 40]).position(), None);
+        assert_eq!(Covariance::Full(vec![0.; 40]).yaw_variance(), None);
+    }
+}
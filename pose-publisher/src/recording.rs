@@ -0,0 +1,518 @@
+use crate::{
+    commands::{Command, CommandStatus},
+    header::Header,
+    topics, Heartbeat, LaserScan, OccupancyGridMessage, PointCloud2, PointCloud3, PoseClientUpdate,
+    PosePublisherError, Result, TransformUpdate,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    net::SocketAddrV4,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Seconds of log between index points
+const INDEX_INTERVAL: f64 = 1.0;
+
+/// Every topic in [`crate::topics::ALL`] can be recorded
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    Pose,
+    PointCloud,
+    Command,
+    Path,
+    #[serde(rename = "point_cloud_3d")]
+    PointCloud3,
+    LaserScan,
+    OccupancyGrid,
+    Heartbeat,
+    Transform,
+    CommandStatus,
+}
+
+impl Topic {
+    pub const ALL: [Topic; 10] = [
+        Topic::Pose,
+        Topic::PointCloud,
+        Topic::Command,
+        Topic::Path,
+        Topic::PointCloud3,
+        Topic::LaserScan,
+        Topic::OccupancyGrid,
+        Topic::Heartbeat,
+        Topic::Transform,
+        Topic::CommandStatus,
+    ];
+
+    /// Same as the name in [`crate::topics::ALL`]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topic::Pose => "pose",
+            Topic::PointCloud => "point_cloud",
+            Topic::Command => "command",
+            Topic::Path => "path",
+            Topic::PointCloud3 => "point_cloud_3d",
+            Topic::LaserScan => "laser_scan",
+            Topic::OccupancyGrid => "occupancy_grid",
+            Topic::Heartbeat => "heartbeat",
+            Topic::Transform => "transform",
+            Topic::CommandStatus => "command_status",
+        }
+    }
+
+    /// Default multicast address of the topic
+    pub fn address(&self) -> SocketAddrV4 {
+        topics::resolve(self.name()).expect("all topics have a default address")
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let name = s.to_lowercase().replace('-', "_");
+        Topic::ALL
+            .iter()
+            .find(|topic| topic.name() == name)
+            .copied()
+            .ok_or_else(|| format!("unknown topic {}", s))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "topic", content = "message", rename_all = "snake_case")]
+pub enum LogMessage {
+    Pose(PoseClientUpdate),
+    PointCloud(PointCloud2),
+    Command(Command),
+    Path(crate::Path),
+    #[serde(rename = "point_cloud_3d")]
+    PointCloud3(PointCloud3),
+    LaserScan(LaserScan),
+    OccupancyGrid(OccupancyGridMessage),
+    Heartbeat(Heartbeat),
+    Transform(TransformUpdate),
+    CommandStatus(CommandStatus),
+}
+
+impl LogMessage {
+    pub fn topic(&self) -> Topic {
+        match self {
+            LogMessage::Pose(_) => Topic::Pose,
+            LogMessage::PointCloud(_) => Topic::PointCloud,
+            LogMessage::Command(_) => Topic::Command,
+            LogMessage::Path(_) => Topic::Path,
+            LogMessage::PointCloud3(_) => Topic::PointCloud3,
+            LogMessage::LaserScan(_) => Topic::LaserScan,
+            LogMessage::OccupancyGrid(_) => Topic::OccupancyGrid,
+            LogMessage::Heartbeat(_) => Topic::Heartbeat,
+            LogMessage::Transform(_) => Topic::Transform,
+            LogMessage::CommandStatus(_) => Topic::CommandStatus,
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        match self {
+            LogMessage::Pose(update) => update.header(),
+            LogMessage::PointCloud(point_cloud) => point_cloud.header(),
+            LogMessage::Command(command) => command.header(),
+            LogMessage::Path(path) => path.header(),
+            LogMessage::PointCloud3(point_cloud) => point_cloud.header(),
+            LogMessage::LaserScan(laser_scan) => laser_scan.header(),
+            LogMessage::OccupancyGrid(message) => message.header(),
+            LogMessage::Heartbeat(heartbeat) => heartbeat.header(),
+            LogMessage::Transform(update) => update.header(),
+            LogMessage::CommandStatus(status) => status.header(),
+        }
+    }
+
+    /// Move capture stamp by offset seconds so that replayed data looks current.
+    /// Messages without a stamp are left alone
+    pub fn shift_stamp(self, offset: f64) -> Self {
+        if !self.header().has_stamp() {
+            return self;
+        }
+        let stamp = self.header().stamp + offset;
+        match self {
+            LogMessage::Pose(mut update) => {
+                update.set_stamp(stamp);
+                LogMessage::Pose(update)
+            }
+            LogMessage::PointCloud(point_cloud) => {
+                LogMessage::PointCloud(point_cloud.with_stamp(stamp))
+            }
+            LogMessage::Command(command) => LogMessage::Command(command.with_stamp(stamp)),
+            LogMessage::Path(path) => LogMessage::Path(path.with_stamp(stamp)),
+            LogMessage::PointCloud3(point_cloud) => {
+                LogMessage::PointCloud3(point_cloud.with_stamp(stamp))
+            }
+            LogMessage::LaserScan(laser_scan) => {
+                LogMessage::LaserScan(laser_scan.with_stamp(stamp))
+            }
+            LogMessage::OccupancyGrid(message) => {
+                LogMessage::OccupancyGrid(message.with_stamp(stamp))
+            }
+            LogMessage::Heartbeat(heartbeat) => LogMessage::Heartbeat(heartbeat.with_stamp(stamp)),
+            LogMessage::Transform(update) => LogMessage::Transform(update.shift_stamps(offset)),
+            LogMessage::CommandStatus(status) => {
+                LogMessage::CommandStatus(status.with_stamp(stamp))
+            }
+        }
+    }
+}
+
+/// Single line of a log file
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LogEntry {
+    /// Seconds since unix epoch at which the message was received by the recorder
+    pub received: f64,
+    #[serde(flatten)]
+    pub message: LogMessage,
+}
+
+/// Receive time and byte offset of an entry in the log
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct IndexPoint {
+    received: f64,
+    offset: u64,
+}
+
+/// Index is stored next to the log with an added `.index` extension
+fn index_path(path: &Path) -> PathBuf {
+    let mut index_path = path.as_os_str().to_owned();
+    index_path.push(".index");
+    index_path.into()
+}
+
+/// Writes entries as json lines and keeps an index of receive times for seeking
+///
+/// Index points are written as json lines while recording so that the index stays valid
+/// if the recorder is killed
+pub struct LogWriter {
+    writer: BufWriter<File>,
+    index: File,
+    last_index_point: Option<IndexPoint>,
+    offset: u64,
+}
+
+impl LogWriter {
+    /// Replaces an existing log and its index
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            index: File::create(index_path(path))?,
+            last_index_point: None,
+            offset: 0,
+        })
+    }
+
+    pub fn write(&mut self, entry: &LogEntry) -> Result<()> {
+        let needs_index = self
+            .last_index_point
+            .is_none_or(|last| entry.received - last.received >= INDEX_INTERVAL);
+        if needs_index {
+            // flush regularly so little is lost if the recorder is killed
+            self.writer.flush()?;
+            let index_point = IndexPoint {
+                received: entry.received,
+                offset: self.offset,
+            };
+            let mut line = serde_json::to_string(&index_point)
+                .map_err(|_| PosePublisherError::JsonParsingError)?;
+            line.push('\n');
+            self.index.write_all(line.as_bytes())?;
+            self.last_index_point = Some(index_point);
+        }
+        let mut line =
+            serde_json::to_string(entry).map_err(|_| PosePublisherError::JsonParsingError)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        self.offset += line.len() as u64;
+        Ok(())
+    }
+
+    /// Flush log and index. Logs without an index are still readable
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.index.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads entries in the order they were recorded
+pub struct LogReader {
+    reader: BufReader<File>,
+    index: Vec<IndexPoint>,
+    /// Skip entries received before this time after seeking
+    skip_until: Option<f64>,
+}
+
+impl LogReader {
+    /// Index is rebuilt by scanning the log if it's missing or doesn't match the log
+    pub fn open(path: &Path) -> Result<Self> {
+        let log_len = std::fs::metadata(path)?.len();
+        let index = match std::fs::read_to_string(index_path(path))
+            .ok()
+            .and_then(|index| Self::parse_index(&index, log_len))
+        {
+            Some(index) => index,
+            None => Self::build_index(path)?,
+        };
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+            index,
+            skip_until: None,
+        })
+    }
+
+    /// None if the index is malformed or points past the end of the log
+    fn parse_index(index: &str, log_len: u64) -> Option<Vec<IndexPoint>> {
+        let mut points: Vec<IndexPoint> = vec![];
+        // last line may be incomplete if the recorder was killed
+        for line in index
+            .split_inclusive('\n')
+            .filter(|line| line.ends_with('\n'))
+        {
+            let point: IndexPoint = serde_json::from_str(line).ok()?;
+            let is_ordered = points.last().is_none_or(|last| last.offset < point.offset);
+            if !is_ordered || point.offset > log_len {
+                return None;
+            }
+            points.push(point);
+        }
+        Some(points)
+    }
+
+    fn build_index(path: &Path) -> Result<Vec<IndexPoint>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut index: Vec<IndexPoint> = vec![];
+        let mut offset = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let len = reader.read_line(&mut line)?;
+            // last line may be incomplete if the recorder was killed
+            if len == 0 || !line.ends_with('\n') {
+                return Ok(index);
+            }
+            let entry: LogEntry =
+                serde_json::from_str(&line).map_err(|_| PosePublisherError::JsonParsingError)?;
+            if index
+                .last()
+                .is_none_or(|last| entry.received - last.received >= INDEX_INTERVAL)
+            {
+                index.push(IndexPoint {
+                    received: entry.received,
+                    offset,
+                });
+            }
+            offset += len as u64;
+        }
+    }
+
+    /// Receive time of first entry
+    pub fn start_time(&self) -> Option<f64> {
+        self.index.first().map(|point| point.received)
+    }
+
+    /// Continue reading from first entry received at or after time
+    pub fn seek(&mut self, time: f64) -> Result<()> {
+        let offset = self
+            .index
+            .iter()
+            .take_while(|point| point.received <= time)
+            .last()
+            .map(|point| point.offset)
+            .unwrap_or(0);
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.skip_until = Some(time);
+        Ok(())
+    }
+
+    pub fn next_entry(&mut self) -> Result<Option<LogEntry>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 || !line.ends_with('\n') {
+                return Ok(None);
+            }
+            let entry: LogEntry =
+                serde_json::from_str(&line).map_err(|_| PosePublisherError::JsonParsingError)?;
+            match self.skip_until {
+                Some(time) if entry.received < time => continue,
+                _ => {
+                    self.skip_until = None;
+                    return Ok(Some(entry));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::CommandState, OccupancyGrid, PoseClientUpdate, Transform};
+
+    /// Log path in the temp directory that is unique to the test
+    fn log_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rustviz_{}_{}.log", std::process::id(), name))
+    }
+
+    fn entry(received: f64) -> LogEntry {
+        let mut update = PoseClientUpdate::new();
+        update.set_stamp(received);
+        LogEntry {
+            received,
+            message: LogMessage::Pose(update),
+        }
+    }
+
+    fn write_log(path: &Path, times: &[f64]) -> LogWriter {
+        let mut writer = LogWriter::create(path).unwrap();
+        for time in times {
+            writer.write(&entry(*time)).unwrap();
+        }
+        writer
+    }
+
+    fn read_all(reader: &mut LogReader) -> Vec<f64> {
+        std::iter::from_fn(|| reader.next_entry().unwrap())
+            .map(|entry| entry.received)
+            .collect()
+    }
+
+    fn remove_log(path: &Path) {
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(index_path(path));
+    }
+
+    #[test]
+    fn seek_uses_index() {
+        let path = log_path("seek");
+        write_log(&path, &[10., 10.5, 11., 12.5, 13.])
+            .finish()
+            .unwrap();
+        let mut reader = LogReader::open(&path).unwrap();
+        assert_eq!(reader.start_time(), Some(10.));
+        reader.seek(12.).unwrap();
+        assert_eq!(read_all(&mut reader), vec![12.5, 13.]);
+        remove_log(&path);
+    }
+
+    #[test]
+    fn index_is_usable_without_finishing() {
+        let path = log_path("unfinished");
+        let writer = write_log(&path, &[10., 11., 12.]);
+        // index is complete before the writer is dropped
+        let index = std::fs::read_to_string(index_path(&path)).unwrap();
+        let log_len = std::fs::metadata(&path).unwrap().len();
+        assert_eq!(LogReader::parse_index(&index, log_len).unwrap().len(), 3);
+        drop(writer);
+        let mut reader = LogReader::open(&path).unwrap();
+        reader.seek(11.).unwrap();
+        assert_eq!(read_all(&mut reader), vec![11., 12.]);
+        remove_log(&path);
+    }
+
+    #[test]
+    fn stale_index_is_replaced() {
+        let path = log_path("stale");
+        write_log(&path, &[10., 11., 12., 13., 14.])
+            .finish()
+            .unwrap();
+        write_log(&path, &[20.]).finish().unwrap();
+        let mut reader = LogReader::open(&path).unwrap();
+        assert_eq!(reader.start_time(), Some(20.));
+        reader.seek(20.).unwrap();
+        assert_eq!(read_all(&mut reader), vec![20.]);
+        remove_log(&path);
+    }
+
+    #[test]
+    fn index_past_end_of_log_is_rebuilt() {
+        let path = log_path("past_end");
+        write_log(&path, &[10., 11.]).finish().unwrap();
+        std::fs::write(
+            index_path(&path),
+            "{\"received\":10.0,\"offset\":0}\n{\"received\":11.0,\"offset\":100000}\n",
+        )
+        .unwrap();
+        let mut reader = LogReader::open(&path).unwrap();
+        reader.seek(11.).unwrap();
+        assert_eq!(read_all(&mut reader), vec![11.]);
+        remove_log(&path);
+    }
+
+    fn message_of_every_topic() -> Vec<LogMessage> {
+        let mut transforms = TransformUpdate::new();
+        transforms.add(Transform::new(
+            "map",
+            "robot",
+            (1., 0., 0.),
+            (0., 0., 0., 1.),
+        ));
+        vec![
+            LogMessage::Pose(PoseClientUpdate::new()),
+            LogMessage::PointCloud(PointCloud2::from_points("cloud", vec![(1., 2.)])),
+            LogMessage::Command(Command::goal(1, (1., 2.), 0.5)),
+            LogMessage::Path(crate::Path::from_points("path", vec![(0., 0., 0.)])),
+            LogMessage::PointCloud3(PointCloud3::from_points("cloud", vec![(1., 2., 3.)])),
+            LogMessage::LaserScan(LaserScan::new("scan", 0., 0.1, 0., 10., vec![1.])),
+            LogMessage::OccupancyGrid(
+                OccupancyGrid::new("grid", 0.1, 2, 2)
+                    .to_messages()
+                    .remove(0),
+            ),
+            LogMessage::Heartbeat(Heartbeat::new("robot", 1.)),
+            LogMessage::Transform(transforms),
+            LogMessage::CommandStatus(CommandStatus::new(1, CommandState::Accepted)),
+        ]
+    }
+
+    #[test]
+    fn every_topic_can_be_recorded() {
+        let names: Vec<_> = Topic::ALL.iter().map(Topic::name).collect();
+        let topic_names: Vec<_> = topics::ALL.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, topic_names);
+        for topic in Topic::ALL.iter() {
+            assert_eq!(topic.name().parse::<Topic>(), Ok(*topic));
+            assert_eq!(Some(topic.address()), topics::resolve(topic.name()));
+        }
+        let recorded: Vec<_> = message_of_every_topic()
+            .iter()
+            .map(LogMessage::topic)
+            .collect();
+        assert_eq!(recorded, Topic::ALL.to_vec());
+    }
+
+    #[test]
+    fn messages_of_every_topic_survive_logging() {
+        for message in message_of_every_topic() {
+            let topic = message.topic();
+            let entry = LogEntry {
+                received: 10.,
+                message,
+            };
+            let line = serde_json::to_string(&entry).unwrap();
+            let entry: LogEntry = serde_json::from_str(&line).unwrap();
+            assert_eq!(entry.message.topic(), topic);
+            // messages are stored as sent on their topic
+            let tagged = serde_json::to_value(&entry.message).unwrap();
+            let payload = tagged["message"].to_string();
+            assert!(topics::validate(topic.name(), &payload).is_ok());
+        }
+    }
+
+    #[test]
+    fn shifting_moves_stamps_of_every_topic() {
+        for message in message_of_every_topic() {
+            let stamp = message.header().stamp;
+            let shifted = message.shift_stamp(100.);
+            assert_eq!(shifted.header().stamp, stamp + 100.);
+        }
+    }
+}
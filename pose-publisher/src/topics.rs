@@ -0,0 +1,73 @@
+//! Default multicast addresses of all topics
+use crate::{
+    commands::{Command, CommandStatus},
+    Heartbeat, LaserScan, OccupancyGridMessage, Path, PointCloud2, PointCloud3, PoseClientUpdate,
+    PosePublisherError, Result, TransformUpdate,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::net::SocketAddrV4;
+
+pub const POSE: &str = "239.0.0.22:7072";
+pub const POINT_CLOUD: &str = "239.0.0.22:7075";
+pub const COMMAND: &str = "239.0.0.22:7076";
+pub const PATH: &str = "239.0.0.22:7077";
+pub const POINT_CLOUD_3D: &str = "239.0.0.22:7078";
+pub const LASER_SCAN: &str = "239.0.0.22:7079";
+pub const OCCUPANCY_GRID: &str = "239.0.0.22:7080";
+pub const HEARTBEAT: &str = "239.0.0.22:7081";
+pub const TRANSFORM: &str = "239.0.0.22:7082";
+pub const COMMAND_STATUS: &str = "239.0.0.22:7083";
+
+/// Topic names with their default addresses
+pub const ALL: [(&str, &str); 10] = [
+    ("pose", POSE),
+    ("point_cloud", POINT_CLOUD),
+    ("command", COMMAND),
+    ("path", PATH),
+    ("point_cloud_3d", POINT_CLOUD_3D),
+    ("laser_scan", LASER_SCAN),
+    ("occupancy_grid", OCCUPANCY_GRID),
+    ("heartbeat", HEARTBEAT),
+    ("transform", TRANSFORM),
+    ("command_status", COMMAND_STATUS),
+];
+
+/// Default address of topic name or the address itself if one is given
+pub fn resolve(topic: &str) -> Option<SocketAddrV4> {
+    let name = topic.to_lowercase().replace('-', "_");
+    ALL.iter()
+        .find(|(topic_name, _)| *topic_name == name)
+        .and_then(|(_, address)| address.parse().ok())
+        .or_else(|| topic.parse().ok())
+}
+
+/// Name of the topic using a default address
+pub fn name(address: &SocketAddrV4) -> Option<&'static str> {
+    ALL.iter()
+        .find(|(_, topic_address)| topic_address.parse().ok() == Some(*address))
+        .map(|(name, _)| *name)
+}
+
+fn check<T: DeserializeOwned>(payload: &str) -> Result<()> {
+    serde_json::from_str::<T>(payload)
+        .map(|_| ())
+        .map_err(|_| PosePublisherError::JsonParsingError)
+}
+
+/// Checks that payload is a message of the named topic. Unknown topics accept any json
+pub fn validate(name: &str, payload: &str) -> Result<()> {
+    match name {
+        "pose" => check::<PoseClientUpdate>(payload),
+        "point_cloud" => check::<PointCloud2>(payload),
+        "command" => check::<Command>(payload),
+        "path" => check::<Path>(payload),
+        "point_cloud_3d" => check::<PointCloud3>(payload),
+        "laser_scan" => check::<LaserScan>(payload),
+        "occupancy_grid" => check::<OccupancyGridMessage>(payload),
+        "heartbeat" => check::<Heartbeat>(payload),
+        "transform" => check::<TransformUpdate>(payload),
+        "command_status" => check::<CommandStatus>(payload),
+        _ => check::<Value>(payload),
+    }
+}
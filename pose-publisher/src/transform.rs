@@ -0,0 +1,645 @@
+use crate::header::{timestamp_now, Header};
+use crate::pose::Quaternion;
+use nalgebra as na;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use thiserror::Error;
+
+/// Seconds of history kept per frame
+const DEFAULT_CACHE_DURATION: f64 = 10.;
+
+/// Pose of child frame within parent frame at a point in time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct Transform {
+    parent_frame_id: String,
+    child_frame_id: String,
+    translation: (f32, f32, f32),
+    rotation: Quaternion,
+    /// Seconds since unix epoch
+    stamp: f64,
+    /// Valid at any time, for example a sensor bolted to a robot
+    #[serde(default)]
+    is_static: bool,
+}
+
+impl Transform {
+    /// Rotation in form (x, y, z, w). Stamped with current time
+    pub fn new(
+        parent_frame_id: &str,
+        child_frame_id: &str,
+        translation: (f32, f32, f32),
+        rotation: Quaternion,
+    ) -> Self {
+        Self {
+            parent_frame_id: parent_frame_id.to_owned(),
+            child_frame_id: child_frame_id.to_owned(),
+            translation,
+            rotation,
+            stamp: timestamp_now(),
+            is_static: false,
+        }
+    }
+
+    /// Rotation in form (x, y, z, w). Transform that never changes
+    pub fn new_static(
+        parent_frame_id: &str,
+        child_frame_id: &str,
+        translation: (f32, f32, f32),
+        rotation: Quaternion,
+    ) -> Self {
+        Self {
+            is_static: true,
+            ..Self::new(parent_frame_id, child_frame_id, translation, rotation)
+        }
+    }
+
+    /// Seconds since unix epoch
+    pub fn with_stamp(mut self, stamp: f64) -> Self {
+        self.stamp = stamp;
+        self
+    }
+
+    pub fn parent_frame_id(&self) -> &str {
+        &self.parent_frame_id
+    }
+
+    pub fn child_frame_id(&self) -> &str {
+        &self.child_frame_id
+    }
+
+    pub fn translation(&self) -> (f32, f32, f32) {
+        self.translation
+    }
+
+    /// in form (x, y, z, w)
+    pub fn rotation(&self) -> Quaternion {
+        self.rotation
+    }
+
+    pub fn stamp(&self) -> f64 {
+        self.stamp
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.is_static
+    }
+
+    fn isometry(&self) -> na::Isometry3<f32> {
+        let (x, y, z, w) = self.rotation;
+        na::Isometry3::from_parts(
+            na::Translation3::new(self.translation.0, self.translation.1, self.translation.2),
+            na::UnitQuaternion::new_normalize(na::Quaternion::new(w, x, y, z)),
+        )
+    }
+
+    fn from_isometry(
+        parent_frame_id: &str,
+        child_frame_id: &str,
+        isometry: &na::Isometry3<f32>,
+        stamp: f64,
+    ) -> Self {
+        let translation = isometry.translation.vector;
+        let rotation = isometry.rotation;
+        Self {
+            parent_frame_id: parent_frame_id.to_owned(),
+            child_frame_id: child_frame_id.to_owned(),
+            translation: (translation.x, translation.y, translation.z),
+            rotation: (rotation.i, rotation.j, rotation.k, rotation.w),
+            stamp,
+            is_static: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct TransformUpdate {
+    #[serde(default)]
+    header: Header,
+    transforms: Vec<Transform>,
+}
+
+impl Default for TransformUpdate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransformUpdate {
+    pub fn new() -> Self {
+        Self {
+            header: Header::new(),
+            transforms: vec![],
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn add(&mut self, transform: Transform) -> &mut Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    pub fn transforms(&self) -> &Vec<Transform> {
+        &self.transforms
+    }
+
+    /// Move header and transform stamps by offset seconds. Zero stamps stay unknown
+    pub fn shift_stamps(mut self, offset: f64) -> Self {
+        if self.header.has_stamp() {
+            self.header.stamp += offset;
+        }
+        for transform in &mut self.transforms {
+            if transform.stamp > 0. {
+                transform.stamp += offset;
+            }
+        }
+        self
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TransformError {
+    #[error("frame {0} does not exist")]
+    UnknownFrame(String),
+    #[error("frames {0} and {1} are not connected")]
+    NotConnected(String, String),
+    #[error("frame {0} has no transform at time {1}")]
+    Extrapolation(String, f64),
+    #[error("frame {0} is part of a cycle")]
+    Cycle(String),
+}
+
+type Result<T> = std::result::Result<T, TransformError>;
+
+struct Sample {
+    stamp: f64,
+    isometry: na::Isometry3<f32>,
+}
+
+/// History of a single frame relative to its parent
+struct FrameBuffer {
+    parent_frame_id: String,
+    is_static: bool,
+    samples: VecDeque<Sample>,
+}
+
+impl FrameBuffer {
+    fn latest_stamp(&self) -> Option<f64> {
+        if self.is_static {
+            None
+        } else {
+            self.samples.back().map(|sample| sample.stamp)
+        }
+    }
+
+    /// Interpolated transform from frame to parent. Latest if time is None
+    fn lookup(&self, frame_id: &str, time: Option<f64>) -> Result<na::Isometry3<f32>> {
+        let latest = self
+            .samples
+            .back()
+            .ok_or_else(|| TransformError::UnknownFrame(frame_id.to_owned()))?;
+        let time = match time {
+            Some(time) if !self.is_static => time,
+            _ => return Ok(latest.isometry),
+        };
+        let after = self
+            .samples
+            .iter()
+            .position(|sample| sample.stamp >= time)
+            .ok_or_else(|| TransformError::Extrapolation(frame_id.to_owned(), time))?;
+        let after_sample = &self.samples[after];
+        if (after_sample.stamp - time).abs() < f64::EPSILON {
+            return Ok(after_sample.isometry);
+        }
+        if after == 0 {
+            return Err(TransformError::Extrapolation(frame_id.to_owned(), time));
+        }
+        let before_sample = &self.samples[after - 1];
+        let ratio =
+            ((time - before_sample.stamp) / (after_sample.stamp - before_sample.stamp)) as f32;
+        let translation = before_sample
+            .isometry
+            .translation
+            .vector
+            .lerp(&after_sample.isometry.translation.vector, ratio);
+        let rotation = before_sample
+            .isometry
+            .rotation
+            .try_slerp(&after_sample.isometry.rotation, ratio, 1.0e-6)
+            .unwrap_or(before_sample.isometry.rotation);
+        Ok(na::Isometry3::from_parts(
+            na::Translation3::from(translation),
+            rotation,
+        ))
+    }
+}
+
+/// Buffer of timestamped transforms between named frames
+///
+/// Every frame has at most one parent so frames form a forest
+pub struct TransformTree {
+    frames: HashMap<String, FrameBuffer>,
+    cache_duration: f64,
+}
+
+impl Default for TransformTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransformTree {
+    pub fn new() -> Self {
+        Self {
+            frames: HashMap::new(),
+            cache_duration: DEFAULT_CACHE_DURATION,
+        }
+    }
+
+    /// Seconds of history kept per frame
+    pub fn with_cache_duration(mut self, cache_duration: f64) -> Self {
+        self.cache_duration = cache_duration;
+        self
+    }
+
+    /// Reparenting a frame or changing whether it is static drops its history
+    pub fn insert(&mut self, transform: &Transform) {
+        let cache_duration = self.cache_duration;
+        let frame = self
+            .frames
+            .entry(transform.child_frame_id.clone())
+            .or_insert_with(|| FrameBuffer {
+                parent_frame_id: transform.parent_frame_id.clone(),
+                is_static: transform.is_static,
+                samples: VecDeque::new(),
+            });
+        if frame.parent_frame_id != transform.parent_frame_id
+            || frame.is_static != transform.is_static
+            || transform.is_static
+        {
+            frame.parent_frame_id = transform.parent_frame_id.clone();
+            frame.is_static = transform.is_static;
+            frame.samples.clear();
+        }
+        let sample = Sample {
+            stamp: transform.stamp,
+            isometry: transform.isometry(),
+        };
+        match frame
+            .samples
+            .iter()
+            .rposition(|existing| existing.stamp <= sample.stamp)
+        {
+            Some(index) if (frame.samples[index].stamp - sample.stamp).abs() < f64::EPSILON => {
+                frame.samples[index] = sample
+            }
+            Some(index) => frame.samples.insert(index + 1, sample),
+            None => frame.samples.push_front(sample),
+        }
+        if let Some(newest) = frame.samples.back().map(|sample| sample.stamp) {
+            while frame
+                .samples
+                .front()
+                .is_some_and(|oldest| oldest.stamp < newest - cache_duration)
+            {
+                frame.samples.pop_front();
+            }
+        }
+    }
+
+    pub fn remove_frame(&mut self, frame_id: &str) {
+        self.frames.remove(frame_id);
+    }
+
+    /// Remove frames that have not been updated since `stamp`. Static frames are kept
+    pub fn remove_older_than(&mut self, stamp: f64) {
+        self.frames.retain(|_, frame| {
+            frame
+                .latest_stamp()
+                .is_none_or(|latest_stamp| latest_stamp >= stamp)
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Frame is known either as a child or as a parent of another frame
+    pub fn has_frame(&self, frame_id: &str) -> bool {
+        self.frames.contains_key(frame_id)
+            || self
+                .frames
+                .values()
+                .any(|frame| frame.parent_frame_id == frame_id)
+    }
+
+    pub fn parent(&self, frame_id: &str) -> Option<&str> {
+        self.frames
+            .get(frame_id)
+            .map(|frame| frame.parent_frame_id.as_str())
+    }
+
+    /// All (child, parent) pairs
+    pub fn frames(&self) -> Vec<(&str, &str)> {
+        self.frames
+            .iter()
+            .map(|(child, frame)| (child.as_str(), frame.parent_frame_id.as_str()))
+            .collect()
+    }
+
+    /// Stamp of the newest transform of a frame. None for static and unknown frames
+    pub fn latest_stamp(&self, frame_id: &str) -> Option<f64> {
+        self.frames
+            .get(frame_id)
+            .and_then(|frame| frame.latest_stamp())
+    }
+
+    /// Frame followed by its parents up to the root
+    fn ancestors(&self, frame_id: &str) -> Result<Vec<String>> {
+        let mut ancestors = vec![];
+        let mut visited = HashSet::new();
+        let mut current = Some(frame_id);
+        while let Some(frame_id) = current {
+            if !visited.insert(frame_id) {
+                return Err(TransformError::Cycle(frame_id.to_owned()));
+            }
+            ancestors.push(frame_id.to_owned());
+            current = self.parent(frame_id);
+        }
+        Ok(ancestors)
+    }
+
+    /// Transform from frame to one of its ancestors
+    fn to_ancestor(
+        &self,
+        frame_id: &str,
+        ancestor: &str,
+        time: Option<f64>,
+    ) -> Result<na::Isometry3<f32>> {
+        let mut transform = na::Isometry3::identity();
+        let mut current = frame_id;
+        while current != ancestor {
+            let frame = self
+                .frames
+                .get(current)
+                .ok_or_else(|| TransformError::UnknownFrame(current.to_owned()))?;
+            transform = frame.lookup(current, time)? * transform;
+            current = &frame.parent_frame_id;
+        }
+        Ok(transform)
+    }
+
+    /// Oldest of the newest stamps along the path between frames
+    ///
+    /// This is the latest time at which the whole path is known. None if all links are static
+    fn latest_common_time(&self, chain: &[String], ancestor: &str) -> Option<f64> {
+        chain
+            .iter()
+            .take_while(|frame_id| frame_id.as_str() != ancestor)
+            .filter_map(|frame_id| self.latest_stamp(frame_id))
+            .fold(None, |oldest: Option<f64>, stamp| {
+                Some(oldest.map_or(stamp, |oldest| oldest.min(stamp)))
+            })
+    }
+
+    /// Pose of `source` frame within `target` frame
+    ///
+    /// Links are interpolated at `time`. None uses the latest time all links are known at
+    pub fn lookup_transform(
+        &self,
+        target: &str,
+        source: &str,
+        time: Option<f64>,
+    ) -> Result<Transform> {
+        for frame_id in &[target, source] {
+            if !self.has_frame(frame_id) {
+                return Err(TransformError::UnknownFrame(frame_id.to_string()));
+            }
+        }
+        let source_chain = self.ancestors(source)?;
+        let target_chain = self.ancestors(target)?;
+        let common = target_chain
+            .iter()
+            .find(|frame_id| source_chain.contains(frame_id))
+            .ok_or_else(|| TransformError::NotConnected(target.to_owned(), source.to_owned()))?;
+        let time = time.or_else(|| {
+            let source_time = self.latest_common_time(&source_chain, common);
+            let target_time = self.latest_common_time(&target_chain, common);
+            match (source_time, target_time) {
+                (Some(source_time), Some(target_time)) => Some(source_time.min(target_time)),
+                (source_time, target_time) => source_time.or(target_time),
+            }
+        });
+        let source_to_common = self.to_ancestor(source, common, time)?;
+        let target_to_common = self.to_ancestor(target, common, time)?;
+        Ok(Transform::from_isometry(
+            target,
+            source,
+            &(target_to_common.inverse() * source_to_common),
+            time.unwrap_or(0.),
+        ))
+    }
+
+    /// Pose of `frame_id` within the root of its tree
+    pub fn transform_to_root(&self, frame_id: &str, time: Option<f64>) -> Result<Transform> {
+        let root = self
+            .ancestors(frame_id)?
+            .pop()
+            .ok_or_else(|| TransformError::UnknownFrame(frame_id.to_owned()))?;
+        self.lookup_transform(&root, frame_id, time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: Quaternion = (0., 0., 0., 1.);
+
+    fn translated(
+        parent: &str,
+        child: &str,
+        translation: (f32, f32, f32),
+        stamp: f64,
+    ) -> Transform {
+        Transform::new(parent, child, translation, IDENTITY).with_stamp(stamp)
+    }
+
+    fn assert_translation(transform: &Transform, expected: (f32, f32, f32)) {
+        let (x, y, z) = transform.translation();
+        let error = (x - expected.0).abs() + (y - expected.1).abs() + (z - expected.2).abs();
+        assert!(error < 1e-5, "{:?} != {:?}", (x, y, z), expected);
+    }
+
+    #[test]
+    fn lookup_interpolates_between_samples() {
+        let mut tree = TransformTree::new();
+        tree.insert(&translated("map", "robot", (0., 0., 0.), 10.));
+        tree.insert(&translated("map", "robot", (2., 4., 0.), 12.));
+        let transform = tree.lookup_transform("map", "robot", Some(11.)).unwrap();
+        assert_translation(&transform, (1., 2., 0.));
+        assert_eq!(transform.stamp(), 11.);
+        // latest sample when no time is given
+        let transform = tree.lookup_transform("map", "robot", None).unwrap();
+        assert_translation(&transform, (2., 4., 0.));
+    }
+
+    #[test]
+    fn lookup_interpolates_rotation() {
+        let half_turn = (0., 0., 1., 0.);
+        let mut tree = TransformTree::new();
+        tree.insert(&Transform::new("map", "robot", (0., 0., 0.), IDENTITY).with_stamp(0.));
+        tree.insert(&Transform::new("map", "robot", (0., 0., 0.), half_turn).with_stamp(2.));
+        let (x, y, z, w) = tree
+            .lookup_transform("map", "robot", Some(1.))
+            .unwrap()
+            .rotation();
+        let angle = std::f32::consts::FRAC_PI_4;
+        assert!(x.abs() < 1e-5 && y.abs() < 1e-5);
+        assert!((z.abs() - angle.sin()).abs() < 1e-5);
+        assert!((w.abs() - angle.cos()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn lookup_outside_of_history_is_extrapolation() {
+        let mut tree = TransformTree::new();
+        tree.insert(&translated("map", "robot", (0., 0., 0.), 10.));
+        tree.insert(&translated("map", "robot", (1., 0., 0.), 11.));
+        assert_eq!(
+            tree.lookup_transform("map", "robot", Some(9.)).unwrap_err(),
+            TransformError::Extrapolation("robot".to_owned(), 9.)
+        );
+        assert_eq!(
+            tree.lookup_transform("map", "robot", Some(12.))
+                .unwrap_err(),
+            TransformError::Extrapolation("robot".to_owned(), 12.)
+        );
+    }
+
+    #[test]
+    fn static_transform_is_valid_at_any_time() {
+        let mut tree = TransformTree::new();
+        tree.insert(&Transform::new_static(
+            "robot",
+            "lidar",
+            (0., 0., 1.),
+            IDENTITY,
+        ));
+        let transform = tree.lookup_transform("robot", "lidar", Some(0.)).unwrap();
+        assert_translation(&transform, (0., 0., 1.));
+    }
+
+    #[test]
+    fn lookup_across_branches() {
+        let mut tree = TransformTree::new();
+        tree.insert(&translated("map", "robot", (1., 0., 0.), 10.));
+        tree.insert(&translated("robot", "lidar", (0., 0., 1.), 10.));
+        tree.insert(&translated("map", "dock", (0., 3., 0.), 10.));
+        let transform = tree.lookup_transform("dock", "lidar", None).unwrap();
+        assert_eq!(transform.parent_frame_id(), "dock");
+        assert_eq!(transform.child_frame_id(), "lidar");
+        assert_translation(&transform, (1., -3., 1.));
+        let transform = tree.transform_to_root("lidar", None).unwrap();
+        assert_eq!(transform.parent_frame_id(), "map");
+        assert_translation(&transform, (1., 0., 1.));
+    }
+
+    #[test]
+    fn latest_common_time_is_used_across_branches() {
+        let mut tree = TransformTree::new();
+        tree.insert(&translated("map", "robot", (0., 0., 0.), 10.));
+        tree.insert(&translated("map", "robot", (2., 0., 0.), 12.));
+        tree.insert(&translated("map", "dock", (0., 0., 0.), 11.));
+        let transform = tree.lookup_transform("dock", "robot", None).unwrap();
+        assert_eq!(transform.stamp(), 11.);
+        assert_translation(&transform, (1., 0., 0.));
+    }
+
+    #[test]
+    fn separate_trees_are_not_connected() {
+        let mut tree = TransformTree::new();
+        tree.insert(&translated("map", "robot", (0., 0., 0.), 10.));
+        tree.insert(&translated("odom", "wheel", (0., 0., 0.), 10.));
+        assert_eq!(
+            tree.lookup_transform("map", "wheel", None).unwrap_err(),
+            TransformError::NotConnected("map".to_owned(), "wheel".to_owned())
+        );
+        assert_eq!(
+            tree.lookup_transform("map", "missing", None).unwrap_err(),
+            TransformError::UnknownFrame("missing".to_owned())
+        );
+    }
+
+    #[test]
+    fn cycle_is_detected() {
+        let mut tree = TransformTree::new();
+        tree.insert(&translated("a", "b", (0., 0., 0.), 10.));
+        tree.insert(&translated("b", "c", (0., 0., 0.), 10.));
+        tree.insert(&translated("c", "a", (0., 0., 0.), 10.));
+        assert!(matches!(
+            tree.lookup_transform("a", "c", None),
+            Err(TransformError::Cycle(_))
+        ));
+        assert!(matches!(
+            tree.transform_to_root("b", None),
+            Err(TransformError::Cycle(_))
+        ));
+    }
+
+    #[test]
+    fn history_older_than_cache_duration_is_dropped() {
+        let mut tree = TransformTree::new().with_cache_duration(5.);
+        for stamp in 0..=10 {
+            tree.insert(&translated(
+                "map",
+                "robot",
+                (stamp as f32, 0., 0.),
+                stamp as f64,
+            ));
+        }
+        assert!(tree.lookup_transform("map", "robot", Some(4.)).is_err());
+        let transform = tree.lookup_transform("map", "robot", Some(5.5)).unwrap();
+        assert_translation(&transform, (5.5, 0., 0.));
+    }
+
+    #[test]
+    fn stale_frames_are_removed_but_static_frames_are_kept() {
+        let mut tree = TransformTree::new();
+        tree.insert(&translated("map", "robot", (0., 0., 0.), 10.));
+        tree.insert(&translated("map", "dock", (0., 0., 0.), 20.));
+        tree.insert(&Transform::new_static(
+            "robot",
+            "lidar",
+            (0., 0., 1.),
+            IDENTITY,
+        ));
+        tree.remove_older_than(15.);
+        assert_eq!(tree.parent("robot"), None);
+        assert_eq!(tree.parent("dock"), Some("map"));
+        assert_eq!(tree.parent("lidar"), Some("robot"));
+    }
+
+    #[test]
+    fn reparenting_drops_history() {
+        let mut tree = TransformTree::new();
+        tree.insert(&translated("map", "robot", (0., 0., 0.), 10.));
+        tree.insert(&translated("odom", "robot", (1., 0., 0.), 11.));
+        assert_eq!(tree.parent("robot"), Some("odom"));
+        assert!(tree.lookup_transform("odom", "robot", Some(10.5)).is_err());
+    }
+
+    #[test]
+    fn shifting_moves_known_stamps() {
+        let mut update = TransformUpdate::new();
+        update.add(translated("map", "robot", (0., 0., 0.), 10.));
+        update.add(Transform::new_static("robot", "laser", (0., 0., 0.), IDENTITY).with_stamp(0.));
+        let stamp = update.header().stamp;
+        let update = update.shift_stamps(5.);
+        assert_eq!(update.header().stamp, stamp + 5.);
+        assert_eq!(update.transforms()[0].stamp(), 15.);
+        assert_eq!(update.transforms()[1].stamp(), 0.);
+    }
+}
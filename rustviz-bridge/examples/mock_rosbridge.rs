@@ -0,0 +1,188 @@
+//! Minimal rosbridge server publishing example ROS messages for testing the rosbridge bridge
+//!
+//! Publishes on every subscribed topic of a known type and prints messages clients publish
+use anyhow::Result;
+use clap::Clap;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    f64::consts::PI,
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tungstenite::Message;
+
+const PUBLISH_PERIOD: Duration = Duration::from_millis(100);
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    #[clap(short, long, default_value = "9090")]
+    port: u16,
+}
+
+fn header(frame_id: &str) -> Value {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    json!({
+        "stamp": {"secs": now.as_secs(), "nsecs": now.subsec_nanos()},
+        "frame_id": frame_id,
+    })
+}
+
+fn pose(x: f64, y: f64, yaw: f64) -> Value {
+    json!({
+        "position": {"x": x, "y": y, "z": 0.},
+        "orientation": {"x": 0., "y": 0., "z": (yaw / 2.).sin(), "w": (yaw / 2.).cos()},
+    })
+}
+
+fn marker(id: i32, kind: i32, pose: Value, scale: f64, points: Vec<Value>) -> Value {
+    json!({
+        "header": header("world"),
+        "ns": "mock",
+        "id": id,
+        "type": kind,
+        "action": 0,
+        "pose": pose,
+        "scale": {"x": scale, "y": scale, "z": scale},
+        "color": {"r": 0., "g": 1., "b": 0.2, "a": 1.},
+        "lifetime": {"secs": 1, "nsecs": 0},
+        "points": points,
+    })
+}
+
+fn point(x: f64, y: f64) -> Value {
+    json!({"x": x, "y": y, "z": 0.})
+}
+
+fn circle(radius: f64, count: usize) -> Vec<(f64, f64)> {
+    (0..count)
+        .map(|index| {
+            let angle = 2. * PI * index as f64 / count as f64;
+            (radius * angle.cos(), radius * angle.sin())
+        })
+        .collect()
+}
+
+/// Example message of a ROS type at time t
+fn message(ros_type: &str, t: f64) -> Option<Value> {
+    let ring = circle(1. + 0.2 * t.sin(), 24);
+    match ros_type {
+        "visualization_msgs/Marker" => Some(marker(0, 2, pose(t.cos(), t.sin(), 0.), 0.2, vec![])),
+        "visualization_msgs/MarkerArray" => Some(json!({
+            "markers": [
+                marker(1, 1, pose(-1.5, 0., t), 0.3, vec![]),
+                marker(2, 0, pose(0., 0., t), 0.8, vec![]),
+                marker(
+                    3,
+                    4,
+                    pose(0., 0., 0.),
+                    0.02,
+                    ring.iter().map(|(x, y)| point(*x, *y)).collect(),
+                ),
+                marker(
+                    4,
+                    7,
+                    pose(2., 0., t),
+                    0.1,
+                    vec![point(0., 0.), point(0.3, 0.), point(0.6, 0.)],
+                ),
+            ],
+        })),
+        "sensor_msgs/PointCloud2" => {
+            let mut data = vec![];
+            for (x, y) in &ring {
+                for value in &[*x as f32 * 2., *y as f32 * 2., 0.] {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Some(json!({
+                "header": header("world"),
+                "height": 1,
+                "width": ring.len(),
+                "fields": [
+                    {"name": "x", "offset": 0, "datatype": 7, "count": 1},
+                    {"name": "y", "offset": 4, "datatype": 7, "count": 1},
+                    {"name": "z", "offset": 8, "datatype": 7, "count": 1},
+                ],
+                "is_bigendian": false,
+                "point_step": 12,
+                "row_step": 12 * ring.len(),
+                "data": base64::encode(&data),
+                "is_dense": true,
+            }))
+        }
+        "geometry_msgs/PoseStamped" => Some(json!({
+            "header": header("world"),
+            "pose": pose(0.5 * t.cos(), 0.5, t),
+        })),
+        "nav_msgs/Path" => Some(json!({
+            "header": header("world"),
+            "poses": ring
+                .iter()
+                .map(|(x, y)| json!({"header": header("world"), "pose": pose(*x, *y, 0.)}))
+                .collect::<Vec<_>>(),
+        })),
+        _ => None,
+    }
+}
+
+fn serve(stream: TcpStream) -> Result<()> {
+    let mut socket = tungstenite::accept(stream)?;
+    socket
+        .get_ref()
+        .set_read_timeout(Some(Duration::from_millis(10)))?;
+    let mut subscriptions: HashMap<String, String> = HashMap::new();
+    let start = Instant::now();
+    let mut last_publish = Instant::now();
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let operation: Value = serde_json::from_str(&text).unwrap_or_default();
+                match operation["op"].as_str() {
+                    Some("subscribe") => {
+                        let topic = operation["topic"].as_str().unwrap_or_default();
+                        let ros_type = operation["type"].as_str().unwrap_or_default();
+                        println!("subscribe {} {}", topic, ros_type);
+                        subscriptions.insert(topic.to_owned(), ros_type.to_owned());
+                    }
+                    Some(op) => println!("{} {}", op, operation),
+                    None => (),
+                }
+            }
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(error))
+                if error.kind() == ErrorKind::WouldBlock || error.kind() == ErrorKind::TimedOut => {
+            }
+            Err(error) => return Err(error.into()),
+        }
+        if last_publish.elapsed() > PUBLISH_PERIOD {
+            last_publish = Instant::now();
+            let t = start.elapsed().as_secs_f64();
+            for (topic, ros_type) in &subscriptions {
+                if let Some(msg) = message(ros_type, t) {
+                    let publish = json!({"op": "publish", "topic": topic, "msg": msg});
+                    socket.send(Message::Text(publish.to_string()))?;
+                }
+            }
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let listener = TcpListener::bind(("127.0.0.1", args.port))?;
+    println!("Mock rosbridge on ws://{}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        std::thread::spawn(move || {
+            if let Err(error) = serve(stream) {
+                println!("Client disconnected {}", error);
+            }
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,402 @@
+//! Serves rustviz topics to Foxglove Studio over the Foxglove WebSocket protocol
+//!
+//! Objects and point clouds are sent as `foxglove.SceneUpdate` json messages.
+//! Messages clients publish are forwarded as commands
+use anyhow::Result;
+use clap::Clap;
+use pose_publisher::{
+    commands::Command, header::timestamp_now, CommandPublisher, CommandSubscriber,
+    PointCloudSubscriber, PosePublisherError, PoseSubscriber,
+};
+use rustviz_bridge::{commands, scene};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{SocketAddrV4, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tungstenite::{
+    error::ProtocolError,
+    handshake::server::{ErrorResponse, Request, Response},
+    http::HeaderValue,
+    Message, WebSocket,
+};
+
+const SUBPROTOCOL: &str = "foxglove.websocket.v1";
+const OP_MESSAGE_DATA: u8 = 0x01;
+/// How long a client thread waits for client messages before forwarding topics
+const READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+const POSE_CHANNEL: u32 = 1;
+const POINT_CLOUD_CHANNEL: u32 = 2;
+const COMMAND_CHANNEL: u32 = 3;
+
+const STATUS_WARNING: u8 = 1;
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    #[clap(short, long, default_value = "8765")]
+    port: u16,
+    /// Address to listen on. Use 0.0.0.0 to accept remote connections
+    #[clap(long, default_value = "127.0.0.1")]
+    bind: String,
+    /// Frame of objects without parent frame
+    #[clap(long, default_value = "world")]
+    fixed_frame: String,
+    #[clap(long, default_value = "239.0.0.22:7072")]
+    pose_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7075")]
+    point_cloud_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7076")]
+    command_address: SocketAddrV4,
+}
+
+/// Message of a topic shared by all clients
+#[derive(Clone)]
+struct Outgoing {
+    channel_id: u32,
+    stamp: u64,
+    payload: Arc<Vec<u8>>,
+}
+
+type Clients = Arc<Mutex<Vec<Sender<Outgoing>>>>;
+
+fn broadcast(clients: &Clients, outgoing: Outgoing) {
+    let mut clients = clients.lock().unwrap();
+    clients.retain(|client| client.send(outgoing.clone()).is_ok());
+}
+
+/// Converts and broadcasts messages of one topic until the subscriber fails
+fn forward<T, F, C>(clients: Clients, channel_id: u32, next: F, convert: C)
+where
+    F: Fn() -> Result<T, PosePublisherError> + Send + 'static,
+    C: Fn(&T) -> Value + Send + 'static,
+    T: 'static,
+{
+    std::thread::spawn(move || loop {
+        match next() {
+            Ok(message) => {
+                let payload = convert(&message).to_string().into_bytes();
+                let outgoing = Outgoing {
+                    channel_id,
+                    stamp: (timestamp_now() * 1e9) as u64,
+                    payload: Arc::new(payload),
+                };
+                broadcast(&clients, outgoing);
+            }
+            Err(PosePublisherError::JsonParsingError) => eprintln!("Failed to parse message"),
+            Err(error) => {
+                eprintln!("Receiving failed {}", error);
+                return;
+            }
+        }
+    });
+}
+
+fn channels() -> Value {
+    let scene_schema = scene::schema();
+    let command_schema = serde_json::to_string(&schemars::schema_for!(Command)).unwrap_or_default();
+    json!([
+        {
+            "id": POSE_CHANNEL,
+            "topic": "/pose",
+            "encoding": "json",
+            "schemaName": scene::SCHEMA_NAME,
+            "schema": scene_schema,
+            "schemaEncoding": "jsonschema",
+        },
+        {
+            "id": POINT_CLOUD_CHANNEL,
+            "topic": "/point_cloud",
+            "encoding": "json",
+            "schemaName": scene::SCHEMA_NAME,
+            "schema": scene_schema,
+            "schemaEncoding": "jsonschema",
+        },
+        {
+            "id": COMMAND_CHANNEL,
+            "topic": "/command",
+            "encoding": "json",
+            "schemaName": "rustviz.Command",
+            "schema": command_schema,
+            "schemaEncoding": "jsonschema",
+        },
+    ])
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum ClientOperation {
+    Subscribe {
+        subscriptions: Vec<Subscription>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Unsubscribe {
+        subscription_ids: Vec<u32>,
+    },
+    Advertise {
+        channels: Vec<ClientChannel>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Unadvertise {
+        channel_ids: Vec<u32>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Subscription {
+    id: u32,
+    channel_id: u32,
+}
+
+#[derive(Deserialize)]
+struct ClientChannel {
+    id: u32,
+    topic: String,
+    encoding: String,
+}
+
+struct Session {
+    socket: WebSocket<TcpStream>,
+    receiver: Receiver<Outgoing>,
+    /// Subscription ids by channel id
+    subscriptions: HashMap<u32, u32>,
+    /// Topics of channels advertised by the client
+    client_channels: HashMap<u32, String>,
+    command_publisher: CommandPublisher,
+    command_ids: Arc<AtomicU32>,
+}
+
+impl Session {
+    fn send_json(&mut self, message: Value) -> Result<()> {
+        self.socket.send(Message::Text(message.to_string()))?;
+        Ok(())
+    }
+
+    fn send_status(&mut self, message: &str) -> Result<()> {
+        self.send_json(json!({
+            "op": "status",
+            "level": STATUS_WARNING,
+            "message": message,
+        }))
+    }
+
+    fn handle_text(&mut self, text: &str) -> Result<()> {
+        let operation = match serde_json::from_str(text) {
+            Ok(operation) => operation,
+            // unsupported operations like service calls are ignored
+            Err(_) => return Ok(()),
+        };
+        match operation {
+            ClientOperation::Subscribe { subscriptions } => {
+                for subscription in subscriptions {
+                    self.subscriptions
+                        .insert(subscription.channel_id, subscription.id);
+                }
+            }
+            ClientOperation::Unsubscribe { subscription_ids } => self
+                .subscriptions
+                .retain(|_, id| !subscription_ids.contains(id)),
+            ClientOperation::Advertise { channels } => {
+                for channel in channels {
+                    if channel.encoding == "json" {
+                        self.client_channels.insert(channel.id, channel.topic);
+                    } else {
+                        self.send_status(&format!(
+                            "Unsupported encoding {} on {}. Only json can be published",
+                            channel.encoding, channel.topic
+                        ))?;
+                    }
+                }
+            }
+            ClientOperation::Unadvertise { channel_ids } => {
+                for channel_id in channel_ids {
+                    self.client_channels.remove(&channel_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Client message data is opcode, channel id and payload
+    fn handle_binary(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() < 5 || data[0] != OP_MESSAGE_DATA {
+            return Ok(());
+        }
+        let mut channel_id = [0; 4];
+        channel_id.copy_from_slice(&data[1..5]);
+        let topic = match self.client_channels.get(&u32::from_le_bytes(channel_id)) {
+            Some(topic) => topic.clone(),
+            None => return Ok(()),
+        };
+        let id = self.command_ids.fetch_add(1, Ordering::Relaxed);
+        let command = serde_json::from_slice(&data[5..])
+            .ok()
+            .and_then(|message| commands::from_json(&topic, &message, id));
+        match command {
+            Some(command) => {
+                if let Err(error) = self.command_publisher.publish(&command) {
+                    eprintln!("Failed to publish command {}", error);
+                }
+                Ok(())
+            }
+            None => self.send_status(&format!("Message on {} is not a command", topic)),
+        }
+    }
+
+    /// Server message data is opcode, subscription id, timestamp in nanoseconds and payload
+    fn send_pending(&mut self) -> Result<()> {
+        while let Ok(outgoing) = self.receiver.try_recv() {
+            let subscription_id = match self.subscriptions.get(&outgoing.channel_id) {
+                Some(id) => *id,
+                None => continue,
+            };
+            let mut data = Vec::with_capacity(13 + outgoing.payload.len());
+            data.push(OP_MESSAGE_DATA);
+            data.extend_from_slice(&subscription_id.to_le_bytes());
+            data.extend_from_slice(&outgoing.stamp.to_le_bytes());
+            data.extend_from_slice(&outgoing.payload);
+            self.socket.send(Message::Binary(data))?;
+        }
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<()> {
+        self.send_json(json!({
+            "op": "serverInfo",
+            "name": "rustviz",
+            "capabilities": ["clientPublish"],
+            "supportedEncodings": ["json"],
+            "metadata": {},
+        }))?;
+        self.send_json(json!({
+            "op": "advertise",
+            "channels": channels(),
+        }))?;
+        loop {
+            match self.socket.read() {
+                Ok(Message::Text(text)) => self.handle_text(&text)?,
+                Ok(Message::Binary(data)) => self.handle_binary(&data)?,
+                Ok(_) => (),
+                Err(tungstenite::Error::Io(error))
+                    if error.kind() == ErrorKind::WouldBlock
+                        || error.kind() == ErrorKind::TimedOut => {}
+                Err(error) => return Err(error.into()),
+            }
+            self.send_pending()?;
+        }
+    }
+}
+
+/// Accepts the Foxglove subprotocol if the client offers it
+#[allow(clippy::result_large_err)] // signature is given by tungstenite
+fn negotiate_subprotocol(
+    request: &Request,
+    mut response: Response,
+) -> Result<Response, ErrorResponse> {
+    let offers_subprotocol = request
+        .headers()
+        .get_all("Sec-WebSocket-Protocol")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.split(',').any(|offer| offer.trim() == SUBPROTOCOL));
+    if offers_subprotocol {
+        response.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            HeaderValue::from_static(SUBPROTOCOL),
+        );
+    }
+    Ok(response)
+}
+
+fn serve_client(
+    stream: TcpStream,
+    clients: Clients,
+    command_address: SocketAddrV4,
+    command_ids: Arc<AtomicU32>,
+) -> Result<()> {
+    let socket = tungstenite::accept_hdr(stream, negotiate_subprotocol)?;
+    socket.get_ref().set_read_timeout(Some(READ_TIMEOUT))?;
+    let (sender, receiver) = channel();
+    clients.lock().unwrap().push(sender);
+    let mut session = Session {
+        socket,
+        receiver,
+        subscriptions: HashMap::new(),
+        client_channels: HashMap::new(),
+        command_publisher: CommandPublisher::new(command_address)?,
+        command_ids,
+    };
+    let error = match session.run() {
+        Ok(()) => return Ok(()),
+        Err(error) => error,
+    };
+    match error.downcast_ref() {
+        Some(tungstenite::Error::ConnectionClosed)
+        | Some(tungstenite::Error::AlreadyClosed)
+        | Some(tungstenite::Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)) => Ok(()),
+        _ => Err(error),
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let clients: Clients = Arc::new(Mutex::new(vec![]));
+    let pose_subscriber = PoseSubscriber::new_blocking(args.pose_address)?;
+    let point_cloud_subscriber = PointCloudSubscriber::new_blocking(args.point_cloud_address)?;
+    let command_subscriber = CommandSubscriber::new_blocking(args.command_address)?;
+
+    let fixed_frame = args.fixed_frame.clone();
+    forward(
+        clients.clone(),
+        POSE_CHANNEL,
+        move || pose_subscriber.next(),
+        move |update| json!(scene::from_pose_update(update, &fixed_frame)),
+    );
+    let fixed_frame = args.fixed_frame.clone();
+    forward(
+        clients.clone(),
+        POINT_CLOUD_CHANNEL,
+        move || point_cloud_subscriber.next(),
+        move |point_cloud| json!(scene::from_point_cloud(point_cloud, &fixed_frame)),
+    );
+    forward(
+        clients.clone(),
+        COMMAND_CHANNEL,
+        move || command_subscriber.next(),
+        |command| json!(command),
+    );
+
+    let listener = TcpListener::bind((args.bind.as_str(), args.port))?;
+    println!("Serving rustviz on ws://{}", listener.local_addr()?);
+    let command_ids = Arc::new(AtomicU32::new(commands::first_command_id()));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("Failed to accept client {}", error);
+                continue;
+            }
+        };
+        let clients = clients.clone();
+        let command_ids = command_ids.clone();
+        let command_address = args.command_address;
+        std::thread::spawn(move || {
+            let peer = stream.peer_addr();
+            if let Err(error) = serve_client(stream, clients, command_address, command_ids) {
+                eprintln!("Client {:?} disconnected {}", peer, error);
+            }
+        });
+    }
+    Ok(())
+}
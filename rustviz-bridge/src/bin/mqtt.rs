@@ -0,0 +1,175 @@
+//! Forwards rustviz topics between the multicast group and an MQTT broker
+//!
+//! Messages from the broker are checked against the topic type before they
+//! are published on the multicast group
+use anyhow::{anyhow, Result};
+use clap::Clap;
+use pose_publisher::{RawPublisher, RawSubscriber, MAX_PAYLOAD_SIZE};
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use rustviz_bridge::mqtt;
+use std::{collections::HashMap, net::SocketAddrV4, time::Duration};
+
+const RECONNECT_PERIOD: Duration = Duration::from_secs(1);
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+/// Messages from the broker have to fit into a multicast datagram
+const MAX_PACKET_SIZE: usize = MAX_PAYLOAD_SIZE;
+const REQUEST_CAPACITY: usize = 64;
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    /// Broker host
+    #[clap(long, default_value = "localhost")]
+    host: String,
+    #[clap(long, default_value = "1883")]
+    port: u16,
+    /// Defaults to rustviz-bridge with process id
+    #[clap(long)]
+    client_id: Option<String>,
+    #[clap(long)]
+    username: Option<String>,
+    #[clap(long)]
+    password: Option<String>,
+    /// Prefix of MQTT topics
+    #[clap(long, default_value = "rustviz")]
+    prefix: String,
+    /// Rustviz topics forwarded from multicast to MQTT, like pose,point_cloud
+    #[clap(long, use_delimiter = true)]
+    to_mqtt: Vec<String>,
+    /// Rustviz topics forwarded from MQTT to multicast, like command
+    #[clap(long, use_delimiter = true)]
+    from_mqtt: Vec<String>,
+    /// Quality of service level 0, 1 or 2
+    #[clap(long, default_value = "0")]
+    qos: u8,
+    /// Topics forwarded to MQTT as retained messages so that new subscribers
+    /// get the latest message, for example pose
+    #[clap(long, use_delimiter = true)]
+    retain: Vec<String>,
+}
+
+/// Publishes messages of a multicast topic to MQTT until the subscriber fails
+fn forward_to_mqtt(
+    mut client: Client,
+    address: SocketAddrV4,
+    mqtt_topic: String,
+    qos: QoS,
+    retain: bool,
+) -> Result<()> {
+    let subscriber = RawSubscriber::new_blocking(address)?;
+    std::thread::spawn(move || loop {
+        let payload = match subscriber.next() {
+            Ok(payload) => payload,
+            Err(error) => {
+                eprintln!("Receiving failed {}", error);
+                return;
+            }
+        };
+        // drop messages while disconnected instead of blocking on a full request queue
+        if let Err(error) = client.try_publish(mqtt_topic.as_str(), qos, retain, payload) {
+            eprintln!("Failed to publish on {} {}", mqtt_topic, error);
+        }
+    });
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let qos = mqtt::qos(args.qos)?;
+    let to_mqtt = args
+        .to_mqtt
+        .iter()
+        .map(|name| mqtt::topic(name))
+        .collect::<Result<Vec<_>>>()?;
+    let from_mqtt = args
+        .from_mqtt
+        .iter()
+        .map(|name| mqtt::topic(name))
+        .collect::<Result<Vec<_>>>()?;
+    let retained = args
+        .retain
+        .iter()
+        .map(|name| mqtt::topic(name).map(|(name, _)| name))
+        .collect::<Result<Vec<_>>>()?;
+    if to_mqtt.is_empty() && from_mqtt.is_empty() {
+        return Err(anyhow!(
+            "no topics to forward. Use --to-mqtt or --from-mqtt"
+        ));
+    }
+    // own multicast messages are received again and would be sent back and forth
+    if let Some((name, _)) = to_mqtt.iter().find(|topic| from_mqtt.contains(topic)) {
+        return Err(anyhow!("{} can only be forwarded in one direction", name));
+    }
+
+    let client_id = args
+        .client_id
+        .clone()
+        .unwrap_or_else(|| format!("rustviz-bridge-{}", std::process::id()));
+    let mut options = MqttOptions::new(client_id, args.host.clone(), args.port);
+    options.set_keep_alive(KEEP_ALIVE);
+    options.set_max_packet_size(MAX_PACKET_SIZE, MAX_PACKET_SIZE);
+    if let Some(username) = &args.username {
+        options.set_credentials(username, args.password.as_deref().unwrap_or_default());
+    }
+    let (client, mut connection) = Client::new(options, REQUEST_CAPACITY);
+
+    for (name, address) in &to_mqtt {
+        let retain = retained.contains(name);
+        forward_to_mqtt(
+            client.clone(),
+            *address,
+            mqtt::mqtt_topic(&args.prefix, name),
+            qos,
+            retain,
+        )?;
+    }
+    let mut publishers = HashMap::new();
+    for (name, address) in &from_mqtt {
+        publishers.insert(
+            mqtt::mqtt_topic(&args.prefix, name),
+            (*name, RawPublisher::new(*address)?),
+        );
+    }
+
+    for event in connection.iter() {
+        match event {
+            // subscriptions are renewed on every connection since sessions are clean
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                println!("Connected to {}:{}", args.host, args.port);
+                // subscribing waits for room in the request queue, which is only made by
+                // polling the connection on this thread
+                let mut client = client.clone();
+                let mqtt_topics: Vec<_> = publishers.keys().cloned().collect();
+                std::thread::spawn(move || {
+                    for mqtt_topic in mqtt_topics {
+                        if let Err(error) = client.subscribe(mqtt_topic.as_str(), qos) {
+                            eprintln!("Failed to subscribe to {} {}", mqtt_topic, error);
+                        }
+                    }
+                });
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                let (name, publisher) = match publishers.get(&publish.topic) {
+                    Some(publisher) => publisher,
+                    None => continue,
+                };
+                let payload = match mqtt::rustviz_payload(name, &publish.payload) {
+                    Ok(payload) => payload,
+                    Err(error) => {
+                        eprintln!("Message on {} is {}", publish.topic, error);
+                        continue;
+                    }
+                };
+                if let Err(error) = publisher.publish(payload) {
+                    eprintln!("Failed to forward message on {} {}", publish.topic, error);
+                }
+            }
+            Ok(_) => (),
+            Err(error) => {
+                eprintln!("Connection to {}:{} failed {}", args.host, args.port, error);
+                std::thread::sleep(RECONNECT_PERIOD);
+            }
+        }
+    }
+    Ok(())
+}
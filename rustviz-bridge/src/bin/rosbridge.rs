@@ -0,0 +1,268 @@
+//! Connects to a rosbridge v2 server and converts ROS topics to rustviz topics
+//!
+//! Markers, point clouds, poses and paths are published on the rustviz multicast topics.
+//! Goal commands are published back to ROS as `geometry_msgs/PoseStamped` and acknowledged
+//! with an accepted command status
+use anyhow::{anyhow, Result};
+use clap::Clap;
+use pose_publisher::{
+    commands::{CommandState, CommandStatus},
+    CommandStatusPublisher, CommandSubscriber, PathPublisher, PointCloudPublisher,
+    PoseClientUpdate, PosePublisher,
+};
+use rustviz_bridge::ros;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::ErrorKind,
+    net::{SocketAddrV4, TcpStream},
+    time::Duration,
+};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+/// How long to wait for ROS messages before checking for commands
+const READ_TIMEOUT: Duration = Duration::from_millis(10);
+const RECONNECT_PERIOD: Duration = Duration::from_secs(1);
+/// Number of recently forwarded commands remembered to skip retransmissions
+const FORWARDED_HISTORY: usize = 64;
+
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    /// Url of the rosbridge server
+    #[clap(short, long, default_value = "ws://localhost:9090")]
+    url: String,
+    /// visualization_msgs/Marker topics
+    #[clap(long, default_value = "/visualization_marker")]
+    markers: Vec<String>,
+    /// visualization_msgs/MarkerArray topics
+    #[clap(long, default_value = "/visualization_marker_array")]
+    marker_arrays: Vec<String>,
+    /// sensor_msgs/PointCloud2 topics. Only x and y fields are used
+    #[clap(long)]
+    point_clouds: Vec<String>,
+    /// geometry_msgs/PoseStamped topics
+    #[clap(long)]
+    poses: Vec<String>,
+    /// nav_msgs/Path topics
+    #[clap(long)]
+    paths: Vec<String>,
+    /// Topic goal commands are published on
+    #[clap(long, default_value = "/move_base_simple/goal")]
+    goal_topic: String,
+    /// Frame of goals without frame id
+    #[clap(long, default_value = "map")]
+    goal_frame: String,
+    #[clap(long, default_value = "239.0.0.22:7072")]
+    pose_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7075")]
+    point_cloud_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7077")]
+    path_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7076")]
+    command_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7083")]
+    command_status_address: SocketAddrV4,
+}
+
+struct Bridge {
+    /// ROS type of each subscribed topic
+    topics: HashMap<String, &'static str>,
+    goal_topic: String,
+    goal_frame: String,
+    pose_publisher: PosePublisher,
+    point_cloud_publisher: PointCloudPublisher,
+    path_publisher: PathPublisher,
+    command_subscriber: CommandSubscriber,
+    command_status_publisher: CommandStatusPublisher,
+    /// Ids and stamps of recently forwarded goals. Commands are retransmitted until acknowledged
+    /// and the stamp tells them apart from new commands of a restarted viewer reusing ids
+    forwarded: VecDeque<(u32, f64)>,
+    markers: ros::ForwardedMarkers,
+    /// Number of parts the last cloud of each topic was split into
+    point_cloud_parts: HashMap<String, usize>,
+}
+
+fn parse<T: DeserializeOwned>(message: Value) -> Result<T> {
+    Ok(serde_json::from_value(message)?)
+}
+
+impl Bridge {
+    fn new(args: &Args) -> Result<Self> {
+        let mut topics = HashMap::new();
+        let subscriptions = [
+            (&args.markers, ros::MARKER),
+            (&args.marker_arrays, ros::MARKER_ARRAY),
+            (&args.point_clouds, ros::POINT_CLOUD),
+            (&args.poses, ros::POSE_STAMPED),
+            (&args.paths, ros::PATH),
+        ];
+        for (names, ros_type) in subscriptions.iter() {
+            for name in names.iter() {
+                topics.insert(name.clone(), *ros_type);
+            }
+        }
+        Ok(Self {
+            topics,
+            goal_topic: args.goal_topic.clone(),
+            goal_frame: args.goal_frame.clone(),
+            pose_publisher: PosePublisher::new(args.pose_address)?,
+            point_cloud_publisher: PointCloudPublisher::new(args.point_cloud_address)?,
+            path_publisher: PathPublisher::new(args.path_address)?,
+            command_subscriber: CommandSubscriber::new(args.command_address)?,
+            command_status_publisher: CommandStatusPublisher::new(args.command_status_address)?,
+            forwarded: VecDeque::new(),
+            markers: ros::ForwardedMarkers::default(),
+            point_cloud_parts: HashMap::new(),
+        })
+    }
+
+    fn connect(&self, url: &str) -> Result<WebSocket<MaybeTlsStream<TcpStream>>> {
+        let (mut socket, _) = tungstenite::connect(url)?;
+        match socket.get_mut() {
+            MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(READ_TIMEOUT))?,
+            _ => return Err(anyhow!("only plain ws urls are supported")),
+        }
+        for (topic, ros_type) in &self.topics {
+            let subscribe = json!({"op": "subscribe", "topic": topic, "type": ros_type});
+            socket.send(Message::Text(subscribe.to_string()))?;
+        }
+        let advertise = json!({
+            "op": "advertise",
+            "topic": self.goal_topic,
+            "type": ros::POSE_STAMPED,
+        });
+        socket.send(Message::Text(advertise.to_string()))?;
+        Ok(socket)
+    }
+
+    fn handle_publish(&mut self, topic: &str, message: Value) -> Result<()> {
+        let ros_type = match self.topics.get(topic) {
+            Some(ros_type) => *ros_type,
+            None => return Ok(()),
+        };
+        // ROS topic names are used as rustviz ids
+        match ros_type {
+            ros::MARKER | ros::MARKER_ARRAY => {
+                let update = if ros_type == ros::MARKER {
+                    ros::from_markers(&[parse::<ros::Marker>(message)?], &mut self.markers)
+                } else {
+                    ros::from_markers(
+                        &parse::<ros::MarkerArray>(message)?.markers,
+                        &mut self.markers,
+                    )
+                };
+                if update.deletions.has_deletions() {
+                    self.pose_publisher.publish(&update.deletions)?;
+                }
+                if !update.pose_update.updates().is_empty() {
+                    self.pose_publisher.publish(&update.pose_update)?;
+                }
+                for point_cloud in &update.point_clouds {
+                    self.point_cloud_publisher.publish(point_cloud)?;
+                }
+            }
+            ros::POINT_CLOUD => match ros::from_point_cloud(topic, &parse(message)?) {
+                Some(point_clouds) => {
+                    for point_cloud in &point_clouds {
+                        self.point_cloud_publisher.publish(point_cloud)?;
+                    }
+                    // parts of a larger previous cloud would stay until they time out
+                    let previous = self
+                        .point_cloud_parts
+                        .insert(topic.to_owned(), point_clouds.len())
+                        .unwrap_or_default();
+                    if previous > point_clouds.len() {
+                        let mut deletions = PoseClientUpdate::new();
+                        for index in point_clouds.len()..previous {
+                            deletions.delete(&ros::point_cloud_part_id(topic, index));
+                        }
+                        self.pose_publisher.publish(&deletions)?;
+                    }
+                }
+                None => eprintln!("Point cloud on {} has no float x and y fields", topic),
+            },
+            ros::POSE_STAMPED => self
+                .pose_publisher
+                .publish(&ros::from_pose_stamped(topic, &parse(message)?))?,
+            ros::PATH => self
+                .path_publisher
+                .publish(&ros::from_path(topic, &parse(message)?))?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn handle_text(&mut self, text: &str) -> Result<()> {
+        let mut operation: Value = serde_json::from_str(text)?;
+        match operation.get("op").and_then(Value::as_str) {
+            Some("publish") => {
+                let topic = operation
+                    .get("topic")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned();
+                let message = operation.get_mut("msg").map(Value::take);
+                if let Err(error) = self.handle_publish(&topic, message.unwrap_or_default()) {
+                    eprintln!("Failed to convert message on {} {}", topic, error);
+                }
+            }
+            Some("status") => eprintln!("rosbridge: {}", operation["msg"]),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn forward_goals(&mut self, socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Result<()> {
+        while let Ok(command) = self.command_subscriber.next() {
+            let goal = match ros::goal_from_command(&command, &self.goal_frame) {
+                Some(goal) => goal,
+                None => continue,
+            };
+            let key = (command.id(), command.header().stamp);
+            // retransmissions are acknowledged again in case the status was lost
+            if !self.forwarded.contains(&key) {
+                let publish = json!({"op": "publish", "topic": self.goal_topic, "msg": goal});
+                socket.send(Message::Text(publish.to_string()))?;
+                self.forwarded.push_back(key);
+                if self.forwarded.len() > FORWARDED_HISTORY {
+                    self.forwarded.pop_front();
+                }
+            }
+            let status = CommandStatus::new(command.id(), CommandState::Accepted)
+                .with_text(&format!("forwarded to {}", self.goal_topic));
+            if let Err(error) = self.command_status_publisher.publish(&status) {
+                eprintln!("Failed to publish command status {}", error);
+            }
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, url: &str) -> Result<()> {
+        let mut socket = self.connect(url)?;
+        println!("Connected to {}", url);
+        loop {
+            match socket.read() {
+                Ok(Message::Text(text)) => self.handle_text(&text)?,
+                Ok(_) => (),
+                Err(tungstenite::Error::Io(error))
+                    if error.kind() == ErrorKind::WouldBlock
+                        || error.kind() == ErrorKind::TimedOut => {}
+                Err(error) => return Err(error.into()),
+            }
+            self.forward_goals(&mut socket)?;
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut bridge = Bridge::new(&args)?;
+    loop {
+        if let Err(error) = bridge.run(&args.url) {
+            eprintln!("Connection to {} failed {}", args.url, error);
+        }
+        std::thread::sleep(RECONNECT_PERIOD);
+    }
+}
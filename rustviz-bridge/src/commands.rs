@@ -0,0 +1,185 @@
+//! Commands from json messages published by clients of a bridge
+use pose_publisher::{commands::Command, header::timestamp_now};
+use serde_json::Value;
+
+/// First id of commands created by this process
+///
+/// The viewer and every bridge publish commands on the same topic and the viewer counts
+/// from zero, so ids are seeded from the clock and process id to keep them apart
+pub fn first_command_id() -> u32 {
+    let millis = (timestamp_now() * 1000.) as u64;
+    (millis as u32) ^ std::process::id().rotate_left(16)
+}
+
+/// Yaw of a quaternion in range -Pi -> Pi
+pub fn yaw(x: f64, y: f64, z: f64, w: f64) -> f32 {
+    (2. * (w * z + x * y)).atan2(1. - 2. * (y * y + z * z)) as f32
+}
+
+fn number(value: &Value, key: &str) -> Option<f64> {
+    value.get(key).and_then(Value::as_f64)
+}
+
+fn point(value: &Value) -> Option<(f64, f64, f64)> {
+    Some((
+        number(value, "x")?,
+        number(value, "y")?,
+        number(value, "z").unwrap_or(0.),
+    ))
+}
+
+/// Position and yaw of a pose with position and orientation fields
+fn pose(value: &Value) -> Option<((f32, f32), f32)> {
+    let (x, y, _) = point(value.get("position")?)?;
+    let orientation = value.get("orientation")?;
+    let angle = yaw(
+        number(orientation, "x")?,
+        number(orientation, "y")?,
+        number(orientation, "z")?,
+        number(orientation, "w")?,
+    );
+    Some(((x as f32, y as f32), angle))
+}
+
+/// Converts a client message to a command with the given id
+///
+/// Accepts rustviz commands as well as ROS style poses and points, with or without
+/// header and covariance. Poses become goals or initial poses if the topic
+/// is named like one and points become clicked points
+pub fn from_json(topic: &str, message: &Value, id: u32) -> Option<Command> {
+    if let Ok(command) = serde_json::from_value::<Command>(message.clone()) {
+        let mut forwarded = Command::from_kind(id, command.kind().clone());
+        if let Some(frame_id) = &command.header().frame_id {
+            forwarded = forwarded.with_frame_id(frame_id);
+        }
+        return Some(forwarded);
+    }
+    let frame_id = message
+        .get("header")
+        .and_then(|header| header.get("frame_id"))
+        .and_then(Value::as_str);
+    let stamped_pose = message
+        .get("pose")
+        .map(|pose| pose.get("pose").unwrap_or(pose));
+    let command = if let Some((point, angle)) = stamped_pose.or(Some(message)).and_then(pose) {
+        if topic.trim_start_matches('/').starts_with("initial") {
+            Command::initial_pose(id, point, angle)
+        } else {
+            Command::goal(id, point, angle)
+        }
+    } else {
+        let (x, y, z) = point(message.get("point").unwrap_or(message))?;
+        Command::clicked_point(id, (x as f32, y as f32, z as f32))
+    };
+    Some(match frame_id {
+        Some(frame_id) if !frame_id.is_empty() => command.with_frame_id(frame_id),
+        _ => command,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pose_publisher::commands::CommandKind;
+    use serde_json::json;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn kind(topic: &str, message: Value) -> Option<CommandKind> {
+        from_json(topic, &message, 7).map(|command| command.kind().clone())
+    }
+
+    fn assert_pose(kind: Option<CommandKind>, goal: bool) {
+        let (point, angle) = match kind {
+            Some(CommandKind::Goal { point, angle }) if goal => (point, angle),
+            Some(CommandKind::InitialPose { point, angle }) if !goal => (point, angle),
+            kind => panic!("unexpected command {:?}", kind),
+        };
+        assert_eq!(point, (1., 2.));
+        assert!((angle - FRAC_PI_2).abs() < 1e-6);
+    }
+
+    fn pose() -> Value {
+        let half = 0.5_f64.sqrt();
+        json!({
+            "position": {"x": 1.0, "y": 2.0, "z": 0.0},
+            "orientation": {"x": 0.0, "y": 0.0, "z": half, "w": half}
+        })
+    }
+
+    #[test]
+    fn pose_stamped_becomes_goal() {
+        let message = json!({"header": {"frame_id": "map"}, "pose": pose()});
+        let command = from_json("/move_base_simple/goal", &message, 7).unwrap();
+        assert_eq!(command.id(), 7);
+        assert_eq!(command.header().frame_id, Some("map".to_owned()));
+        assert_pose(Some(command.kind().clone()), true);
+        assert_pose(kind("/goal", pose()), true);
+    }
+
+    #[test]
+    fn pose_with_covariance_on_initial_topic_becomes_initial_pose() {
+        let message = json!({
+            "header": {"frame_id": ""},
+            "pose": {"pose": pose(), "covariance": vec![0.0; 36]}
+        });
+        let command = from_json("/initialpose", &message, 7).unwrap();
+        assert_eq!(command.header().frame_id, None);
+        assert_pose(Some(command.kind().clone()), false);
+    }
+
+    #[test]
+    fn point_becomes_clicked_point() {
+        let message = json!({"point": {"x": 1.0, "y": 2.0, "z": 3.0}});
+        assert_eq!(
+            kind("/clicked_point", message),
+            Some(CommandKind::ClickedPoint {
+                point: (1., 2., 3.)
+            })
+        );
+        assert_eq!(
+            kind("/clicked_point", json!({"x": 1.0, "y": 2.0})),
+            Some(CommandKind::ClickedPoint {
+                point: (1., 2., 0.)
+            })
+        );
+    }
+
+    #[test]
+    fn rustviz_command_gets_new_id() {
+        let message =
+            serde_json::to_value(Command::waypoints(1, vec![(1., 2.)]).with_frame_id("odom"))
+                .unwrap();
+        let command = from_json("/rustviz", &message, 7).unwrap();
+        assert_eq!(command.id(), 7);
+        assert_eq!(command.header().frame_id, Some("odom".to_owned()));
+        assert_eq!(
+            command.kind(),
+            &CommandKind::Waypoints {
+                points: vec![(1., 2.)]
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        for message in &[
+            json!({}),
+            json!([1.0, 2.0]),
+            json!("goal"),
+            json!({"x": "1", "y": 2.0}),
+            json!({"point": {"x": 1.0}}),
+            json!({"pose": {"position": {"x": 1.0, "y": 2.0}}}),
+            json!({"pose": {"position": {"x": 1.0, "y": 2.0}, "orientation": {"w": 1.0}}}),
+        ] {
+            assert!(kind("/goal", message.clone()).is_none(), "{}", message);
+        }
+    }
+
+    #[test]
+    fn yaw_of_quaternion() {
+        assert_eq!(yaw(0., 0., 0., 1.), 0.);
+        let half = 0.5_f64.sqrt();
+        assert!((yaw(0., 0., half, half) - FRAC_PI_2).abs() < 1e-6);
+        assert!((yaw(0., 0., half, -half) + FRAC_PI_2).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,5 @@
+//! Bridges between rustviz multicast topics and other transports
+pub mod commands;
+pub mod mqtt;
+pub mod ros;
+pub mod scene;
@@ -0,0 +1,93 @@
+//! Mapping between rustviz topics and MQTT topics
+//!
+//! Each rustviz topic maps to MQTT topic `<prefix>/<topic name>` with the json payload
+//! unchanged
+use anyhow::{anyhow, Result};
+use pose_publisher::topics;
+use rumqttc::QoS;
+use std::net::SocketAddrV4;
+
+/// Canonical name and default address of a rustviz topic name
+pub fn topic(name: &str) -> Result<(&'static str, SocketAddrV4)> {
+    topics::resolve(name)
+        .and_then(|address| Some((topics::name(&address)?, address)))
+        .ok_or_else(|| {
+            let names: Vec<_> = topics::ALL.iter().map(|(name, _)| *name).collect();
+            anyhow!("unknown topic {}. Use one of {}", name, names.join(", "))
+        })
+}
+
+pub fn qos(level: u8) -> Result<QoS> {
+    match level {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        _ => Err(anyhow!("qos has to be 0, 1 or 2")),
+    }
+}
+
+/// MQTT topic of a canonical rustviz topic name
+pub fn mqtt_topic(prefix: &str, name: &str) -> String {
+    format!("{}/{}", prefix, name)
+}
+
+/// Payload of a message from the broker if it is a valid message of the named topic
+pub fn rustviz_payload<'a>(name: &str, payload: &'a [u8]) -> Result<&'a str> {
+    let payload = std::str::from_utf8(payload).map_err(|_| anyhow!("not utf8"))?;
+    topics::validate(name, payload).map_err(|_| anyhow!("not a valid {} message", name))?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pose_publisher::{commands::Command, PoseClientUpdate};
+
+    #[test]
+    fn topic_names_are_canonical() {
+        let (name, address) = topic("point-cloud").unwrap();
+        assert_eq!(name, "point_cloud");
+        assert_eq!(topic("POINT_CLOUD").unwrap(), (name, address));
+        assert_eq!(topic(&address.to_string()).unwrap().0, "point_cloud");
+        assert_eq!(
+            mqtt_topic("robot/rustviz", name),
+            "robot/rustviz/point_cloud"
+        );
+    }
+
+    #[test]
+    fn unknown_topics_are_rejected() {
+        let error = topic("camera").unwrap_err().to_string();
+        assert!(error.contains("unknown topic camera"));
+        assert!(error.contains("pose"));
+        // addresses need to be one of the default topics to have a name
+        assert!(topic("239.0.0.1:1234").is_err());
+    }
+
+    #[test]
+    fn qos_levels() {
+        assert_eq!(qos(0).unwrap(), QoS::AtMostOnce);
+        assert_eq!(qos(1).unwrap(), QoS::AtLeastOnce);
+        assert_eq!(qos(2).unwrap(), QoS::ExactlyOnce);
+        assert!(qos(3).is_err());
+    }
+
+    #[test]
+    fn valid_payload_is_passed_through_unchanged() {
+        let command = serde_json::to_string(&Command::goal(1, (1., 2.), 0.)).unwrap();
+        assert_eq!(
+            rustviz_payload("command", command.as_bytes()).unwrap(),
+            command
+        );
+        let pose = serde_json::to_string(&PoseClientUpdate::new()).unwrap();
+        assert_eq!(rustviz_payload("pose", pose.as_bytes()).unwrap(), pose);
+    }
+
+    #[test]
+    fn invalid_payloads_are_rejected() {
+        assert!(rustviz_payload("command", &[0xff, 0xfe]).is_err());
+        assert!(rustviz_payload("command", b"{}").is_err());
+        let pose = serde_json::to_string(&PoseClientUpdate::new()).unwrap();
+        assert!(rustviz_payload("command", pose.as_bytes()).is_err());
+    }
+}
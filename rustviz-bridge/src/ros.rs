@@ -0,0 +1,1036 @@
+//! ROS messages as sent by rosbridge and their rustviz equivalents
+//!
+//! Only the fields needed for conversion are declared. Stamps accept both the ROS 1
+//! (`secs`, `nsecs`) and ROS 2 (`sec`, `nanosec`) field names
+use pose_publisher::{
+    commands::{Command, CommandKind},
+    header::timestamp_now,
+    point_cloud::PointStyle,
+    pose::{Color, Shape},
+    Lifetime, ObjectPose, Path, PointCloud2, PoseClientUpdate, MAX_PAYLOAD_SIZE,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, convert::TryInto};
+
+pub const MARKER: &str = "visualization_msgs/Marker";
+pub const MARKER_ARRAY: &str = "visualization_msgs/MarkerArray";
+pub const POINT_CLOUD: &str = "sensor_msgs/PointCloud2";
+pub const POSE_STAMPED: &str = "geometry_msgs/PoseStamped";
+pub const PATH: &str = "nav_msgs/Path";
+
+const MARKER_ARROW: i32 = 0;
+const MARKER_CUBE: i32 = 1;
+const MARKER_SPHERE: i32 = 2;
+const MARKER_CYLINDER: i32 = 3;
+const MARKER_LINE_STRIP: i32 = 4;
+const MARKER_LINE_LIST: i32 = 5;
+const MARKER_CUBE_LIST: i32 = 6;
+const MARKER_SPHERE_LIST: i32 = 7;
+const MARKER_POINTS: i32 = 8;
+
+const ACTION_DELETE: i32 = 2;
+const ACTION_DELETE_ALL: i32 = 3;
+
+const FIELD_FLOAT32: u8 = 7;
+const FIELD_FLOAT64: u8 = 8;
+
+/// Length of axes drawn for poses
+const POSE_AXES_LENGTH: f32 = 0.3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct Time {
+    #[serde(alias = "sec")]
+    pub secs: i64,
+    #[serde(alias = "nanosec")]
+    pub nsecs: i64,
+}
+
+impl Time {
+    pub fn from_seconds(seconds: f64) -> Self {
+        Self {
+            secs: seconds.trunc() as i64,
+            nsecs: (seconds.fract() * 1e9) as i64,
+        }
+    }
+
+    pub fn to_seconds(self) -> f64 {
+        self.secs as f64 + self.nsecs as f64 / 1e9
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Header {
+    pub stamp: Time,
+    pub frame_id: String,
+}
+
+impl Header {
+    /// Zero if the publisher did not stamp the message
+    fn stamp(&self) -> f64 {
+        self.stamp.to_seconds()
+    }
+
+    fn frame_id(&self) -> Option<&str> {
+        Some(self.frame_id.as_str()).filter(|frame_id| !frame_id.is_empty())
+    }
+}
+
+/// Also used for `geometry_msgs/Vector3`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(default)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Point {
+    fn as_f32(self) -> (f32, f32, f32) {
+        (self.x as f32, self.y as f32, self.z as f32)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Quaternion {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+            w: 1.,
+        }
+    }
+}
+
+impl Quaternion {
+    fn from_yaw(yaw: f32) -> Self {
+        let half = yaw as f64 / 2.;
+        Self {
+            x: 0.,
+            y: 0.,
+            z: half.sin(),
+            w: half.cos(),
+        }
+    }
+
+    /// Uninitialized all zero quaternions are treated as identity
+    fn normalized(self) -> Self {
+        let norm = (self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt();
+        if norm < f64::EPSILON {
+            Self::default()
+        } else {
+            Self {
+                x: self.x / norm,
+                y: self.y / norm,
+                z: self.z / norm,
+                w: self.w / norm,
+            }
+        }
+    }
+
+    fn as_f32(self) -> (f32, f32, f32, f32) {
+        (self.x as f32, self.y as f32, self.z as f32, self.w as f32)
+    }
+
+    fn rotate(self, point: Point) -> Point {
+        let Quaternion { x, y, z, w } = self.normalized();
+        // v + 2w(q x v) + 2q x (q x v)
+        let (cx, cy, cz) = (
+            y * point.z - z * point.y,
+            z * point.x - x * point.z,
+            x * point.y - y * point.x,
+        );
+        Point {
+            x: point.x + 2. * (w * cx + y * cz - z * cy),
+            y: point.y + 2. * (w * cy + z * cx - x * cz),
+            z: point.z + 2. * (w * cz + x * cy - y * cx),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(default)]
+pub struct Pose {
+    pub position: Point,
+    pub orientation: Quaternion,
+}
+
+impl Pose {
+    /// Point given relative to this pose in the parent frame
+    fn transform(&self, point: Point) -> Point {
+        let rotated = self.orientation.rotate(point);
+        Point {
+            x: rotated.x + self.position.x,
+            y: rotated.y + self.position.y,
+            z: rotated.z + self.position.z,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PoseStamped {
+    pub header: Header,
+    pub pose: Pose,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(default)]
+pub struct ColorRgba {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl ColorRgba {
+    /// Closest of the colors rustviz can draw
+    fn nearest(self) -> Color {
+        let colors = [
+            Color::Red,
+            Color::Green,
+            Color::Blue,
+            Color::Cyan,
+            Color::Magenta,
+            Color::Yellow,
+        ];
+        let distance = |color: &Color| {
+            let (r, g, b) = color.to_rgb();
+            (r - self.r).powi(2) + (g - self.g).powi(2) + (b - self.b).powi(2)
+        };
+        colors
+            .iter()
+            .copied()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap_or(Color::Red)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Marker {
+    pub header: Header,
+    pub ns: String,
+    pub id: i32,
+    #[serde(rename = "type")]
+    pub kind: i32,
+    pub action: i32,
+    pub pose: Pose,
+    pub scale: Point,
+    pub color: ColorRgba,
+    /// Zero is forever
+    pub lifetime: Time,
+    pub points: Vec<Point>,
+}
+
+impl Marker {
+    fn rustviz_id(&self) -> String {
+        if self.ns.is_empty() {
+            self.id.to_string()
+        } else {
+            format!("{}/{}", self.ns, self.id)
+        }
+    }
+
+    fn lifetime(&self) -> Lifetime {
+        match self.lifetime.to_seconds() {
+            seconds if seconds > 0. => Lifetime::Duration(seconds as f32),
+            _ => Lifetime::Forever,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct MarkerArray {
+    pub markers: Vec<Marker>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PointField {
+    pub name: String,
+    pub offset: u32,
+    pub datatype: u8,
+    pub count: u32,
+}
+
+/// rosbridge sends byte arrays base64 encoded unless configured otherwise
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Bytes {
+    Base64(String),
+    Array(Vec<u8>),
+}
+
+impl Default for Bytes {
+    fn default() -> Self {
+        Bytes::Array(vec![])
+    }
+}
+
+impl Bytes {
+    fn decode(&self) -> Option<Vec<u8>> {
+        match self {
+            Bytes::Base64(data) => base64::decode(data).ok(),
+            Bytes::Array(data) => Some(data.clone()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RosPointCloud2 {
+    pub header: Header,
+    pub height: u32,
+    pub width: u32,
+    pub fields: Vec<PointField>,
+    pub is_bigendian: bool,
+    pub point_step: u32,
+    pub row_step: u32,
+    pub data: Bytes,
+}
+
+impl RosPointCloud2 {
+    /// Reads a float field of the point at given byte offset
+    fn read(&self, data: &[u8], field: &PointField, point_offset: usize) -> Option<f64> {
+        let start = point_offset + field.offset as usize;
+        match field.datatype {
+            FIELD_FLOAT32 => {
+                let bytes: [u8; 4] = data.get(start..start + 4)?.try_into().ok()?;
+                Some(if self.is_bigendian {
+                    f32::from_be_bytes(bytes)
+                } else {
+                    f32::from_le_bytes(bytes)
+                } as f64)
+            }
+            FIELD_FLOAT64 => {
+                let bytes: [u8; 8] = data.get(start..start + 8)?.try_into().ok()?;
+                Some(if self.is_bigendian {
+                    f64::from_be_bytes(bytes)
+                } else {
+                    f64::from_le_bytes(bytes)
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// x and y of all valid points. None if the cloud has no float x and y fields
+    fn points(&self) -> Option<Vec<(f32, f32)>> {
+        let field = |name| self.fields.iter().find(|field| field.name == name);
+        let (x_field, y_field) = (field("x")?, field("y")?);
+        let data = self.data.decode()?;
+        let mut points = vec![];
+        for row in 0..self.height as usize {
+            for column in 0..self.width as usize {
+                let offset = row * self.row_step as usize + column * self.point_step as usize;
+                let x = self.read(&data, x_field, offset)?;
+                let y = self.read(&data, y_field, offset)?;
+                if x.is_finite() && y.is_finite() {
+                    points.push((x as f32, y as f32));
+                }
+            }
+        }
+        Some(points)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RosPath {
+    pub header: Header,
+    pub poses: Vec<PoseStamped>,
+}
+
+/// Markers forwarded so far. A delete all only removes these and leaves entities
+/// of other rustviz publishers alone
+#[derive(Default, Debug)]
+pub struct ForwardedMarkers {
+    namespaces: BTreeSet<String>,
+    /// Ids of markers without namespace since they share the default namespace
+    ids: BTreeSet<String>,
+}
+
+impl ForwardedMarkers {
+    fn add(&mut self, marker: &Marker) {
+        if marker.ns.is_empty() {
+            self.ids.insert(marker.rustviz_id());
+        } else {
+            self.namespaces.insert(marker.ns.clone());
+        }
+    }
+}
+
+/// Markers converted to rustviz messages
+#[derive(Default)]
+pub struct MarkerUpdate {
+    /// Published before `pose_update` since rustviz applies deletions after updates
+    /// and markers replace their old segments
+    pub deletions: PoseClientUpdate,
+    pub pose_update: PoseClientUpdate,
+    pub point_clouds: Vec<PointCloud2>,
+}
+
+impl MarkerUpdate {
+    fn add_line(&mut self, marker: &Marker, id: &str, start: Point, end: Point) {
+        let object = self.pose_update.add(id, start.as_f32());
+        object
+            .with_namespace(&marker.ns)
+            .with_shape(Shape::Line(end.as_f32()))
+            .with_color(marker.color.nearest())
+            .with_lifetime(marker.lifetime());
+        if let Some(frame_id) = marker.header.frame_id() {
+            object.with_parent_frame_id(frame_id);
+        }
+    }
+
+    fn add_shape(&mut self, marker: &Marker, id: &str, shape: Shape) -> &mut ObjectPose {
+        let object = self.pose_update.add(id, marker.pose.position.as_f32());
+        object
+            .with_namespace(&marker.ns)
+            .with_shape(shape)
+            .with_rotation(marker.pose.orientation.normalized().as_f32())
+            .with_color(marker.color.nearest())
+            .with_lifetime(marker.lifetime());
+        if let Some(frame_id) = marker.header.frame_id() {
+            object.with_parent_frame_id(frame_id);
+        }
+        object
+    }
+
+    fn add_point_cloud(&mut self, marker: &Marker, id: &str, style: PointStyle) {
+        let points = marker
+            .points
+            .iter()
+            .map(|point| {
+                let point = marker.pose.transform(*point);
+                (point.x as f32, point.y as f32)
+            })
+            .collect();
+        let mut point_cloud = PointCloud2::from_points(id, points)
+            .with_namespace(&marker.ns)
+            .with_color(marker.color.nearest())
+            .with_point_size(marker.scale.x as f32)
+            .with_style(style)
+            .with_lifetime(marker.lifetime());
+        if marker.header.stamp() > 0. {
+            point_cloud = point_cloud.with_stamp(marker.header.stamp());
+        }
+        if let Some(frame_id) = marker.header.frame_id() {
+            point_cloud = point_cloud.with_parent_frame_id(frame_id);
+        }
+        self.point_clouds.push(point_cloud);
+    }
+
+    fn delete_marker(&mut self, id: &str) {
+        self.deletions.delete(id);
+        // line markers are split into segments with ids under the marker id
+        self.deletions.delete_matching(&format!("{}/*", id));
+    }
+
+    /// Delete all in a namespace only removes markers of that namespace
+    fn delete_all(&mut self, marker: &Marker, forwarded: &ForwardedMarkers) {
+        if !marker.ns.is_empty() {
+            self.deletions.delete_namespace(&marker.ns);
+            return;
+        }
+        for namespace in &forwarded.namespaces {
+            self.deletions.delete_namespace(namespace);
+        }
+        for id in &forwarded.ids {
+            self.delete_marker(id);
+        }
+    }
+
+    /// Text, mesh and triangle list markers have no rustviz equivalent and are skipped
+    pub fn add(&mut self, marker: &Marker, forwarded: &mut ForwardedMarkers) {
+        let id = marker.rustviz_id();
+        match marker.action {
+            ACTION_DELETE => {
+                self.delete_marker(&id);
+                return;
+            }
+            ACTION_DELETE_ALL => {
+                self.delete_all(marker, forwarded);
+                return;
+            }
+            _ => forwarded.add(marker),
+        }
+        if marker.header.stamp() > 0. {
+            self.pose_update.set_stamp(marker.header.stamp());
+        }
+        let scale = marker.scale.as_f32();
+        match marker.kind {
+            MARKER_ARROW => {
+                let (start, end) = match marker.points.as_slice() {
+                    [start, end, ..] => {
+                        (marker.pose.transform(*start), marker.pose.transform(*end))
+                    }
+                    _ => (
+                        marker.pose.position,
+                        marker.pose.transform(Point {
+                            x: marker.scale.x,
+                            y: 0.,
+                            z: 0.,
+                        }),
+                    ),
+                };
+                self.add_line(marker, &id, start, end);
+            }
+            // cylinders are drawn as their bounding box
+            MARKER_CUBE | MARKER_CYLINDER => {
+                self.add_shape(marker, &id, Shape::Cube(scale.0, scale.1, scale.2));
+            }
+            MARKER_SPHERE => {
+                self.add_shape(marker, &id, Shape::Sphere(0.5))
+                    .with_scale(scale);
+            }
+            MARKER_LINE_STRIP | MARKER_LINE_LIST => {
+                // shorter lines than before must not leave old segments behind
+                self.deletions.delete_matching(&format!("{}/*", id));
+                let points: Vec<_> = marker
+                    .points
+                    .iter()
+                    .map(|point| marker.pose.transform(*point))
+                    .collect();
+                let pairs: Vec<_> = if marker.kind == MARKER_LINE_STRIP {
+                    points.windows(2).map(|pair| (pair[0], pair[1])).collect()
+                } else {
+                    points
+                        .chunks_exact(2)
+                        .map(|pair| (pair[0], pair[1]))
+                        .collect()
+                };
+                for (index, (start, end)) in pairs.into_iter().enumerate() {
+                    self.add_line(marker, &format!("{}/{}", id, index), start, end);
+                }
+            }
+            MARKER_CUBE_LIST => self.add_point_cloud(marker, &id, PointStyle::Cubes),
+            MARKER_SPHERE_LIST => self.add_point_cloud(marker, &id, PointStyle::Spheres),
+            MARKER_POINTS => self.add_point_cloud(marker, &id, PointStyle::Squares),
+            _ => (),
+        }
+    }
+}
+
+/// Deletions of all markers are published before their objects, so a delete all
+/// following additions in the same array removes nothing of the array
+pub fn from_markers<'a>(
+    markers: impl IntoIterator<Item = &'a Marker>,
+    forwarded: &mut ForwardedMarkers,
+) -> MarkerUpdate {
+    let mut update = MarkerUpdate::default();
+    for marker in markers {
+        update.add(marker, forwarded);
+    }
+    update
+}
+
+/// Id of a part of a cloud split by [`from_point_cloud`]
+pub fn point_cloud_part_id(id: &str, index: usize) -> String {
+    if index == 0 {
+        id.to_owned()
+    } else {
+        format!("{}/{}", id, index)
+    }
+}
+
+fn fits_into_datagram(point_cloud: &PointCloud2) -> bool {
+    serde_json::to_vec(point_cloud).is_ok_and(|payload| payload.len() <= MAX_PAYLOAD_SIZE)
+}
+
+/// Point cloud with the x and y fields of the ROS cloud. None if it has no such fields
+///
+/// Clouds too large for one datagram are split into parts named by [`point_cloud_part_id`]
+pub fn from_point_cloud(id: &str, point_cloud: &RosPointCloud2) -> Option<Vec<PointCloud2>> {
+    let convert = |id: &str, points: Vec<(f32, f32)>| {
+        let mut converted = PointCloud2::from_points(id, points);
+        if point_cloud.header.stamp() > 0. {
+            converted = converted.with_stamp(point_cloud.header.stamp());
+        }
+        if let Some(frame_id) = point_cloud.header.frame_id() {
+            converted = converted.with_parent_frame_id(frame_id);
+        }
+        converted
+    };
+    let points = point_cloud.points()?;
+    let whole = convert(id, points.clone());
+    let size = serde_json::to_vec(&whole).ok()?.len();
+    if size <= MAX_PAYLOAD_SIZE {
+        return Some(vec![whole]);
+    }
+    let split = |parts: usize| -> Vec<PointCloud2> {
+        points
+            .chunks(points.len().div_ceil(parts))
+            .enumerate()
+            .map(|(index, points)| convert(&point_cloud_part_id(id, index), points.to_vec()))
+            .collect()
+    };
+    // points differ in length so an even split may still need a part more
+    let parts = (size.div_ceil(MAX_PAYLOAD_SIZE)..points.len())
+        .map(split)
+        .find(|parts| parts.iter().all(fits_into_datagram))
+        .unwrap_or_else(|| split(points.len()));
+    Some(parts)
+}
+
+/// Pose drawn as axes
+pub fn from_pose_stamped(id: &str, pose: &PoseStamped) -> PoseClientUpdate {
+    let mut update = PoseClientUpdate::new();
+    if pose.header.stamp() > 0. {
+        update.set_stamp(pose.header.stamp());
+    }
+    if let Some(frame_id) = pose.header.frame_id() {
+        update.set_frame_id(frame_id);
+    }
+    update
+        .add(id, pose.pose.position.as_f32())
+        .with_shape(Shape::Axes(POSE_AXES_LENGTH))
+        .with_rotation(pose.pose.orientation.normalized().as_f32());
+    update
+}
+
+pub fn from_path(id: &str, path: &RosPath) -> Path {
+    let poses = path
+        .poses
+        .iter()
+        .map(|pose| {
+            (
+                pose.pose.position.as_f32(),
+                pose.pose.orientation.normalized().as_f32(),
+            )
+        })
+        .collect();
+    let mut converted = Path::from_poses(id, poses);
+    if path.header.stamp() > 0. {
+        converted = converted.with_stamp(path.header.stamp());
+    }
+    if let Some(frame_id) = path.header.frame_id() {
+        converted = converted.with_parent_frame_id(frame_id);
+    }
+    converted
+}
+
+/// Goal pose of a goal command. Other commands have no PoseStamped equivalent
+pub fn goal_from_command(command: &Command, default_frame_id: &str) -> Option<PoseStamped> {
+    let (point, angle) = match command.kind() {
+        CommandKind::Goal { point, angle } => (*point, *angle),
+        _ => return None,
+    };
+    let header = command.header();
+    let stamp = if header.has_stamp() {
+        header.stamp
+    } else {
+        timestamp_now()
+    };
+    Some(PoseStamped {
+        header: Header {
+            stamp: Time::from_seconds(stamp),
+            frame_id: header
+                .frame_id
+                .clone()
+                .unwrap_or_else(|| default_frame_id.to_owned()),
+        },
+        pose: Pose {
+            position: Point {
+                x: point.0 as f64,
+                y: point.1 as f64,
+                z: 0.,
+            },
+            orientation: Quaternion::from_yaw(angle),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn marker(message: serde_json::Value) -> Marker {
+        serde_json::from_value(message).unwrap()
+    }
+
+    fn convert(markers: &[Marker]) -> MarkerUpdate {
+        from_markers(markers, &mut ForwardedMarkers::default())
+    }
+
+    fn point(x: f64, y: f64, z: f64) -> serde_json::Value {
+        json!({"x": x, "y": y, "z": z})
+    }
+
+    #[test]
+    fn ros_1_and_ros_2_stamps_are_read() {
+        let ros_1: Header =
+            serde_json::from_value(json!({"stamp": {"secs": 2, "nsecs": 500_000_000}})).unwrap();
+        let ros_2: Header =
+            serde_json::from_value(json!({"stamp": {"sec": 2, "nanosec": 500_000_000}})).unwrap();
+        assert_eq!(ros_1.stamp(), 2.5);
+        assert_eq!(ros_2.stamp(), 2.5);
+        assert_eq!(ros_1.frame_id(), None);
+    }
+
+    #[test]
+    fn shape_markers_become_objects() {
+        let update = convert(&[
+            marker(json!({"ns": "shapes", "id": 1, "type": MARKER_CUBE,
+                "header": {"frame_id": "map", "stamp": {"secs": 3, "nsecs": 0}},
+                "pose": {"position": point(1., 2., 3.)},
+                "scale": point(0.1, 0.2, 0.3), "color": {"g": 1.0, "a": 1.0}})),
+            marker(json!({"id": 2, "type": MARKER_SPHERE, "scale": point(0.5, 0.5, 1.)})),
+            marker(json!({"id": 3, "type": MARKER_CYLINDER, "scale": point(1., 1., 2.)})),
+        ]);
+        let objects = update.pose_update.updates();
+        assert_eq!(objects.len(), 3);
+
+        assert_eq!(objects[0].id, "shapes/1");
+        assert_eq!(objects[0].namespace, "shapes");
+        assert_eq!(objects[0].parent_frame_id, Some("map".to_owned()));
+        assert_eq!(objects[0].pose, (1., 2., 3.));
+        assert_eq!(objects[0].shape, Shape::Cube(0.1, 0.2, 0.3));
+        assert_eq!(objects[0].color, Color::Green);
+        assert_eq!(update.pose_update.header().stamp, 3.);
+
+        assert_eq!(objects[1].id, "2");
+        assert_eq!(objects[1].namespace, "");
+        assert_eq!(objects[1].parent_frame_id, None);
+        assert_eq!(objects[1].shape, Shape::Sphere(0.5));
+        assert_eq!(objects[1].scale, Some((0.5, 0.5, 1.)));
+
+        assert_eq!(objects[2].shape, Shape::Cube(1., 1., 2.));
+        assert!(update.point_clouds.is_empty());
+    }
+
+    #[test]
+    fn arrow_goes_along_x_or_between_its_points() {
+        let update = convert(&[
+            marker(
+                json!({"id": 1, "type": MARKER_ARROW, "scale": point(2., 0.1, 0.1),
+                "pose": {"position": point(1., 0., 0.),
+                    "orientation": {"x": 0., "y": 0., "z": 0.5_f64.sqrt(), "w": 0.5_f64.sqrt()}}}),
+            ),
+            marker(json!({"id": 2, "type": MARKER_ARROW,
+                "points": [point(0., 0., 0.), point(0., 0., 1.)]})),
+        ]);
+        let objects = update.pose_update.updates();
+        assert_eq!(objects[0].pose, (1., 0., 0.));
+        match objects[0].shape {
+            Shape::Line((x, y, z)) => {
+                assert!((x - 1.).abs() < 1e-6);
+                assert!((y - 2.).abs() < 1e-6);
+                assert_eq!(z, 0.);
+            }
+            shape => panic!("arrow became {:?}", shape),
+        }
+        assert_eq!(objects[1].pose, (0., 0., 0.));
+        assert_eq!(objects[1].shape, Shape::Line((0., 0., 1.)));
+    }
+
+    #[test]
+    fn line_strip_is_split_into_segments() {
+        let update = convert(&[marker(
+            json!({"ns": "plan", "id": 4, "type": MARKER_LINE_STRIP,
+            "points": [point(0., 0., 0.), point(1., 0., 0.), point(1., 1., 0.)]}),
+        )]);
+        let objects = update.pose_update.updates();
+        let ids: Vec<_> = objects.iter().map(|object| object.id.as_str()).collect();
+        assert_eq!(ids, vec!["plan/4/0", "plan/4/1"]);
+        assert_eq!(objects[1].pose, (1., 0., 0.));
+        assert_eq!(objects[1].shape, Shape::Line((1., 1., 0.)));
+        // segments of a longer strip sent before are removed
+        assert!(update.deletions.should_delete("plan/4/7", "plan"));
+        assert!(!update.deletions.should_delete("plan/40/0", "plan"));
+        assert!(!update.deletions.should_delete("plan/4", "plan"));
+    }
+
+    #[test]
+    fn line_list_pairs_points() {
+        let update = convert(&[marker(json!({"id": 5, "type": MARKER_LINE_LIST,
+            "points": [point(0., 0., 0.), point(1., 0., 0.), point(2., 0., 0.),
+                point(3., 0., 0.), point(4., 0., 0.)]}))]);
+        let objects = update.pose_update.updates();
+        let ids: Vec<_> = objects.iter().map(|object| object.id.as_str()).collect();
+        assert_eq!(ids, vec!["5/0", "5/1"]);
+        assert_eq!(objects[1].pose, (2., 0., 0.));
+        assert_eq!(objects[1].shape, Shape::Line((3., 0., 0.)));
+    }
+
+    #[test]
+    fn point_markers_become_point_clouds() {
+        let markers: Vec<_> = [
+            (MARKER_CUBE_LIST, PointStyle::Cubes),
+            (MARKER_SPHERE_LIST, PointStyle::Spheres),
+            (MARKER_POINTS, PointStyle::Squares),
+        ]
+        .iter()
+        .map(|(kind, _)| {
+            marker(json!({"ns": "points", "id": kind, "type": kind,
+                "header": {"frame_id": "odom"}, "scale": point(0.2, 0.2, 0.2),
+                "pose": {"position": point(1., 1., 0.)},
+                "points": [point(1., 2., 3.)], "color": {"b": 1.0}}))
+        })
+        .collect();
+        let update = convert(&markers);
+        assert!(update.pose_update.updates().is_empty());
+        assert_eq!(update.point_clouds.len(), 3);
+        for (point_cloud, style) in update.point_clouds.iter().zip(&[
+            PointStyle::Cubes,
+            PointStyle::Spheres,
+            PointStyle::Squares,
+        ]) {
+            assert_eq!(point_cloud.style(), style);
+            assert_eq!(point_cloud.namespace(), "points");
+            assert_eq!(point_cloud.points(), &vec![(2., 3.)]);
+            assert_eq!(point_cloud.point_size(), 0.2);
+            assert_eq!(point_cloud.color(), &Color::Blue);
+            assert_eq!(point_cloud.parent_frame_id(), &Some("odom".to_owned()));
+        }
+        assert_eq!(
+            update.point_clouds[0].id(),
+            format!("points/{}", MARKER_CUBE_LIST)
+        );
+    }
+
+    #[test]
+    fn unsupported_markers_are_skipped() {
+        let text = 9;
+        let update = convert(&[marker(json!({"id": 1, "type": text}))]);
+        assert!(update.pose_update.updates().is_empty());
+        assert!(update.point_clouds.is_empty());
+        assert!(!update.deletions.has_deletions());
+    }
+
+    #[test]
+    fn zero_lifetime_is_forever() {
+        let update = convert(&[
+            marker(json!({"id": 1, "type": MARKER_CUBE})),
+            marker(
+                json!({"id": 2, "type": MARKER_CUBE, "lifetime": {"secs": 1, "nsecs": 500_000_000}}),
+            ),
+        ]);
+        let objects = update.pose_update.updates();
+        assert_eq!(objects[0].effective_lifetime(), Lifetime::Forever);
+        assert_eq!(objects[1].effective_lifetime(), Lifetime::Duration(1.5));
+    }
+
+    #[test]
+    fn delete_removes_marker_and_its_segments() {
+        let update = convert(&[marker(
+            json!({"ns": "plan", "id": 4, "type": MARKER_LINE_STRIP, "action": ACTION_DELETE}),
+        )]);
+        assert!(update.pose_update.updates().is_empty());
+        assert!(update.deletions.should_delete("plan/4", "plan"));
+        assert!(update.deletions.should_delete("plan/4/0", "plan"));
+        assert!(!update.deletions.should_delete("plan/5", "plan"));
+    }
+
+    #[test]
+    fn delete_all_only_removes_forwarded_markers() {
+        let mut forwarded = ForwardedMarkers::default();
+        from_markers(
+            &[
+                marker(json!({"ns": "plan", "id": 1, "type": MARKER_CUBE})),
+                marker(json!({"id": 2, "type": MARKER_LINE_STRIP,
+                    "points": [point(0., 0., 0.), point(1., 0., 0.)]})),
+            ],
+            &mut forwarded,
+        );
+        let update = from_markers(
+            &[marker(json!({"action": ACTION_DELETE_ALL}))],
+            &mut forwarded,
+        );
+        let deletions = &update.deletions;
+        assert!(!deletions.deletes_all());
+        assert!(deletions.should_delete("plan/1", "plan"));
+        assert!(deletions.should_delete("2", ""));
+        assert!(deletions.should_delete("2/0", ""));
+        // entities of other rustviz publishers stay
+        assert!(!deletions.should_delete("robot", ""));
+        assert!(!deletions.should_delete("goal", "navigation"));
+    }
+
+    #[test]
+    fn delete_all_with_namespace_only_removes_namespace() {
+        let mut forwarded = ForwardedMarkers::default();
+        from_markers(
+            &[
+                marker(json!({"ns": "plan", "id": 1, "type": MARKER_CUBE})),
+                marker(json!({"ns": "map", "id": 1, "type": MARKER_CUBE})),
+            ],
+            &mut forwarded,
+        );
+        let update = from_markers(
+            &[marker(json!({"ns": "plan", "action": ACTION_DELETE_ALL}))],
+            &mut forwarded,
+        );
+        assert!(update.deletions.should_delete("plan/1", "plan"));
+        assert!(!update.deletions.should_delete("map/1", "map"));
+    }
+
+    fn ros_point_cloud(data: Bytes) -> RosPointCloud2 {
+        let field = |name: &str, offset| PointField {
+            name: name.to_owned(),
+            offset,
+            datatype: FIELD_FLOAT32,
+            count: 1,
+        };
+        RosPointCloud2 {
+            header: Header {
+                stamp: Time::from_seconds(4.),
+                frame_id: "lidar".to_owned(),
+            },
+            height: 1,
+            width: 3,
+            fields: vec![field("x", 0), field("y", 4), field("z", 8)],
+            is_bigendian: false,
+            point_step: 12,
+            row_step: 36,
+            data,
+        }
+    }
+
+    fn point_bytes() -> Vec<u8> {
+        to_bytes(&[(1., 2., 3.), (f32::NAN, 0., 0.), (-4., 5., 6.)])
+    }
+
+    fn to_bytes(points: &[(f32, f32, f32)]) -> Vec<u8> {
+        points
+            .iter()
+            .flat_map(|(x, y, z)| {
+                let mut bytes = x.to_le_bytes().to_vec();
+                bytes.extend_from_slice(&y.to_le_bytes());
+                bytes.extend_from_slice(&z.to_le_bytes());
+                bytes
+            })
+            .collect()
+    }
+
+    #[test]
+    fn point_cloud_keeps_finite_x_and_y() {
+        for data in [
+            Bytes::Array(point_bytes()),
+            Bytes::Base64(base64::encode(point_bytes())),
+        ] {
+            let mut point_clouds = from_point_cloud("/scan", &ros_point_cloud(data)).unwrap();
+            assert_eq!(point_clouds.len(), 1);
+            let point_cloud = point_clouds.remove(0);
+            assert_eq!(point_cloud.id(), "/scan");
+            assert_eq!(point_cloud.points(), &vec![(1., 2.), (-4., 5.)]);
+            assert_eq!(point_cloud.header().stamp, 4.);
+            assert_eq!(point_cloud.parent_frame_id(), &Some("lidar".to_owned()));
+        }
+    }
+
+    #[test]
+    fn point_cloud_larger_than_a_datagram_is_split() {
+        let points: Vec<_> = (0..20_000)
+            .map(|i| (i as f32 * 0.123_456, -(i as f32) * 1.234_567, 0.))
+            .collect();
+        let mut point_cloud = ros_point_cloud(Bytes::Array(to_bytes(&points)));
+        point_cloud.width = points.len() as u32;
+        point_cloud.row_step = point_cloud.width * point_cloud.point_step;
+        let parts = from_point_cloud("/points", &point_cloud).unwrap();
+        assert!(parts.len() > 1);
+        let mut received = vec![];
+        for (index, part) in parts.iter().enumerate() {
+            assert!(serde_json::to_vec(part).unwrap().len() <= MAX_PAYLOAD_SIZE);
+            assert_eq!(part.id(), point_cloud_part_id("/points", index));
+            assert_eq!(part.header().stamp, 4.);
+            assert_eq!(part.parent_frame_id(), &Some("lidar".to_owned()));
+            received.extend_from_slice(part.points());
+        }
+        let expected: Vec<_> = points.iter().map(|(x, y, _)| (*x, *y)).collect();
+        assert_eq!(received, expected);
+        assert_eq!(point_cloud_part_id("/points", 1), "/points/1");
+    }
+
+    #[test]
+    fn point_cloud_without_float_x_and_y_is_rejected() {
+        let mut point_cloud = ros_point_cloud(Bytes::Array(point_bytes()));
+        point_cloud.fields.retain(|field| field.name != "y");
+        assert!(from_point_cloud("/scan", &point_cloud).is_none());
+
+        let mut point_cloud = ros_point_cloud(Bytes::Array(point_bytes()));
+        point_cloud.fields[0].datatype = 2;
+        assert!(from_point_cloud("/scan", &point_cloud).is_none());
+
+        // data shorter than the declared size
+        let point_cloud = ros_point_cloud(Bytes::Array(vec![0; 20]));
+        assert!(from_point_cloud("/scan", &point_cloud).is_none());
+    }
+
+    #[test]
+    fn pose_stamped_becomes_axes() {
+        let pose: PoseStamped = serde_json::from_value(json!({
+            "header": {"frame_id": "map", "stamp": {"sec": 7, "nanosec": 0}},
+            "pose": {"position": point(1., 2., 0.), "orientation": {"w": 2.0}}
+        }))
+        .unwrap();
+        let update = from_pose_stamped("/pose", &pose);
+        assert_eq!(update.header().stamp, 7.);
+        assert_eq!(update.header().frame_id, Some("map".to_owned()));
+        let object = &update.updates()[0];
+        assert_eq!(object.id, "/pose");
+        assert_eq!(object.pose, (1., 2., 0.));
+        assert_eq!(object.shape, Shape::Axes(POSE_AXES_LENGTH));
+        assert_eq!(object.rotation, (0., 0., 0., 1.));
+    }
+
+    #[test]
+    fn path_keeps_poses_in_order() {
+        let path: RosPath = serde_json::from_value(json!({
+            "header": {"frame_id": "map"},
+            "poses": [
+                {"pose": {"position": point(0., 0., 0.)}},
+                {"pose": {"position": point(1., 0., 0.), "orientation": {"z": 1.0, "w": 0.0}}}
+            ]
+        }))
+        .unwrap();
+        let path = from_path("/plan", &path);
+        assert_eq!(path.id(), "/plan");
+        assert_eq!(path.parent_frame_id(), &Some("map".to_owned()));
+        assert_eq!(path.points().len(), 2);
+        assert_eq!(path.points()[1].position(), (1., 0., 0.));
+        assert_eq!(path.points()[1].orientation(), Some((0., 0., 1., 0.)));
+    }
+
+    #[test]
+    fn goal_command_becomes_pose_stamped() {
+        let command = Command::goal(1, (1., 2.), std::f32::consts::FRAC_PI_2).with_stamp(10.25);
+        let goal = goal_from_command(&command, "map").unwrap();
+        assert_eq!(goal.header.frame_id, "map");
+        assert_eq!(goal.header.stamp.to_seconds(), 10.25);
+        assert_eq!(goal.pose.position.x, 1.);
+        assert_eq!(goal.pose.position.y, 2.);
+        assert!((goal.pose.orientation.z - 0.5_f64.sqrt()).abs() < 1e-6);
+        assert!((goal.pose.orientation.w - 0.5_f64.sqrt()).abs() < 1e-6);
+
+        let command = Command::goal(2, (0., 0.), 0.).with_frame_id("odom");
+        assert_eq!(
+            goal_from_command(&command, "map").unwrap().header.frame_id,
+            "odom"
+        );
+    }
+
+    #[test]
+    fn only_goals_are_forwarded() {
+        for command in &[
+            Command::initial_pose(1, (0., 0.), 0.),
+            Command::clicked_point(2, (0., 0., 0.)),
+            Command::waypoints(3, vec![(0., 0.)]),
+            Command::velocity(4, (0.5, 0.), 0.1),
+        ] {
+            assert!(goal_from_command(command, "map").is_none());
+        }
+    }
+}
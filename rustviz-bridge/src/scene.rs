@@ -0,0 +1,352 @@
+//! Rustviz objects and point clouds as `foxglove.SceneUpdate` entities
+use pose_publisher::{
+    header::timestamp_now,
+    point_cloud::PointStyle,
+    pose::{Color as RustvizColor, Shape},
+    Header, Lifetime, ObjectPose, PointCloud2, PoseClientUpdate,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Points drawn in pixels have no size in meters
+const POINT_DIAMETER: f32 = 0.02;
+const LINE_THICKNESS: f32 = 0.01;
+
+const DELETION_MATCHING_ID: u8 = 0;
+const DELETION_ALL: u8 = 1;
+const LINE_STRIP: u8 = 0;
+const LINE_LIST: u8 = 2;
+
+#[derive(Serialize, JsonSchema, Clone, Copy, Default)]
+pub struct Time {
+    pub sec: u32,
+    pub nsec: u32,
+}
+
+impl Time {
+    pub fn from_seconds(seconds: f64) -> Self {
+        let seconds = seconds.max(0.);
+        Self {
+            sec: seconds.trunc() as u32,
+            nsec: (seconds.fract() * 1e9) as u32,
+        }
+    }
+
+    /// Header stamp or now if the publisher did not set one
+    pub fn from_header(header: &Header) -> Self {
+        if header.has_stamp() {
+            Self::from_seconds(header.stamp)
+        } else {
+            Self::from_seconds(timestamp_now())
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema, Clone, Copy, Default)]
+pub struct Vector3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl From<(f32, f32, f32)> for Vector3 {
+    fn from((x, y, z): (f32, f32, f32)) -> Self {
+        Self {
+            x: x as f64,
+            y: y as f64,
+            z: z as f64,
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema, Clone, Copy)]
+pub struct Quaternion {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+            w: 1.,
+        }
+    }
+}
+
+impl From<(f32, f32, f32, f32)> for Quaternion {
+    fn from((x, y, z, w): (f32, f32, f32, f32)) -> Self {
+        Self {
+            x: x as f64,
+            y: y as f64,
+            z: z as f64,
+            w: w as f64,
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema, Clone, Copy, Default)]
+pub struct Pose {
+    pub position: Vector3,
+    pub orientation: Quaternion,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Copy)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+impl From<(f32, f32, f32)> for Color {
+    fn from((r, g, b): (f32, f32, f32)) -> Self {
+        Self {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: 1.,
+        }
+    }
+}
+
+impl From<RustvizColor> for Color {
+    fn from(color: RustvizColor) -> Self {
+        color.to_rgb().into()
+    }
+}
+
+/// Used for spheres and cubes
+#[derive(Serialize, JsonSchema)]
+pub struct ShapePrimitive {
+    pub pose: Pose,
+    pub size: Vector3,
+    pub color: Color,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct LinePrimitive {
+    #[serde(rename = "type")]
+    pub kind: u8,
+    pub pose: Pose,
+    pub thickness: f64,
+    pub scale_invariant: bool,
+    pub points: Vec<Vector3>,
+    pub color: Color,
+    /// Per point colors, overrides color if not empty
+    pub colors: Vec<Color>,
+    pub indices: Vec<u32>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SceneEntity {
+    pub timestamp: Time,
+    pub frame_id: String,
+    pub id: String,
+    /// Zero is forever
+    pub lifetime: Time,
+    pub frame_locked: bool,
+    pub metadata: Vec<Value>,
+    pub arrows: Vec<Value>,
+    pub cubes: Vec<ShapePrimitive>,
+    pub spheres: Vec<ShapePrimitive>,
+    pub cylinders: Vec<Value>,
+    pub lines: Vec<LinePrimitive>,
+    pub triangles: Vec<Value>,
+    pub texts: Vec<Value>,
+    pub models: Vec<Value>,
+}
+
+impl SceneEntity {
+    fn new(timestamp: Time, frame_id: &str, id: &str, lifetime: &Lifetime) -> Self {
+        let lifetime = match lifetime {
+            Lifetime::Duration(seconds) => Time::from_seconds(*seconds as f64),
+            // publisher heartbeats are not forwarded so these are kept until deleted
+            Lifetime::Forever | Lifetime::UntilPublisherGone(_) => Time::default(),
+        };
+        Self {
+            timestamp,
+            frame_id: frame_id.to_owned(),
+            id: id.to_owned(),
+            lifetime,
+            frame_locked: true,
+            metadata: vec![],
+            arrows: vec![],
+            cubes: vec![],
+            spheres: vec![],
+            cylinders: vec![],
+            lines: vec![],
+            triangles: vec![],
+            texts: vec![],
+            models: vec![],
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SceneEntityDeletion {
+    pub timestamp: Time,
+    #[serde(rename = "type")]
+    pub kind: u8,
+    pub id: String,
+}
+
+#[derive(Serialize, JsonSchema, Default)]
+pub struct SceneUpdate {
+    pub deletions: Vec<SceneEntityDeletion>,
+    pub entities: Vec<SceneEntity>,
+}
+
+pub const SCHEMA_NAME: &str = "foxglove.SceneUpdate";
+
+/// Json schema of [`SceneUpdate`] as string
+pub fn schema() -> String {
+    serde_json::to_string(&schemars::schema_for!(SceneUpdate)).unwrap_or_default()
+}
+
+fn object_entity(object: &ObjectPose, header: &Header, fixed_frame: &str) -> SceneEntity {
+    let frame_id = object
+        .parent_frame_id
+        .as_deref()
+        .or(header.frame_id.as_deref())
+        .unwrap_or(fixed_frame);
+    let mut entity = SceneEntity::new(
+        Time::from_header(header),
+        frame_id,
+        &object.id,
+        &object.effective_lifetime(),
+    );
+    let pose = Pose {
+        position: object.pose.into(),
+        orientation: object.rotation.into(),
+    };
+    let (scale_x, scale_y, scale_z) = object.scale.unwrap_or((1., 1., 1.));
+    match object.shape {
+        Shape::Sphere(radius) => entity.spheres.push(ShapePrimitive {
+            pose,
+            size: (
+                radius * 2. * scale_x,
+                radius * 2. * scale_y,
+                radius * 2. * scale_z,
+            )
+                .into(),
+            color: object.color.into(),
+        }),
+        Shape::Cube(x, y, z) => entity.cubes.push(ShapePrimitive {
+            pose,
+            size: (x * scale_x, y * scale_y, z * scale_z).into(),
+            color: object.color.into(),
+        }),
+        // line runs from the object position to the end point, both in the parent frame
+        Shape::Line(end) => entity.lines.push(LinePrimitive {
+            kind: LINE_STRIP,
+            pose: Pose::default(),
+            thickness: LINE_THICKNESS as f64,
+            scale_invariant: false,
+            points: vec![object.pose.into(), end.into()],
+            color: object.color.into(),
+            colors: vec![],
+            indices: vec![],
+        }),
+        Shape::Axes(length) => {
+            let axes = [
+                ((length, 0., 0.), (1., 0., 0.)),
+                ((0., length, 0.), (0., 1., 0.)),
+                ((0., 0., length), (0., 0., 1.)),
+            ];
+            let mut points = vec![];
+            let mut colors = vec![];
+            for (end, color) in &axes {
+                points.push(Vector3::default());
+                points.push((*end).into());
+                colors.push((*color).into());
+                colors.push((*color).into());
+            }
+            entity.lines.push(LinePrimitive {
+                kind: LINE_LIST,
+                pose,
+                thickness: LINE_THICKNESS as f64,
+                scale_invariant: false,
+                points,
+                color: object.color.into(),
+                colors,
+                indices: vec![],
+            })
+        }
+    }
+    entity
+}
+
+/// Namespace and pattern deletions have no equivalent and are dropped
+///
+/// Foxglove applies deletions first so objects deleted by the same update are left out
+pub fn from_pose_update(update: &PoseClientUpdate, fixed_frame: &str) -> SceneUpdate {
+    let timestamp = Time::from_header(update.header());
+    let mut deletions: Vec<_> = update
+        .deletions()
+        .iter()
+        .map(|id| SceneEntityDeletion {
+            timestamp,
+            kind: DELETION_MATCHING_ID,
+            id: id.clone(),
+        })
+        .collect();
+    if update.deletes_all() {
+        deletions.push(SceneEntityDeletion {
+            timestamp,
+            kind: DELETION_ALL,
+            id: String::new(),
+        });
+    }
+    SceneUpdate {
+        deletions,
+        entities: update
+            .updates()
+            .iter()
+            .filter(|object| !update.should_delete(&object.id, &object.namespace))
+            .map(|object| object_entity(object, update.header(), fixed_frame))
+            .collect(),
+    }
+}
+
+/// Every point becomes a cube or sphere of the cloud entity
+pub fn from_point_cloud(point_cloud: &PointCloud2, fixed_frame: &str) -> SceneUpdate {
+    let frame_id = point_cloud
+        .parent_frame_id()
+        .as_deref()
+        .unwrap_or(fixed_frame);
+    let mut entity = SceneEntity::new(
+        Time::from_header(point_cloud.header()),
+        frame_id,
+        point_cloud.id(),
+        point_cloud.lifetime(),
+    );
+    let (diameter, height) = match point_cloud.style() {
+        PointStyle::Points => (POINT_DIAMETER, POINT_DIAMETER),
+        PointStyle::Squares => (point_cloud.point_size(), 0.001),
+        PointStyle::Cubes | PointStyle::Spheres => {
+            (point_cloud.point_size(), point_cloud.point_size())
+        }
+    };
+    let primitives = point_cloud.points().iter().map(|(x, y)| ShapePrimitive {
+        pose: Pose {
+            position: (*x, *y, 0.).into(),
+            orientation: Quaternion::default(),
+        },
+        size: (diameter, diameter, height).into(),
+        color: (*point_cloud.color()).into(),
+    });
+    match point_cloud.style() {
+        PointStyle::Squares | PointStyle::Cubes => entity.cubes.extend(primitives),
+        PointStyle::Points | PointStyle::Spheres => entity.spheres.extend(primitives),
+    }
+    SceneUpdate {
+        deletions: vec![],
+        entities: vec![entity],
+    }
+}
@@ -0,0 +1,213 @@
+use std::{collections::VecDeque, time::Instant};
+
+/// Summary of the samples in the window
+pub struct Summary {
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub std_dev: f64,
+    pub count: usize,
+}
+
+/// Last `size` samples
+pub struct Window {
+    samples: VecDeque<f64>,
+    size: usize,
+}
+
+impl Window {
+    pub fn new(size: usize) -> Self {
+        Self {
+            samples: VecDeque::new(),
+            size: size.max(1),
+        }
+    }
+
+    pub fn push(&mut self, sample: f64) {
+        self.samples.push_back(sample);
+        while self.samples.len() > self.size {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let count = self.samples.len();
+        let mean = self.samples.iter().sum::<f64>() / count as f64;
+        let variance = self
+            .samples
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f64>()
+            / count as f64;
+        Some(Summary {
+            mean,
+            min: self.samples.iter().cloned().fold(f64::INFINITY, f64::min),
+            max: self
+                .samples
+                .iter()
+                .cloned()
+                .fold(f64::NEG_INFINITY, f64::max),
+            std_dev: variance.sqrt(),
+            count,
+        })
+    }
+}
+
+/// Intervals between messages
+pub struct RateMeter {
+    intervals: Window,
+    last_message: Option<Instant>,
+    /// Messages recorded since the last report
+    since_report: usize,
+}
+
+impl RateMeter {
+    pub fn new(window: usize) -> Self {
+        Self {
+            intervals: Window::new(window),
+            last_message: None,
+            since_report: 0,
+        }
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        if let Some(last_message) = self.last_message {
+            self.intervals
+                .push(now.duration_since(last_message).as_secs_f64());
+        }
+        self.last_message = Some(now);
+        self.since_report += 1;
+    }
+
+    /// None if nothing arrived since the last report
+    pub fn report(&mut self) -> Option<String> {
+        if std::mem::take(&mut self.since_report) == 0 {
+            return None;
+        }
+        let summary = self.intervals.summary()?;
+        if summary.mean <= 0. {
+            return None;
+        }
+        Some(format!(
+            "average rate: {:.3} Hz\n\tmin: {:.3}s max: {:.3}s std dev: {:.5}s window: {}",
+            1. / summary.mean,
+            summary.min,
+            summary.max,
+            summary.std_dev,
+            summary.count
+        ))
+    }
+}
+
+/// Message sizes and arrival times
+pub struct BandwidthMeter {
+    sizes: Window,
+    arrivals: VecDeque<Instant>,
+    window: usize,
+    /// Messages recorded since the last report
+    since_report: usize,
+}
+
+impl BandwidthMeter {
+    pub fn new(window: usize) -> Self {
+        Self {
+            sizes: Window::new(window),
+            arrivals: VecDeque::new(),
+            window: window.max(1),
+            since_report: 0,
+        }
+    }
+
+    pub fn record(&mut self, now: Instant, size: usize) {
+        self.sizes.push(size as f64);
+        self.arrivals.push_back(now);
+        while self.arrivals.len() > self.window {
+            self.arrivals.pop_front();
+        }
+        self.since_report += 1;
+    }
+
+    /// None if nothing arrived since the last report
+    pub fn report(&mut self) -> Option<String> {
+        if std::mem::take(&mut self.since_report) == 0 {
+            return None;
+        }
+        let summary = self.sizes.summary()?;
+        let first = self.arrivals.front()?;
+        let last = self.arrivals.back()?;
+        let elapsed = last.duration_since(*first).as_secs_f64();
+        // first message only marks start of the measured span
+        let bandwidth = if elapsed > 0. {
+            summary.mean * (summary.count - 1) as f64 / elapsed
+        } else {
+            0.
+        };
+        Some(format!(
+            "average: {}/s\n\tmean: {} min: {} max: {} std dev: {} window: {}",
+            format_bytes(bandwidth),
+            format_bytes(summary.mean),
+            format_bytes(summary.min),
+            format_bytes(summary.max),
+            format_bytes(summary.std_dev),
+            summary.count
+        ))
+    }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    if bytes >= 1_000_000. {
+        format!("{:.2}MB", bytes / 1_000_000.)
+    } else if bytes >= 1_000. {
+        format!("{:.2}KB", bytes / 1_000.)
+    } else {
+        format!("{:.0}B", bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn window_keeps_last_samples() {
+        let mut window = Window::new(3);
+        for sample in &[10., 1., 2., 3.] {
+            window.push(*sample);
+        }
+        let summary = window.summary().unwrap();
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.mean, 2.);
+        assert_eq!(summary.min, 1.);
+        assert_eq!(summary.max, 3.);
+    }
+
+    #[test]
+    fn rate_is_only_reported_for_new_messages() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new(10);
+        for index in 0..5 {
+            meter.record(start + Duration::from_millis(100 * index));
+        }
+        assert!(meter
+            .report()
+            .unwrap()
+            .starts_with("average rate: 10.000 Hz"));
+        assert!(meter.report().is_none());
+        meter.record(start + Duration::from_millis(500));
+        assert!(meter.report().is_some());
+    }
+
+    #[test]
+    fn bandwidth_is_only_reported_for_new_messages() {
+        let start = Instant::now();
+        let mut meter = BandwidthMeter::new(10);
+        meter.record(start, 1000);
+        meter.record(start + Duration::from_secs(1), 1000);
+        assert!(meter.report().unwrap().starts_with("average: 1.00KB/s"));
+        assert!(meter.report().is_none());
+    }
+}
@@ -0,0 +1,184 @@
+//! Renders recorded logs without a display
+//!
+//! Scenes are drawn by a small software rasterizer so that no OpenGL context is needed
+mod raster;
+mod scene;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Clap;
+use image::{codecs::gif::GifEncoder, Delay, Frame, RgbImage};
+use nalgebra as na;
+use pose_publisher::recording::LogReader;
+use raster::{Camera, Canvas, Rgb};
+use scene::Scene;
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Same as the viewer
+const BACKGROUND: f32 = 0.1;
+const FIELD_OF_VIEW: f32 = std::f32::consts::FRAC_PI_4;
+/// Color quantization speed from 1 to 30. Scenes have few colors so quality barely suffers
+const GIF_SPEED: i32 = 10;
+
+/// Point given as x,y,z
+struct Point3Arg(na::Point3<f32>);
+
+impl FromStr for Point3Arg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| error.to_string())?;
+        match values.as_slice() {
+            [x, y, z] => Ok(Point3Arg(na::Point3::new(*x, *y, *z))),
+            _ => Err(format!("expected x,y,z but got {}", s)),
+        }
+    }
+}
+
+/// Render a recorded log to PNG frames or a GIF
+///
+/// Objects, paths, point clouds and laser scans are drawn. Occupancy grids and
+/// covariances are not
+#[derive(Clap)]
+#[clap()]
+struct Args {
+    /// Log file written by the recorder
+    log: PathBuf,
+    /// Directory for a PNG series, a .png file for a single frame at start
+    /// or a .gif file for an animation
+    #[clap(short, long, default_value = "frames")]
+    output: PathBuf,
+    #[clap(long, default_value = "800")]
+    width: u32,
+    #[clap(long, default_value = "600")]
+    height: u32,
+    /// Camera position as x,y,z
+    #[clap(long, default_value = "1,1,1")]
+    eye: Point3Arg,
+    /// Point the camera looks at as x,y,z
+    #[clap(long, default_value = "0,0,0")]
+    target: Point3Arg,
+    /// Frames per second of log time
+    #[clap(long, default_value = "10")]
+    fps: f32,
+    /// Seconds from the start of the log to the first frame
+    #[clap(long, default_value = "0")]
+    start: f64,
+    /// Seconds of log to render. Until the end of the log if not set
+    #[clap(long)]
+    duration: Option<f64>,
+    /// Frame of objects without parent frame
+    #[clap(long, default_value = "world")]
+    fixed_frame: String,
+}
+
+enum Output {
+    Series(PathBuf),
+    Single(PathBuf),
+    Gif(GifEncoder<File>),
+}
+
+impl Output {
+    fn new(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("gif") => {
+                let file = File::create(path)
+                    .with_context(|| format!("failed to create {}", path.display()))?;
+                Ok(Output::Gif(GifEncoder::new_with_speed(file, GIF_SPEED)))
+            }
+            Some("png") => Ok(Output::Single(path.to_owned())),
+            _ => {
+                std::fs::create_dir_all(path)
+                    .with_context(|| format!("failed to create {}", path.display()))?;
+                Ok(Output::Series(path.to_owned()))
+            }
+        }
+    }
+
+    fn is_single(&self) -> bool {
+        matches!(self, Output::Single(_))
+    }
+
+    fn write(&mut self, index: usize, frame: RgbImage, fps: f32) -> Result<()> {
+        match self {
+            Output::Series(directory) => {
+                frame.save(directory.join(format!("frame_{:05}.png", index)))?
+            }
+            Output::Single(path) => frame.save(&path)?,
+            Output::Gif(encoder) => {
+                let delay = Delay::from_numer_denom_ms(1000, fps.round().max(1.) as u32);
+                let frame = image::DynamicImage::ImageRgb8(frame).into_rgba8();
+                encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if args.fps <= 0. {
+        return Err(anyhow!("fps has to be positive"));
+    }
+    let mut reader = LogReader::open(&args.log)
+        .with_context(|| format!("failed to open {}", args.log.display()))?;
+    let log_start = reader
+        .start_time()
+        .ok_or_else(|| anyhow!("{} is empty", args.log.display()))?;
+    let mut output = Output::new(&args.output)?;
+    let camera = Camera::new(
+        args.eye.0,
+        args.target.0,
+        FIELD_OF_VIEW,
+        args.width,
+        args.height,
+    );
+
+    // state is accumulated from the start of the log since entities persist
+    let mut scene = Scene::new(&args.fixed_frame);
+    let period = 1. / args.fps as f64;
+    let start = log_start + args.start;
+    let end = args.duration.map(|duration| start + duration);
+    let mut frame_time = start;
+    let mut frames = 0;
+    let mut next_entry = reader.next_entry()?;
+    loop {
+        while let Some(entry) = next_entry.take() {
+            if entry.received > frame_time {
+                next_entry = Some(entry);
+                break;
+            }
+            scene.apply(&entry.message, entry.received);
+            next_entry = reader.next_entry()?;
+        }
+        scene.remove_timed_out(frame_time);
+        let mut canvas = Canvas::new(
+            args.width,
+            args.height,
+            Rgb::new(BACKGROUND, BACKGROUND, BACKGROUND),
+        );
+        scene.draw(&mut canvas, &camera);
+        output.write(frames, canvas.into_image(), args.fps)?;
+        frames += 1;
+
+        frame_time = start + frames as f64 * period;
+        let finished =
+            output.is_single() || end.map_or(next_entry.is_none(), |end| frame_time > end);
+        if finished {
+            break;
+        }
+    }
+    println!("Rendered {} frames to {}", frames, args.output.display());
+    Ok(())
+}
@@ -0,0 +1,299 @@
+//! Software rasterizer with depth buffer
+use nalgebra as na;
+
+const NEAR_PLANE: f32 = 0.01;
+
+pub type Rgb = na::Point3<f32>;
+
+/// Perspective camera looking at target with z up
+pub struct Camera {
+    view: na::Isometry3<f32>,
+    /// Focal length in pixels
+    focal: f32,
+    center: (f32, f32),
+}
+
+impl Camera {
+    /// Vertical field of view in radians
+    pub fn new(
+        eye: na::Point3<f32>,
+        target: na::Point3<f32>,
+        fov: f32,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            view: na::Isometry3::look_at_rh(&eye, &target, &na::Vector3::z()),
+            focal: height as f32 / 2. / (fov / 2.).tan(),
+            center: (width as f32 / 2., height as f32 / 2.),
+        }
+    }
+
+    fn to_view(&self, point: &na::Point3<f32>) -> na::Point3<f32> {
+        self.view * point
+    }
+
+    /// Pixel coordinates and depth of a point in view space
+    fn project_view(&self, point: &na::Point3<f32>) -> (f32, f32, f32) {
+        let depth = -point.z;
+        (
+            self.center.0 + point.x * self.focal / depth,
+            self.center.1 - point.y * self.focal / depth,
+            depth,
+        )
+    }
+
+    /// None if the point is behind the camera
+    pub fn project(&self, point: &na::Point3<f32>) -> Option<(f32, f32, f32)> {
+        let point = self.to_view(point);
+        if -point.z < NEAR_PLANE {
+            None
+        } else {
+            Some(self.project_view(&point))
+        }
+    }
+
+    /// Size in pixels of a length at given depth
+    pub fn pixels(&self, length: f32, depth: f32) -> f32 {
+        length * self.focal / depth
+    }
+
+    /// Shading of a surface with given normal lit from the camera
+    pub fn shade(&self, normal: &na::Vector3<f32>) -> f32 {
+        let normal = self.view * normal;
+        0.3 + 0.7 * normal.z.abs().min(1.)
+    }
+}
+
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    color: Vec<Rgb>,
+    depth: Vec<f32>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, background: Rgb) -> Self {
+        let size = (width * height) as usize;
+        Self {
+            width,
+            height,
+            color: vec![background; size],
+            depth: vec![f32::INFINITY; size],
+        }
+    }
+
+    fn set(&mut self, x: i64, y: i64, depth: f32, color: &Rgb) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let index = (y * self.width as i64 + x) as usize;
+        if depth < self.depth[index] {
+            self.depth[index] = depth;
+            self.color[index] = *color;
+        }
+    }
+
+    /// Triangles reaching behind the camera are skipped
+    pub fn triangle(&mut self, camera: &Camera, corners: [na::Point3<f32>; 3], color: &Rgb) {
+        let mut projected = [(0., 0., 0.); 3];
+        for (corner, projected) in corners.iter().zip(projected.iter_mut()) {
+            *projected = match camera.project(corner) {
+                Some(point) => point,
+                None => return,
+            };
+        }
+        let [a, b, c] = projected;
+        let area = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+        if area.abs() < f32::EPSILON {
+            return;
+        }
+        let min_x = a.0.min(b.0).min(c.0).floor().max(0.) as i64;
+        let max_x = a.0.max(b.0).max(c.0).ceil().min(self.width as f32) as i64;
+        let min_y = a.1.min(b.1).min(c.1).floor().max(0.) as i64;
+        let max_y = a.1.max(b.1).max(c.1).ceil().min(self.height as f32) as i64;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let wa = ((b.0 - px) * (c.1 - py) - (b.1 - py) * (c.0 - px)) / area;
+                let wb = ((c.0 - px) * (a.1 - py) - (c.1 - py) * (a.0 - px)) / area;
+                let wc = 1. - wa - wb;
+                if wa < 0. || wb < 0. || wc < 0. {
+                    continue;
+                }
+                // perspective correct depth
+                let depth = 1. / (wa / a.2 + wb / b.2 + wc / c.2);
+                self.set(x, y, depth, color);
+            }
+        }
+    }
+
+    /// Two triangles with corners in order around the quad
+    pub fn quad(&mut self, camera: &Camera, corners: [na::Point3<f32>; 4], color: &Rgb) {
+        self.triangle(camera, [corners[0], corners[1], corners[2]], color);
+        self.triangle(camera, [corners[0], corners[2], corners[3]], color);
+    }
+
+    /// Line clipped at the near plane
+    pub fn line(
+        &mut self,
+        camera: &Camera,
+        start: &na::Point3<f32>,
+        end: &na::Point3<f32>,
+        color: &Rgb,
+    ) {
+        let (mut start, mut end) = (camera.to_view(start), camera.to_view(end));
+        let (start_depth, end_depth) = (-start.z, -end.z);
+        if start_depth < NEAR_PLANE && end_depth < NEAR_PLANE {
+            return;
+        }
+        let clip = |inside: &na::Point3<f32>, outside: &na::Point3<f32>| {
+            let t = (-NEAR_PLANE - inside.z) / (outside.z - inside.z);
+            inside + (outside - inside) * t
+        };
+        if start_depth < NEAR_PLANE {
+            start = clip(&end, &start);
+        } else if end_depth < NEAR_PLANE {
+            end = clip(&start, &end);
+        }
+        let (x0, y0, d0) = camera.project_view(&start);
+        let (x1, y1, d1) = camera.project_view(&end);
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.) as i64;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            // lines win against surfaces they lie on
+            let depth = 1. / ((1. - t) / d0 + t / d1) - 0.001;
+            self.set(
+                (x0 + (x1 - x0) * t).floor() as i64,
+                (y0 + (y1 - y0) * t).floor() as i64,
+                depth,
+                color,
+            );
+        }
+    }
+
+    /// Shaded disk facing the camera, used for spheres
+    pub fn sphere(&mut self, camera: &Camera, center: &na::Point3<f32>, radius: f32, color: &Rgb) {
+        let (cx, cy, depth) = match camera.project(center) {
+            Some(projected) => projected,
+            None => return,
+        };
+        let pixel_radius = camera.pixels(radius, depth).max(0.5);
+        let extent = pixel_radius.ceil() as i64;
+        let (center_x, center_y) = (cx as i64, cy as i64);
+        let min_x = center_x.saturating_sub(extent).max(0);
+        let max_x = center_x.saturating_add(extent).min(self.width as i64 - 1);
+        let min_y = center_y.saturating_sub(extent).max(0);
+        let max_y = center_y.saturating_add(extent).min(self.height as i64 - 1);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let (dx, dy) = (x - center_x, y - center_y);
+                let (x, y) = (dx as f32 / pixel_radius, dy as f32 / pixel_radius);
+                let distance = x * x + y * y;
+                if distance > 1. {
+                    continue;
+                }
+                // normal towards the camera is the light direction
+                let facing = (1. - distance).sqrt();
+                let shade = 0.3 + 0.7 * facing;
+                self.set(
+                    center_x + dx,
+                    center_y + dy,
+                    depth - radius * facing,
+                    &(color * shade),
+                );
+            }
+        }
+    }
+
+    /// Square of given size in pixels facing the camera
+    pub fn point(&mut self, camera: &Camera, center: &na::Point3<f32>, size: f32, color: &Rgb) {
+        let (cx, cy, depth) = match camera.project(center) {
+            Some(projected) => projected,
+            None => return,
+        };
+        let half = (size / 2.).max(0.5);
+        let min_x = (cx - half).round().max(0.) as i64;
+        let max_x = (cx + half).round().min(self.width as f32) as i64;
+        let min_y = (cy - half).round().max(0.) as i64;
+        let max_y = (cy + half).round().min(self.height as f32) as i64;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                self.set(x, y, depth, color);
+            }
+        }
+    }
+
+    pub fn into_image(self) -> image::RgbImage {
+        let to_byte = |value: f32| (value.clamp(0., 1.) * 255.).round() as u8;
+        let mut image = image::RgbImage::new(self.width, self.height);
+        for (pixel, color) in image.pixels_mut().zip(self.color.iter()) {
+            *pixel = image::Rgb([to_byte(color.x), to_byte(color.y), to_byte(color.z)]);
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb = Rgb::new(0., 0., 0.);
+    const WHITE: Rgb = Rgb::new(1., 1., 1.);
+
+    fn camera() -> Camera {
+        Camera::new(
+            na::Point3::new(-10., 0., 0.),
+            na::Point3::origin(),
+            1.,
+            40,
+            30,
+        )
+    }
+
+    fn lit_pixels(canvas: Canvas) -> usize {
+        canvas
+            .into_image()
+            .pixels()
+            .filter(|pixel| pixel.0 != [0, 0, 0])
+            .count()
+    }
+
+    #[test]
+    fn point_is_square_of_given_size() {
+        let mut canvas = Canvas::new(40, 30, BLACK);
+        canvas.point(&camera(), &na::Point3::origin(), 4., &WHITE);
+        assert_eq!(lit_pixels(canvas), 16);
+    }
+
+    #[test]
+    fn huge_point_is_clipped_to_canvas() {
+        let mut canvas = Canvas::new(40, 30, BLACK);
+        canvas.point(&camera(), &na::Point3::origin(), 1e12, &WHITE);
+        assert_eq!(lit_pixels(canvas), 40 * 30);
+    }
+
+    #[test]
+    fn sphere_next_to_camera_is_clipped_to_canvas() {
+        let mut canvas = Canvas::new(40, 30, BLACK);
+        canvas.sphere(&camera(), &na::Point3::new(-9.9, 0., 0.), 1e6, &WHITE);
+        assert_eq!(lit_pixels(canvas), 40 * 30);
+    }
+
+    #[test]
+    fn sphere_outside_of_canvas_draws_nothing() {
+        let mut canvas = Canvas::new(40, 30, BLACK);
+        canvas.sphere(&camera(), &na::Point3::new(0., 100., 0.), 1., &WHITE);
+        assert_eq!(lit_pixels(canvas), 0);
+    }
+
+    #[test]
+    fn nearer_surface_wins() {
+        let mut canvas = Canvas::new(40, 30, BLACK);
+        let red = Rgb::new(1., 0., 0.);
+        canvas.point(&camera(), &na::Point3::origin(), 2., &red);
+        canvas.point(&camera(), &na::Point3::new(5., 0., 0.), 2., &WHITE);
+        let image = canvas.into_image();
+        assert_eq!(image.get_pixel(20, 15).0, [255, 0, 0]);
+    }
+}
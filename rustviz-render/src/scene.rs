@@ -0,0 +1,585 @@
+//! Scene state rebuilt from log entries
+use crate::raster::{Camera, Canvas, Rgb};
+use nalgebra as na;
+use pose_publisher::{
+    point_cloud::PointStyle,
+    pose::Shape,
+    recording::LogMessage,
+    transform::{Transform, TransformError},
+    LaserScan, Lifetime, ObjectPose, Path, PointCloud2, PointCloud3, PoseClientUpdate,
+    TransformTree, TransformUpdate,
+};
+use std::collections::HashMap;
+
+const GROUND_TILE_SIZE: f32 = 0.5;
+const GROUND_TILES: usize = 4;
+/// Clouds without known frame are lifted above the ground plane like in the viewer
+const GROUND_OFFSET: f32 = 0.01;
+/// Seconds of log time without updates before frames from transform messages are dropped
+const TRANSFORM_TIMEOUT: f64 = 10.;
+
+/// Lifetimes are measured in log time. Publishers are never considered gone
+/// since heartbeats are not recorded
+fn is_expired(lifetime: &Lifetime, updated: f64, time: f64) -> bool {
+    match lifetime {
+        Lifetime::Duration(timeout) => time - updated > *timeout as f64,
+        Lifetime::Forever | Lifetime::UntilPublisherGone(_) => false,
+    }
+}
+
+fn isometry(transform: &Transform) -> na::Isometry3<f32> {
+    let (x, y, z) = transform.translation();
+    let (qx, qy, qz, qw) = transform.rotation();
+    na::Isometry3::from_parts(
+        na::Translation3::new(x, y, z),
+        na::UnitQuaternion::from_quaternion(na::Quaternion::new(qw, qx, qy, qz)),
+    )
+}
+
+fn rgb((r, g, b): (f32, f32, f32)) -> Rgb {
+    Rgb::new(r, g, b)
+}
+
+struct SceneObject {
+    object: ObjectPose,
+    parent_frame_id: Option<String>,
+    updated: f64,
+}
+
+/// Entity with the log time of its latest update
+struct SceneEntity<T> {
+    entity: T,
+    updated: f64,
+}
+
+fn insert_entity<T>(entities: &mut HashMap<String, SceneEntity<T>>, id: &str, entity: &T, time: f64)
+where
+    T: Clone,
+{
+    entities.insert(
+        id.to_owned(),
+        SceneEntity {
+            entity: entity.clone(),
+            updated: time,
+        },
+    );
+}
+
+pub struct Scene {
+    fixed_frame: String,
+    objects: HashMap<String, SceneObject>,
+    point_clouds: HashMap<String, SceneEntity<PointCloud2>>,
+    /// Kept apart from 2d clouds so that a 2d and a 3d cloud can share an id
+    point_clouds_3d: HashMap<String, SceneEntity<PointCloud3>>,
+    laser_scans: HashMap<String, SceneEntity<LaserScan>>,
+    paths: HashMap<String, SceneEntity<Path>>,
+    /// Frames from transform messages and objects
+    transforms: TransformTree,
+    /// Log time at which frames of transform messages were last received
+    transform_frames: HashMap<String, f64>,
+}
+
+impl Scene {
+    pub fn new(fixed_frame: &str) -> Self {
+        Self {
+            fixed_frame: fixed_frame.to_owned(),
+            objects: HashMap::new(),
+            point_clouds: HashMap::new(),
+            point_clouds_3d: HashMap::new(),
+            laser_scans: HashMap::new(),
+            paths: HashMap::new(),
+            transforms: TransformTree::new(),
+            transform_frames: HashMap::new(),
+        }
+    }
+
+    /// Occupancy grids, covariances and commands are not drawn
+    pub fn apply(&mut self, message: &LogMessage, time: f64) {
+        match message {
+            LogMessage::Pose(update) => self.update_objects(update, time),
+            LogMessage::PointCloud(point_cloud) => {
+                insert_entity(&mut self.point_clouds, point_cloud.id(), point_cloud, time)
+            }
+            LogMessage::PointCloud3(point_cloud) => insert_entity(
+                &mut self.point_clouds_3d,
+                point_cloud.id(),
+                point_cloud,
+                time,
+            ),
+            LogMessage::LaserScan(laser_scan) => {
+                insert_entity(&mut self.laser_scans, laser_scan.id(), laser_scan, time)
+            }
+            LogMessage::Path(path) => insert_entity(&mut self.paths, path.id(), path, time),
+            LogMessage::Transform(update) => self.update_transforms(update, time),
+            _ => (),
+        }
+    }
+
+    fn update_transforms(&mut self, update: &TransformUpdate, time: f64) {
+        for transform in update.transforms() {
+            self.transforms.insert(transform);
+            self.transform_frames
+                .insert(transform.child_frame_id().to_owned(), time);
+        }
+    }
+
+    /// Deletions are applied after updates like in the viewer
+    fn update_objects(&mut self, update: &PoseClientUpdate, time: f64) {
+        self.insert_objects(update, time);
+        if update.has_deletions() {
+            let deleted: Vec<_> = self
+                .objects
+                .iter()
+                .filter(|(id, object)| update.should_delete(id, &object.object.namespace))
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in deleted {
+                self.remove_object(&id);
+            }
+            self.point_clouds.retain(|id, point_cloud| {
+                !update.should_delete(id, point_cloud.entity.namespace())
+            });
+            self.point_clouds_3d.retain(|id, point_cloud| {
+                !update.should_delete(id, point_cloud.entity.namespace())
+            });
+            self.laser_scans
+                .retain(|id, laser_scan| !update.should_delete(id, laser_scan.entity.namespace()));
+            self.paths
+                .retain(|id, path| !update.should_delete(id, path.entity.namespace()));
+        }
+    }
+
+    /// Objects are also frames unless a transform message publishes a frame of the same id
+    fn insert_objects(&mut self, update: &PoseClientUpdate, time: f64) {
+        // unstamped updates are stamped on arrival
+        let header = update.header();
+        let stamp = if header.has_stamp() {
+            header.stamp
+        } else {
+            time
+        };
+        for object in update.updates() {
+            let parent_frame_id = object
+                .parent_frame_id
+                .clone()
+                .or_else(|| update.header().frame_id.clone());
+            if !self.transform_frames.contains_key(&object.id) {
+                self.transforms.insert(
+                    &Transform::new(
+                        parent_frame_id.as_deref().unwrap_or(&self.fixed_frame),
+                        &object.id,
+                        object.pose,
+                        object.rotation,
+                    )
+                    .with_stamp(stamp),
+                );
+            }
+            self.objects.insert(
+                object.id.clone(),
+                SceneObject {
+                    object: object.clone(),
+                    parent_frame_id,
+                    updated: time,
+                },
+            );
+        }
+    }
+
+    fn remove_object(&mut self, id: &str) {
+        self.objects.remove(id);
+        if !self.transform_frames.contains_key(id) {
+            self.transforms.remove_frame(id);
+        }
+    }
+
+    pub fn remove_timed_out(&mut self, time: f64) {
+        let expired: Vec<_> = self
+            .objects
+            .iter()
+            .filter(|(_, object)| {
+                is_expired(&object.object.effective_lifetime(), object.updated, time)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            self.remove_object(&id);
+        }
+        self.point_clouds.retain(|_, point_cloud| {
+            !is_expired(point_cloud.entity.lifetime(), point_cloud.updated, time)
+        });
+        self.point_clouds_3d.retain(|_, point_cloud| {
+            !is_expired(point_cloud.entity.lifetime(), point_cloud.updated, time)
+        });
+        self.laser_scans.retain(|_, laser_scan| {
+            !is_expired(laser_scan.entity.lifetime(), laser_scan.updated, time)
+        });
+        self.paths
+            .retain(|_, path| !is_expired(path.entity.lifetime(), path.updated, time));
+        // expired by arrival like in the viewer. Static frames are kept until replaced
+        let transforms = &mut self.transforms;
+        self.transform_frames.retain(|id, received| {
+            let keep =
+                transforms.latest_stamp(id).is_none() || time - *received < TRANSFORM_TIMEOUT;
+            if !keep {
+                transforms.remove_frame(id);
+            }
+            keep
+        });
+    }
+
+    /// Pose of frame in the fixed frame, or in the root of its tree if not connected
+    fn resolve_frame(&self, frame_id: &str) -> Option<na::Isometry3<f32>> {
+        let transform = match self
+            .transforms
+            .lookup_transform(&self.fixed_frame, frame_id, None)
+        {
+            Err(TransformError::NotConnected(_, _)) | Err(TransformError::UnknownFrame(_))
+                if self.transforms.has_frame(frame_id) =>
+            {
+                self.transforms.transform_to_root(frame_id, None)
+            }
+            transform => transform,
+        };
+        transform.ok().map(|transform| isometry(&transform))
+    }
+
+    /// Pose of the parent frame of an entity, or fallback if it has none or it is unknown
+    fn entity_frame(
+        &self,
+        parent_frame_id: &Option<String>,
+        fallback: na::Isometry3<f32>,
+    ) -> na::Isometry3<f32> {
+        parent_frame_id
+            .as_deref()
+            .and_then(|frame_id| self.resolve_frame(frame_id))
+            .unwrap_or(fallback)
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, camera: &Camera) {
+        draw_ground_plane(canvas, camera);
+        let above_ground = na::Isometry3::translation(0., 0., GROUND_OFFSET);
+        for object in self.objects.values() {
+            let parent = self.entity_frame(&object.parent_frame_id, na::Isometry3::identity());
+            draw_object(canvas, camera, &parent, &object.object);
+        }
+        for path in self.paths.values() {
+            let frame = self.entity_frame(path.entity.parent_frame_id(), na::Isometry3::identity());
+            draw_path(canvas, camera, &frame, &path.entity);
+        }
+        for point_cloud in self.point_clouds.values() {
+            let point_cloud = &point_cloud.entity;
+            let frame = self.entity_frame(point_cloud.parent_frame_id(), above_ground);
+            let color = rgb(point_cloud.color().to_rgb());
+            let points = point_cloud
+                .points()
+                .iter()
+                .map(|(x, y)| (na::Point3::new(*x, *y, 0.), color));
+            draw_points(
+                canvas,
+                camera,
+                &frame,
+                points,
+                point_cloud.point_size(),
+                point_cloud.style(),
+            );
+        }
+        for point_cloud in self.point_clouds_3d.values() {
+            let frame = self.entity_frame(
+                point_cloud.entity.parent_frame_id(),
+                na::Isometry3::identity(),
+            );
+            draw_point_cloud_3d(canvas, camera, &frame, &point_cloud.entity);
+        }
+        for laser_scan in self.laser_scans.values() {
+            let frame = self.entity_frame(laser_scan.entity.parent_frame_id(), above_ground);
+            // drawn like the viewer draws scans
+            draw_point_cloud_3d(canvas, camera, &frame, &laser_scan.entity.to_point_cloud());
+        }
+    }
+}
+
+/// Same checkerboard as the viewer
+fn draw_ground_plane(canvas: &mut Canvas, camera: &Camera) {
+    let offset = 2_f32.sqrt();
+    let half = GROUND_TILE_SIZE / 2.;
+    for i in 0..GROUND_TILES {
+        for j in 0..GROUND_TILES {
+            let x = GROUND_TILE_SIZE * (i as f32 - offset);
+            let y = GROUND_TILE_SIZE * (j as f32 - offset);
+            let color = if (i + j) % 2 == 0 {
+                Rgb::new(0., 0., 0.)
+            } else {
+                Rgb::new(1., 1., 1.)
+            };
+            let shaded = color * camera.shade(&na::Vector3::z());
+            canvas.quad(
+                camera,
+                [
+                    na::Point3::new(x - half, y - half, 0.),
+                    na::Point3::new(x + half, y - half, 0.),
+                    na::Point3::new(x + half, y + half, 0.),
+                    na::Point3::new(x - half, y + half, 0.),
+                ],
+                &shaded,
+            );
+        }
+    }
+}
+
+/// Box with given half extents, flat shaded per face
+fn draw_cube(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    transform: &na::Isometry3<f32>,
+    half_extents: (f32, f32, f32),
+    color: &Rgb,
+) {
+    let (hx, hy, hz) = half_extents;
+    let corner = |x: f32, y: f32, z: f32| transform * na::Point3::new(x * hx, y * hy, z * hz);
+    let faces = [
+        (
+            na::Vector3::x(),
+            [(1., -1., -1.), (1., 1., -1.), (1., 1., 1.), (1., -1., 1.)],
+        ),
+        (
+            -na::Vector3::x(),
+            [
+                (-1., -1., -1.),
+                (-1., -1., 1.),
+                (-1., 1., 1.),
+                (-1., 1., -1.),
+            ],
+        ),
+        (
+            na::Vector3::y(),
+            [(-1., 1., -1.), (-1., 1., 1.), (1., 1., 1.), (1., 1., -1.)],
+        ),
+        (
+            -na::Vector3::y(),
+            [
+                (-1., -1., -1.),
+                (1., -1., -1.),
+                (1., -1., 1.),
+                (-1., -1., 1.),
+            ],
+        ),
+        (
+            na::Vector3::z(),
+            [(-1., -1., 1.), (1., -1., 1.), (1., 1., 1.), (-1., 1., 1.)],
+        ),
+        (
+            -na::Vector3::z(),
+            [
+                (-1., -1., -1.),
+                (-1., 1., -1.),
+                (1., 1., -1.),
+                (1., -1., -1.),
+            ],
+        ),
+    ];
+    for (normal, corners) in faces.iter() {
+        let shade = camera.shade(&(transform.rotation * normal));
+        let [a, b, c, d] = corners;
+        canvas.quad(
+            camera,
+            [
+                corner(a.0, a.1, a.2),
+                corner(b.0, b.1, b.2),
+                corner(c.0, c.1, c.2),
+                corner(d.0, d.1, d.2),
+            ],
+            &(color * shade),
+        );
+    }
+}
+
+fn draw_object(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    parent: &na::Isometry3<f32>,
+    object: &ObjectPose,
+) {
+    let (x, y, z) = object.pose;
+    let (qx, qy, qz, qw) = object.rotation;
+    let transform = parent
+        * na::Isometry3::from_parts(
+            na::Translation3::new(x, y, z),
+            na::UnitQuaternion::from_quaternion(na::Quaternion::new(qw, qx, qy, qz)),
+        );
+    let (scale_x, scale_y, scale_z) = object.scale.unwrap_or((1., 1., 1.));
+    let color = rgb(object.color.to_rgb());
+    match object.shape {
+        // scaled spheres are drawn with their largest radius
+        Shape::Sphere(radius) => {
+            let radius = radius * scale_x.max(scale_y).max(scale_z);
+            canvas.sphere(camera, &(transform * na::Point3::origin()), radius, &color);
+        }
+        Shape::Cube(x, y, z) => draw_cube(
+            canvas,
+            camera,
+            &transform,
+            (x * scale_x / 2., y * scale_y / 2., z * scale_z / 2.),
+            &color,
+        ),
+        Shape::Line((x, y, z)) => canvas.line(
+            camera,
+            &(transform * na::Point3::origin()),
+            &(parent * na::Point3::new(x, y, z)),
+            &color,
+        ),
+        Shape::Axes(length) => {
+            let origin = transform * na::Point3::origin();
+            for (end, color) in &[
+                ((length * scale_x, 0., 0.), Rgb::new(1., 0., 0.)),
+                ((0., length * scale_y, 0.), Rgb::new(0., 1., 0.)),
+                ((0., 0., length * scale_z), Rgb::new(0., 0., 1.)),
+            ] {
+                let end = transform * na::Point3::new(end.0, end.1, end.2);
+                canvas.line(camera, &origin, &end, color);
+            }
+        }
+    }
+}
+
+fn draw_path(canvas: &mut Canvas, camera: &Camera, frame: &na::Isometry3<f32>, path: &Path) {
+    let color = rgb(path.color().to_rgb());
+    for segment in path.points().windows(2) {
+        let point = |index: usize| {
+            let (x, y, z) = segment[index].position();
+            frame * na::Point3::new(x, y, z)
+        };
+        canvas.line(camera, &point(0), &point(1), &color);
+    }
+}
+
+fn draw_point_cloud_3d(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    frame: &na::Isometry3<f32>,
+    point_cloud: &PointCloud3,
+) {
+    let points = point_cloud
+        .points()
+        .iter()
+        .zip(point_cloud.point_colors())
+        .map(|((x, y, z), color)| (na::Point3::new(*x, *y, *z), rgb(color)));
+    draw_points(
+        canvas,
+        camera,
+        frame,
+        points,
+        point_cloud.point_size(),
+        point_cloud.style(),
+    );
+}
+
+/// Squares lie in the xy plane of the frame like in the viewer
+fn draw_points(
+    canvas: &mut Canvas,
+    camera: &Camera,
+    frame: &na::Isometry3<f32>,
+    points: impl Iterator<Item = (na::Point3<f32>, Rgb)>,
+    size: f32,
+    style: &PointStyle,
+) {
+    let half = size / 2.;
+    let shade = camera.shade(&(frame.rotation * na::Vector3::z()));
+    for (point, color) in points {
+        let center = frame * point;
+        match style {
+            PointStyle::Points => canvas.point(camera, &center, size, &color),
+            PointStyle::Squares => {
+                let corner = |dx: f32, dy: f32| frame * (point + na::Vector3::new(dx, dy, 0.));
+                canvas.quad(
+                    camera,
+                    [
+                        corner(-half, -half),
+                        corner(half, -half),
+                        corner(half, half),
+                        corner(-half, half),
+                    ],
+                    &(color * shade),
+                );
+            }
+            PointStyle::Cubes => {
+                let transform = frame * na::Translation3::from(point.coords);
+                draw_cube(canvas, camera, &transform, (half, half, half), &color);
+            }
+            PointStyle::Spheres => canvas.sphere(camera, &center, half, &color),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pose_publisher::pose::Color;
+
+    fn camera() -> Camera {
+        Camera::new(
+            na::Point3::new(-10., 0., 0.),
+            na::Point3::origin(),
+            1.,
+            40,
+            30,
+        )
+    }
+
+    fn transform_update(transform: Transform) -> LogMessage {
+        let mut update = TransformUpdate::new();
+        update.add(transform);
+        LogMessage::Transform(update)
+    }
+
+    fn is_red(image: &image::RgbImage, point: na::Point3<f32>) -> bool {
+        let (x, y, _) = camera().project(&point).unwrap();
+        let [r, g, b] = image.get_pixel(x as u32, y as u32).0;
+        r > 0 && g == 0 && b == 0
+    }
+
+    #[test]
+    fn object_is_drawn_in_its_transform_frame() {
+        let mut scene = Scene::new("map");
+        scene.apply(
+            &transform_update(
+                Transform::new("map", "robot", (0., 0., 2.), (0., 0., 0., 1.)).with_stamp(1.),
+            ),
+            100.,
+        );
+        let mut update = PoseClientUpdate::new();
+        update
+            .add("marker", (0., 0., 0.))
+            .with_parent_frame_id("robot")
+            .with_shape(Shape::Sphere(0.5))
+            .with_color(Color::Red);
+        scene.apply(&LogMessage::Pose(update), 100.);
+
+        let mut canvas = Canvas::new(40, 30, Rgb::new(0., 0., 0.));
+        scene.draw(&mut canvas, &camera());
+        let image = canvas.into_image();
+        assert!(is_red(&image, na::Point3::new(0., 0., 2.)));
+        assert!(!is_red(&image, na::Point3::origin()));
+    }
+
+    #[test]
+    fn transform_frames_expire_by_arrival_and_win_over_objects() {
+        let mut scene = Scene::new("map");
+        // publisher clock far behind the log
+        scene.apply(
+            &transform_update(
+                Transform::new("map", "robot", (0., 0., 2.), (0., 0., 0., 1.)).with_stamp(1.),
+            ),
+            100.,
+        );
+        let mut update = PoseClientUpdate::new();
+        update
+            .add("robot", (5., 0., 0.))
+            .with_parent_frame_id("odom");
+        scene.apply(&LogMessage::Pose(update), 101.);
+        scene.remove_timed_out(105.);
+        assert_eq!(scene.transforms.parent("robot"), Some("map"));
+
+        scene.remove_timed_out(100. + TRANSFORM_TIMEOUT);
+        assert_eq!(scene.transforms.parent("robot"), None);
+    }
+}
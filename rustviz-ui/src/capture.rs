@@ -0,0 +1,197 @@
+use image::RgbImage;
+use kiss3d::window::Window;
+use pose_publisher::header::timestamp_now;
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Sender},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// Unix time in milliseconds used to name captures
+fn timestamp_name() -> u64 {
+    (timestamp_now() * 1000.0) as u64
+}
+
+fn screenshot_path(directory: &Path, timestamp: u64) -> PathBuf {
+    directory.join(format!("rustviz_{}.png", timestamp))
+}
+
+fn recording_directory(directory: &Path, timestamp: u64) -> PathBuf {
+    directory.join(format!("rustviz_recording_{}", timestamp))
+}
+
+struct Recording {
+    directory: PathBuf,
+    frames: usize,
+    last_frame: Instant,
+}
+
+impl Recording {
+    /// First frame is due right away
+    fn new(directory: PathBuf, period: Duration, now: Instant) -> Self {
+        Self {
+            directory,
+            frames: 0,
+            last_frame: now - period,
+        }
+    }
+
+    /// Path of the next frame if one is due at `now`
+    fn next_frame(&mut self, period: Duration, now: Instant) -> Option<PathBuf> {
+        if now.duration_since(self.last_frame) < period {
+            return None;
+        }
+        self.last_frame = now;
+        let path = self.directory.join(format!("frame_{:05}.png", self.frames));
+        self.frames += 1;
+        Some(path)
+    }
+}
+
+/// Saves screenshots and recorded frames of the window as PNG
+///
+/// Images are encoded on a separate thread so that recording doesn't stall rendering
+pub struct Capture {
+    directory: PathBuf,
+    period: Duration,
+    screenshot_requested: bool,
+    recording: Option<Recording>,
+    writer: Sender<(PathBuf, RgbImage)>,
+    writer_thread: JoinHandle<()>,
+}
+
+impl Capture {
+    /// Rate of recorded frames in Hz. Has to be positive
+    pub fn new(directory: PathBuf, rate: f32) -> Self {
+        let (writer, images) = channel::<(PathBuf, RgbImage)>();
+        let writer_thread = std::thread::spawn(move || {
+            for (path, image) in images {
+                if let Err(error) = image.save(&path) {
+                    eprintln!("Failed to save {} {}", path.display(), error);
+                }
+            }
+        });
+        Self {
+            directory,
+            period: Duration::from_secs_f32(1.0 / rate),
+            screenshot_requested: false,
+            recording: None,
+            writer,
+            writer_thread,
+        }
+    }
+
+    /// Screenshot is taken after the next frame is rendered
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// Frames are saved to a new timestamped directory on every recording
+    pub fn toggle_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            println!(
+                "Recorded {} frames to {}",
+                recording.frames,
+                recording.directory.display()
+            );
+            return;
+        }
+        let directory = recording_directory(&self.directory, timestamp_name());
+        if let Err(error) = std::fs::create_dir_all(&directory) {
+            eprintln!("Failed to create {} {}", directory.display(), error);
+            return;
+        }
+        println!("Recording to {}", directory.display());
+        self.recording = Some(Recording::new(directory, self.period, Instant::now()));
+    }
+
+    /// Frames recorded so far
+    pub fn recorded_frames(&self) -> Option<usize> {
+        self.recording.as_ref().map(|recording| recording.frames)
+    }
+
+    /// Call after rendering so that the rendered frame is captured
+    pub fn capture(&mut self, window: &Window) {
+        let mut paths = vec![];
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            let path = screenshot_path(&self.directory, timestamp_name());
+            println!("Saving screenshot to {}", path.display());
+            paths.push(path);
+        }
+        if let Some(recording) = &mut self.recording {
+            paths.extend(recording.next_frame(self.period, Instant::now()));
+        }
+        if paths.is_empty() {
+            return;
+        }
+        let image = window.snap_image();
+        for path in paths {
+            if self.writer.send((path, image.clone())).is_err() {
+                eprintln!("Image writer stopped");
+            }
+        }
+    }
+
+    /// Waits until all captured images are written
+    pub fn finish(self) {
+        let Self {
+            writer,
+            writer_thread,
+            ..
+        } = self;
+        drop(writer);
+        if writer_thread.join().is_err() {
+            eprintln!("Image writer panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_are_named_by_timestamp() {
+        let directory = Path::new("captures");
+        assert_eq!(
+            screenshot_path(directory, 1_600_000_000_123),
+            Path::new("captures/rustviz_1600000000123.png")
+        );
+        assert_eq!(
+            recording_directory(directory, 1_600_000_000_123),
+            Path::new("captures/rustviz_recording_1600000000123")
+        );
+    }
+
+    #[test]
+    fn first_frame_is_due_right_away() {
+        let now = Instant::now();
+        let mut recording = Recording::new("recording".into(), Duration::from_millis(100), now);
+        assert_eq!(
+            recording.next_frame(Duration::from_millis(100), now),
+            Some(PathBuf::from("recording/frame_00000.png"))
+        );
+    }
+
+    #[test]
+    fn frames_are_recorded_once_per_period() {
+        let period = Duration::from_millis(100);
+        let start = Instant::now();
+        let mut recording = Recording::new("recording".into(), period, start);
+        let frames: Vec<_> = (0..10)
+            .filter_map(|i| recording.next_frame(period, start + Duration::from_millis(i * 30)))
+            .collect();
+        // due at 0, 120 and 240 ms
+        assert_eq!(
+            frames,
+            vec![
+                PathBuf::from("recording/frame_00000.png"),
+                PathBuf::from("recording/frame_00001.png"),
+                PathBuf::from("recording/frame_00002.png"),
+            ]
+        );
+        assert_eq!(recording.frames, 3);
+    }
+}
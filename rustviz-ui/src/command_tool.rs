@@ -0,0 +1,211 @@
+use kiss3d::event::{Key, Modifiers};
+use nalgebra as na;
+use pose_publisher::commands::{Command, CommandStatus};
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// Time between retransmissions of an unacknowledged command
+const RETRANSMIT_PERIOD: Duration = Duration::from_millis(250);
+/// Robot is considered unreachable after this many unacknowledged transmissions
+const MAX_TRANSMISSIONS: u32 = 10;
+
+/// Kind of command created by right clicking the ground plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandTool {
+    /// Drag from goal position towards goal heading
+    Goal,
+    /// Drag from estimated position towards estimated heading
+    InitialPose,
+    /// Single click
+    Point,
+    /// Click every waypoint and send with Enter
+    Waypoints,
+}
+
+impl CommandTool {
+    /// Tools are selected with number keys 1 -> 4
+    pub fn from_key(key: Key) -> Option<Self> {
+        match key {
+            Key::Key1 => Some(CommandTool::Goal),
+            Key::Key2 => Some(CommandTool::InitialPose),
+            Key::Key3 => Some(CommandTool::Point),
+            Key::Key4 => Some(CommandTool::Waypoints),
+            _ => None,
+        }
+    }
+
+    /// Held modifier overrides the selected tool.
+    /// Shift for initial pose, Control for point and Alt for waypoints
+    pub fn with_modifiers(self, modifiers: Modifiers) -> Self {
+        if modifiers.contains(Modifiers::Shift) {
+            CommandTool::InitialPose
+        } else if modifiers.contains(Modifiers::Control) {
+            CommandTool::Point
+        } else if modifiers.contains(Modifiers::Alt) {
+            CommandTool::Waypoints
+        } else {
+            self
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandTool::Goal => "goal",
+            CommandTool::InitialPose => "initial pose",
+            CommandTool::Point => "point",
+            CommandTool::Waypoints => "waypoints",
+        }
+    }
+
+    /// Color of markers drawn while using the tool
+    pub fn color(&self) -> na::Point3<f32> {
+        match self {
+            CommandTool::Goal => na::Point3::new(1.0, 0.0, 1.0),
+            CommandTool::InitialPose => na::Point3::new(0.0, 1.0, 0.0),
+            CommandTool::Point => na::Point3::new(1.0, 1.0, 0.0),
+            CommandTool::Waypoints => na::Point3::new(0.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl FromStr for CommandTool {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "goal" => Ok(CommandTool::Goal),
+            "initial_pose" | "initial-pose" => Ok(CommandTool::InitialPose),
+            "point" => Ok(CommandTool::Point),
+            "waypoints" => Ok(CommandTool::Waypoints),
+            _ => Err(format!("unknown command tool {}", s)),
+        }
+    }
+}
+
+/// Last command sent and feedback received for it
+pub struct PendingCommand {
+    command: Command,
+    /// Where the status is shown
+    marker: na::Point3<f32>,
+    status: Option<CommandStatus>,
+    transmissions: u32,
+    last_sent: Option<Instant>,
+}
+
+impl PendingCommand {
+    pub fn new(command: Command, marker: na::Point3<f32>) -> Self {
+        Self {
+            command,
+            marker,
+            status: None,
+            transmissions: 0,
+            last_sent: None,
+        }
+    }
+
+    pub fn marker(&self) -> &na::Point3<f32> {
+        &self.marker
+    }
+
+    /// Commands are retransmitted until the robot acknowledges them with any status
+    pub fn is_acknowledged(&self) -> bool {
+        self.status.is_some()
+    }
+
+    /// Robot never acknowledged the command and retransmission stopped
+    pub fn is_unanswered(&self, now: Instant) -> bool {
+        !self.is_acknowledged()
+            && self.transmissions >= MAX_TRANSMISSIONS
+            && self.last_sent.is_some_and(|last_sent| {
+                now.saturating_duration_since(last_sent) >= RETRANSMIT_PERIOD
+            })
+    }
+
+    /// Command to send now if it is due for a transmission. Counts as sent
+    pub fn transmission(&mut self, now: Instant) -> Option<&Command> {
+        let is_due = self
+            .last_sent
+            .is_none_or(|last_sent| now.saturating_duration_since(last_sent) >= RETRANSMIT_PERIOD);
+        if self.is_acknowledged() || self.transmissions >= MAX_TRANSMISSIONS || !is_due {
+            return None;
+        }
+        self.transmissions += 1;
+        self.last_sent = Some(now);
+        Some(&self.command)
+    }
+
+    /// Statuses of other commands are ignored
+    pub fn update_status(&mut self, status: CommandStatus) {
+        if status.command_id() == self.command.id() {
+            self.status = Some(status);
+        }
+    }
+
+    pub fn description(&self, now: Instant) -> String {
+        let status = match &self.status {
+            Some(status) if status.text().is_empty() => status.state().name().to_owned(),
+            Some(status) => format!("{} {}", status.state().name(), status.text()),
+            None if self.is_unanswered(now) => "no response".to_owned(),
+            None => "waiting for robot".to_owned(),
+        };
+        format!(
+            "#{} {}: {}",
+            self.command.id(),
+            self.command.kind().name(),
+            status
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pose_publisher::commands::CommandState;
+
+    #[test]
+    fn command_is_retransmitted_at_limited_rate() {
+        let start = Instant::now();
+        let mut pending = PendingCommand::new(
+            Command::clicked_point(1, (0., 0., 0.)),
+            na::Point3::origin(),
+        );
+        assert!(pending.transmission(start).is_some());
+        assert!(pending
+            .transmission(start + RETRANSMIT_PERIOD / 2)
+            .is_none());
+        assert!(pending.transmission(start + RETRANSMIT_PERIOD).is_some());
+    }
+
+    #[test]
+    fn retransmission_stops_after_acknowledgement() {
+        let start = Instant::now();
+        let mut pending = PendingCommand::new(
+            Command::clicked_point(1, (0., 0., 0.)),
+            na::Point3::origin(),
+        );
+        assert!(pending.transmission(start).is_some());
+        pending.update_status(CommandStatus::new(2, CommandState::Accepted));
+        assert!(!pending.is_acknowledged());
+        pending.update_status(CommandStatus::new(1, CommandState::Accepted));
+        assert!(pending.transmission(start + RETRANSMIT_PERIOD).is_none());
+    }
+
+    #[test]
+    fn command_without_response_is_given_up() {
+        let mut now = Instant::now();
+        let mut pending = PendingCommand::new(
+            Command::clicked_point(1, (0., 0., 0.)),
+            na::Point3::origin(),
+        );
+        for _ in 0..MAX_TRANSMISSIONS {
+            assert!(pending.transmission(now).is_some());
+            assert!(!pending.is_unanswered(now));
+            now += RETRANSMIT_PERIOD;
+        }
+        assert!(pending.transmission(now).is_none());
+        assert!(pending.is_unanswered(now));
+        assert!(pending.description(now).ends_with("no response"));
+    }
+}
@@ -0,0 +1,152 @@
+use pose_publisher::{Heartbeat, Lifetime};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Publisher is considered gone after this many missed heartbeats
+const MISSED_HEARTBEAT_LIMIT: u32 = 3;
+/// How long entities of publishers we never heard from are kept
+const UNKNOWN_PUBLISHER_GRACE: Duration = Duration::from_secs(5);
+
+/// None for non finite or out of range seconds, which are treated as forever
+fn seconds_to_duration(seconds: f32) -> Option<Duration> {
+    if !seconds.is_finite() {
+        return None;
+    }
+    Duration::try_from_secs_f32(seconds.max(0.0)).ok()
+}
+
+struct PublisherStatus {
+    last_seen: Instant,
+    /// Publisher never times out if None
+    timeout: Option<Duration>,
+}
+
+impl PublisherStatus {
+    fn is_alive(&self, now: Instant) -> bool {
+        self.timeout
+            .is_none_or(|timeout| now.saturating_duration_since(self.last_seen) < timeout)
+    }
+}
+
+/// Last heartbeat of every publisher
+#[derive(Default)]
+pub struct PublisherTracker {
+    publishers: HashMap<String, PublisherStatus>,
+}
+
+impl PublisherTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, heartbeat: &Heartbeat, now: Instant) {
+        self.publishers.insert(
+            heartbeat.publisher_id().to_owned(),
+            PublisherStatus {
+                last_seen: now,
+                timeout: seconds_to_duration(heartbeat.period())
+                    .and_then(|period| period.checked_mul(MISSED_HEARTBEAT_LIMIT)),
+            },
+        );
+    }
+
+    pub fn is_alive(&self, publisher_id: &str, now: Instant) -> Option<bool> {
+        self.publishers
+            .get(publisher_id)
+            .map(|status| status.is_alive(now))
+    }
+
+    /// Forget publishers that stopped sending heartbeats
+    pub fn remove_gone(&mut self, now: Instant) {
+        self.publishers.retain(|_, status| status.is_alive(now));
+    }
+
+    /// (publisher id, seconds since last heartbeat)
+    pub fn publishers(&self, now: Instant) -> Vec<(String, f32)> {
+        self.publishers
+            .iter()
+            .map(|(id, status)| {
+                (
+                    id.clone(),
+                    now.saturating_duration_since(status.last_seen)
+                        .as_secs_f32(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Tracks when an entity was last updated and whether it should be removed
+///
+/// Times are scene times so that history can be replayed with the original timing
+pub struct Expiry {
+    lifetime: Lifetime,
+    last_touched: Instant,
+}
+
+impl Expiry {
+    pub fn new(lifetime: Lifetime, now: Instant) -> Self {
+        Self {
+            lifetime,
+            last_touched: now,
+        }
+    }
+
+    pub fn touch(&mut self, lifetime: Lifetime, now: Instant) {
+        self.lifetime = lifetime;
+        self.last_touched = now;
+    }
+
+    pub fn is_expired(&self, publishers: &PublisherTracker, now: Instant) -> bool {
+        let since_touched = now.saturating_duration_since(self.last_touched);
+        match &self.lifetime {
+            Lifetime::Forever => false,
+            Lifetime::Duration(timeout) => {
+                seconds_to_duration(*timeout).is_some_and(|timeout| since_touched > timeout)
+            }
+            Lifetime::UntilPublisherGone(publisher_id) => {
+                match publishers.is_alive(publisher_id, now) {
+                    Some(alive) => !alive,
+                    None => since_touched > UNKNOWN_PUBLISHER_GRACE,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_durations_are_forever() {
+        assert_eq!(seconds_to_duration(f32::NAN), None);
+        assert_eq!(seconds_to_duration(f32::INFINITY), None);
+        assert_eq!(seconds_to_duration(f32::MAX), None);
+        assert_eq!(seconds_to_duration(-1.0), Some(Duration::ZERO));
+        assert_eq!(seconds_to_duration(0.5), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn huge_heartbeat_period_keeps_publisher_alive() {
+        let now = Instant::now();
+        let mut tracker = PublisherTracker::new();
+        tracker.record(&Heartbeat::new("a", 1e12), now);
+        tracker.record(&Heartbeat::new("b", f32::INFINITY), now);
+        let later = now + Duration::from_secs(3600);
+        assert_eq!(tracker.is_alive("a", later), Some(true));
+        assert_eq!(tracker.is_alive("b", later), Some(true));
+    }
+
+    #[test]
+    fn non_finite_duration_never_expires() {
+        let now = Instant::now();
+        let expiry = Expiry::new(Lifetime::Duration(f32::NAN), now);
+        let later = now + Duration::from_secs(3600);
+        assert!(!expiry.is_expired(&PublisherTracker::new(), later));
+        let expiry = Expiry::new(Lifetime::Duration(1.0), now);
+        assert!(expiry.is_expired(&PublisherTracker::new(), later));
+    }
+}
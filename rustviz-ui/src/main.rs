@@ -1,24 +1,57 @@
-use anyhow::Result;
+mod capture;
+mod command_tool;
+mod lifetime;
+mod occupancy_grid;
+mod point_batches;
+mod point_cloud_mesh;
+mod render_state;
+mod snapshot;
+mod teleop;
+mod time_sync;
+mod timeline;
+mod translucent;
+
+use anyhow::{anyhow, Result};
+use capture::Capture;
 use clap::Clap;
+use command_tool::{CommandTool, PendingCommand};
 use kiss3d::{
     camera::Camera,
-    event::{Action, MouseButton, WindowEvent},
+    event::{Action, Key, Modifiers, MouseButton, WindowEvent},
     light::Light,
+    resource::Mesh,
     scene::SceneNode,
     window::Window,
 };
+use lifetime::{Expiry, PublisherTracker};
 use nalgebra as na;
+use occupancy_grid::{GridColorScheme, OccupancyGridContainer};
+use point_batches::PointBatches;
 use pose_publisher::{
     commands::Command,
-    point_cloud::PointCloud2,
-    pose::{Color, Shape},
-    CommandPublisher, ObjectPose, PointCloudSubscriber, PoseSubscriber,
+    header::timestamp_now,
+    point_cloud::{PointCloud2, PointCloud3, PointStyle},
+    pose::{Color, Covariance, Shape},
+    transform::TransformError,
+    CommandPublisher, CommandStatusSubscriber, Header, Heartbeat, HeartbeatSubscriber, LaserScan,
+    LaserScanSubscriber, ObjectPose, OccupancyGridMessage, OccupancyGridSubscriber, Path,
+    PathSubscriber, PointCloud3Subscriber, PointCloudSubscriber, PoseClientUpdate, PoseSubscriber,
+    Transform, TransformSubscriber, TransformTree, TransformUpdate,
 };
+use render_state::RenderState;
+use snapshot::SceneSnapshot;
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
     net::SocketAddrV4,
+    path::PathBuf,
+    rc::Rc,
     time::{Duration, Instant},
 };
+use teleop::Teleop;
+use time_sync::TimeSynchronizer;
+use timeline::Timeline;
+use translucent::TranslucentRenderer;
 
 fn convert_coordinate_system((x, y, z): (f32, f32, f32)) -> na::Vector3<f32> {
     na::Vector3::new(y, z, x)
@@ -38,68 +71,414 @@ fn attach_node_type(shape: Shape, window: &mut Window) -> Option<SceneNode> {
     match shape {
         Shape::Sphere(radius) => Some(window.add_sphere(radius)),
         Shape::Cube(x, y, z) => Some(window.add_cube(y, z, x)),
-        Shape::Line(_) => None,
+        Shape::Line(_) | Shape::Axes(_) => None,
+    }
+}
+
+const PATH_ARROW_LENGTH: f32 = 0.1;
+const POINT_SCALE_STEP: f32 = 1.25;
+const COVARIANCE_ELLIPSE_SEGMENTS: usize = 32;
+const COVARIANCE_WEDGE_RADIUS: f32 = 0.3;
+const DEFAULT_AXES_LENGTH: f32 = 0.2;
+/// Time without updates before frames from transform messages are dropped
+const TRANSFORM_TIMEOUT: Duration = Duration::from_secs(10);
+/// Seconds moved per scrub key press, with Shift held for the large step
+const TIMELINE_STEP: f32 = 0.5;
+const TIMELINE_LARGE_STEP: f32 = 5.0;
+
+/// Viewer side overrides of how point clouds are drawn
+#[derive(Clone)]
+struct PointRenderSettings {
+    size_override: Option<f32>,
+    style_override: Option<PointStyle>,
+    scale: f32,
+}
+
+impl PointRenderSettings {
+    fn style(&self, style: PointStyle) -> PointStyle {
+        self.style_override.unwrap_or(style)
+    }
+
+    fn size(&self, size: f32) -> f32 {
+        self.size_override.unwrap_or(size) * self.scale
+    }
+}
+
+/// Viewer options that can be changed from command line or keyboard
+#[derive(Clone)]
+struct ViewSettings {
+    point_render: PointRenderSettings,
+    laser_rays: bool,
+    grid_color_scheme: GridColorScheme,
+    /// Number of standard deviations covariance ellipses span
+    covariance_sigma: f32,
+    /// Draw coordinate axes on every object
+    axes: bool,
+    /// Frame everything is drawn in
+    fixed_frame: String,
+    frame_tree: bool,
+}
+
+/// Stamp of a header if the publisher set one
+fn header_stamp(header: &Header) -> Option<f64> {
+    if header.has_stamp() {
+        Some(header.stamp)
+    } else {
+        None
     }
 }
 
+/// Removes entities deleted by `update`. Entities without a namespace pass an empty one
+fn retain_undeleted<T>(
+    entities: &mut HashMap<String, T>,
+    update: &PoseClientUpdate,
+    namespace: impl Fn(&T) -> &str,
+) {
+    entities.retain(|id, entity| !update.should_delete(id, namespace(entity)));
+}
+
+/// Message applied to the scene. Kept in the timeline for scrubbing
+#[derive(Clone)]
+enum Message {
+    Pose(PoseClientUpdate),
+    PointCloud(PointCloud2),
+    PointCloud3(PointCloud3),
+    LaserScan(LaserScan),
+    OccupancyGrid(OccupancyGridMessage),
+    Path(Path),
+    Transform(TransformUpdate),
+    Heartbeat(Heartbeat),
+}
+
+/// Map a point cloud container is kept in
+#[derive(Clone, Copy)]
+enum PointSource {
+    Cloud,
+    Cloud3d,
+    LaserScan,
+}
+
 struct ObjectContainer {
     objects: HashMap<String, VisualizerObject>,
     point_clouds: HashMap<String, PointCloudContainer>,
+    /// Kept apart from 2d clouds so that a 2d and a 3d cloud can share an id
+    point_clouds_3d: HashMap<String, PointCloudContainer>,
+    /// Kept apart from clouds so that a scan and a cloud can share an id
+    laser_scans: HashMap<String, PointCloudContainer>,
+    paths: HashMap<String, PathContainer>,
+    occupancy_grids: HashMap<String, OccupancyGridContainer>,
+    publishers: PublisherTracker,
+    /// Frames from transform messages and objects
+    transforms: TransformTree,
+    /// Scene time at which frames of transform messages were last received
+    transform_frames: HashMap<String, Instant>,
+    settings: ViewSettings,
+    /// Scene time used for lifetimes. Current time unless history is replayed
+    now: Instant,
 }
 
 impl ObjectContainer {
-    fn new() -> Self {
+    fn new(settings: ViewSettings) -> Self {
         Self {
             objects: HashMap::new(),
             point_clouds: HashMap::new(),
+            point_clouds_3d: HashMap::new(),
+            laser_scans: HashMap::new(),
+            paths: HashMap::new(),
+            occupancy_grids: HashMap::new(),
+            publishers: PublisherTracker::new(),
+            transforms: TransformTree::new(),
+            transform_frames: HashMap::new(),
+            settings,
+            now: Instant::now(),
         }
     }
 
-    fn update_object(&mut self, object: &ObjectPose, window: &mut Window) {
+    fn set_time(&mut self, now: Instant) {
+        self.now = now;
+    }
+
+    /// Scene rebuilt from the snapshot before the timeline and messages in timeline up to time
+    fn from_history(
+        settings: ViewSettings,
+        snapshot: &SceneSnapshot,
+        timeline: &Timeline<Message>,
+        time: Instant,
+        window: &mut Window,
+    ) -> Self {
+        let mut container = Self::new(settings);
+        for (applied, message) in snapshot.messages() {
+            container.set_time(applied);
+            container.apply_message(message, window);
+        }
+        for (applied, message) in timeline.until(time) {
+            container.set_time(*applied);
+            container.apply_message(message.clone(), window);
+        }
+        container.set_time(time);
+        container.remove_timed_out();
+        container
+    }
+
+    /// Hide all scene nodes while another scene is shown
+    fn set_visible(&mut self, visible: bool) {
+        for object in self.objects.values_mut() {
+            if let Some(node) = &mut object.node {
+                node.set_visible(visible);
+            }
+        }
+        for point_cloud in self
+            .point_clouds
+            .values_mut()
+            .chain(self.point_clouds_3d.values_mut())
+            .chain(self.laser_scans.values_mut())
+        {
+            if let Some(node) = &mut point_cloud.node {
+                node.set_visible(visible);
+            }
+        }
+        for path in self.paths.values_mut() {
+            if let Some(ribbon) = &mut path.ribbon {
+                ribbon.set_visible(visible);
+            }
+        }
+        for grid in self.occupancy_grids.values_mut() {
+            grid.set_visible(visible);
+        }
+    }
+
+    fn apply_message(&mut self, message: Message, window: &mut Window) {
+        match message {
+            Message::Pose(update) => {
+                for object_update in update.updates() {
+                    self.update_object(object_update, update.header(), window);
+                }
+                self.apply_deletions(&update);
+            }
+            Message::PointCloud(point_cloud) => self.update_point_clouds(point_cloud, window),
+            Message::PointCloud3(point_cloud) => self.update_point_clouds_3d(point_cloud, window),
+            Message::LaserScan(laser_scan) => self.update_laser_scans(laser_scan, window),
+            Message::OccupancyGrid(message) => self.update_occupancy_grids(message, window),
+            Message::Path(path) => self.update_paths(path, window),
+            Message::Transform(update) => self.update_transforms(&update),
+            Message::Heartbeat(heartbeat) => self.update_heartbeat(heartbeat),
+        }
+    }
+
+    /// Objects are also frames other entities can be attached to unless a transform message
+    /// publishes a frame of the same id
+    ///
+    /// Objects without a parent frame are placed in the frame of the update header
+    fn update_object(&mut self, object: &ObjectPose, header: &Header, window: &mut Window) {
+        let parent_frame_id = object
+            .parent_frame_id
+            .clone()
+            .or_else(|| header.frame_id.clone());
         if let Some(node_reference) = self.objects.get_mut(&object.id) {
-            node_reference.update(object, window);
+            node_reference.update(object, header, self.now, window);
         } else {
-            let node = VisualizerObject::new(object, window);
+            let node = VisualizerObject::new(object, header, self.now, window);
             self.objects.insert(object.id.clone(), node);
         }
+        let parent_frame_id = parent_frame_id
+            .as_ref()
+            .unwrap_or(&self.settings.fixed_frame);
+        if self.transform_frames.contains_key(&object.id) {
+            return;
+        }
+        let transform = Transform::new(parent_frame_id, &object.id, object.pose, object.rotation);
+        // unstamped updates are stamped on arrival
+        let transform = if header.has_stamp() {
+            transform.with_stamp(header.stamp)
+        } else {
+            transform
+        };
+        self.transforms.insert(&transform);
+    }
+
+    fn update_transforms(&mut self, update: &TransformUpdate) {
+        for transform in update.transforms() {
+            self.transforms.insert(transform);
+            self.transform_frames
+                .insert(transform.child_frame_id().to_owned(), self.now);
+        }
+    }
+
+    fn apply_deletions(&mut self, update: &PoseClientUpdate) {
+        if !update.has_deletions() {
+            return;
+        }
+        let transforms = &mut self.transforms;
+        let transform_frames = &self.transform_frames;
+        self.objects.retain(|id, object| {
+            let keep = !update.should_delete(id, &object.namespace);
+            if !keep && !transform_frames.contains_key(id) {
+                transforms.remove_frame(id);
+            }
+            keep
+        });
+        retain_undeleted(&mut self.point_clouds, update, |point_cloud| {
+            &point_cloud.namespace
+        });
+        retain_undeleted(&mut self.point_clouds_3d, update, |point_cloud| {
+            &point_cloud.namespace
+        });
+        retain_undeleted(&mut self.laser_scans, update, |laser_scan| {
+            &laser_scan.namespace
+        });
+        retain_undeleted(&mut self.paths, update, |path| path.path.namespace());
+        retain_undeleted(&mut self.occupancy_grids, update, |_| "");
+    }
+
+    fn update_point_clouds(&mut self, point_cloud: PointCloud2, window: &mut Window) {
+        let mut container = PointCloudContainer::from_point_cloud_2(point_cloud, self.now);
+        container.build_node(&self.settings.point_render, window);
+        self.point_clouds
+            .insert(container.id().to_owned(), container);
     }
 
-    fn delete_object(&mut self, id: &str) {
-        if let Some(mut object) = self.objects.remove(id) {
-            if let Some(scene_node) = &mut object.node {
-                scene_node.unlink();
+    fn update_point_clouds_3d(&mut self, point_cloud: PointCloud3, window: &mut Window) {
+        let mut container = PointCloudContainer::from_point_cloud_3(point_cloud, self.now);
+        container.build_node(&self.settings.point_render, window);
+        self.point_clouds_3d
+            .insert(container.id().to_owned(), container);
+    }
+
+    fn update_laser_scans(&mut self, laser_scan: LaserScan, window: &mut Window) {
+        let mut container = PointCloudContainer::from_laser_scan(laser_scan, self.now);
+        container.build_node(&self.settings.point_render, window);
+        self.laser_scans
+            .insert(container.id().to_owned(), container);
+    }
+
+    fn toggle_laser_rays(&mut self) {
+        self.settings.laser_rays = !self.settings.laser_rays;
+    }
+
+    fn update_occupancy_grids(&mut self, message: OccupancyGridMessage, window: &mut Window) {
+        match message {
+            OccupancyGridMessage::Info(info) => {
+                let id = info.id().to_owned();
+                let result = if let Some(grid) = self.occupancy_grids.get_mut(&id) {
+                    grid.update_info(info, self.now, window)
+                } else {
+                    OccupancyGridContainer::new(info, self.now, window).map(|grid| {
+                        self.occupancy_grids.insert(id.clone(), grid);
+                    })
+                };
+                if let Err(error) = result {
+                    eprintln!("Dropped occupancy grid {} {}", id, error);
+                }
             }
+            OccupancyGridMessage::Patch(patch) => {
+                // patches for grids we have no info for yet are dropped
+                if let Some(grid) = self.occupancy_grids.get_mut(patch.id()) {
+                    if let Err(error) = grid.apply_patch(&patch, self.now) {
+                        eprintln!("Dropped patch of occupancy grid {} {}", patch.id(), error);
+                    }
+                }
+            }
+        }
+    }
+
+    fn cycle_grid_color_scheme(&mut self) {
+        self.settings.grid_color_scheme = self.settings.grid_color_scheme.next();
+        for grid in self.occupancy_grids.values_mut() {
+            grid.invalidate_texture();
         }
     }
 
-    fn update_point_clouds(&mut self, point_cloud: PointCloud2) {
-        self.point_clouds.insert(
-            point_cloud.id().to_owned(),
-            PointCloudContainer::new(point_cloud),
+    fn scale_point_size(&mut self, factor: f32, window: &mut Window) {
+        self.settings.point_render.scale *= factor;
+        for point_cloud in self
+            .point_clouds
+            .values_mut()
+            .chain(self.point_clouds_3d.values_mut())
+            .chain(self.laser_scans.values_mut())
+        {
+            point_cloud.build_node(&self.settings.point_render, window);
+        }
+    }
+
+    fn update_paths(&mut self, path: Path, window: &mut Window) {
+        self.paths.insert(
+            path.id().to_owned(),
+            PathContainer::new(path, self.now, window),
         );
     }
 
+    fn update_heartbeat(&mut self, heartbeat: Heartbeat) {
+        self.publishers.record(&heartbeat, self.now);
+    }
+
     fn remove_timed_out(&mut self) {
-        self.objects.retain(|_, node| !node.is_timed_out());
+        let now = self.now;
+        let publishers = &self.publishers;
+        let transforms = &mut self.transforms;
+        let transform_frames = &self.transform_frames;
+        self.objects.retain(|id, node| {
+            let keep = !node.is_timed_out(publishers, now);
+            if !keep && !transform_frames.contains_key(id) {
+                transforms.remove_frame(id);
+            }
+            keep
+        });
         self.point_clouds
-            .retain(|_, point_cloud| !point_cloud.is_timed_out());
+            .retain(|_, point_cloud| !point_cloud.is_timed_out(publishers, now));
+        self.point_clouds_3d
+            .retain(|_, point_cloud| !point_cloud.is_timed_out(publishers, now));
+        self.laser_scans
+            .retain(|_, laser_scan| !laser_scan.is_timed_out(publishers, now));
+        self.paths
+            .retain(|_, path| !path.is_timed_out(publishers, now));
+        self.occupancy_grids
+            .retain(|_, grid| !grid.is_timed_out(publishers, now));
+        self.publishers.remove_gone(now);
+        // expired by arrival so that publishers with skewed clocks or replayed stamps
+        // keep their frames. Static frames are kept until replaced
+        let transforms = &mut self.transforms;
+        self.transform_frames.retain(|id, received| {
+            let keep = transforms.latest_stamp(id).is_none()
+                || now.saturating_duration_since(*received) < TRANSFORM_TIMEOUT;
+            if !keep {
+                transforms.remove_frame(id);
+            }
+            keep
+        });
     }
 
     fn display_message(&self) -> String {
         let mut text_buffer = String::new();
         for (id, object) in &self.objects {
+            let id = if object.namespace.is_empty() {
+                id.to_owned()
+            } else {
+                format!("{}/{}", object.namespace, id)
+            };
+            let frame = match &object.parent_frame_id {
+                Some(parent) => match self.resolve_frame(parent, None) {
+                    Err(TransformError::Cycle(_)) => format!(" in {} (frame cycle)", parent),
+                    _ => format!(" in {}", parent),
+                },
+                None => String::new(),
+            };
             text_buffer.push_str(&format!(
-                "{}: {} [{:.2} {:.2} {:.2}] \n",
+                "{}: {} [{:.2} {:.2} {:.2}]{} \n",
                 id,
                 object.last_color.name(),
                 object.last_pose.0,
                 object.last_pose.1,
                 object.last_pose.2,
+                frame,
             ));
         }
-        for (id, point_cloud) in &self.point_clouds {
+        for (id, point_cloud) in self
+            .point_clouds
+            .iter()
+            .chain(&self.point_clouds_3d)
+            .chain(&self.laser_scans)
+        {
             let parent_frame_id = point_cloud
-                .point_cloud()
                 .parent_frame_id()
                 .clone()
                 .unwrap_or_else(|| "N/A".to_owned());
@@ -107,98 +486,459 @@ impl ObjectContainer {
                 "{}: {} len {} \n",
                 id,
                 parent_frame_id,
-                point_cloud.point_cloud().points().len()
+                point_cloud.points().len()
+            ));
+        }
+        for (id, path) in &self.paths {
+            let parent_frame_id = path
+                .path()
+                .parent_frame_id()
+                .clone()
+                .unwrap_or_else(|| "N/A".to_owned());
+            text_buffer.push_str(&format!(
+                "{}: {} path len {} \n",
+                id,
+                parent_frame_id,
+                path.path().points().len()
+            ));
+        }
+        for (id, grid) in &self.occupancy_grids {
+            let info = grid.grid().info();
+            let parent_frame_id = info
+                .parent_frame_id()
+                .clone()
+                .unwrap_or_else(|| "N/A".to_owned());
+            text_buffer.push_str(&format!(
+                "{}: {} grid {}x{} \n",
+                id,
+                parent_frame_id,
+                info.width(),
+                info.height()
             ));
         }
+        for (publisher_id, since_heartbeat) in self.publishers.publishers(self.now) {
+            text_buffer.push_str(&format!(
+                "{}: heartbeat {:.1}s ago \n",
+                publisher_id, since_heartbeat
+            ));
+        }
+        text_buffer
+    }
+
+    /// Move scene nodes of all objects into place along their parent frames
+    fn place_objects(&mut self) {
+        let transforms: Vec<_> = self
+            .objects
+            .iter()
+            .map(|(id, object)| {
+                (
+                    id.clone(),
+                    self.parent_transform(&object.parent_frame_id, object.stamp),
+                )
+            })
+            .collect();
+        for (id, parent_transform) in transforms {
+            if let Some(object) = self.objects.get_mut(&id) {
+                object.place(&parent_transform);
+            }
+        }
+    }
+
+    fn toggle_frame_tree(&mut self) {
+        self.settings.frame_tree = !self.settings.frame_tree;
+    }
+
+    /// Indented tree of all known frames with seconds since their last update
+    fn frame_tree_message(&self) -> String {
+        let frames = self.transforms.frames();
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (child, parent) in &frames {
+            children.entry(*parent).or_default().push(*child);
+        }
+        for frame_children in children.values_mut() {
+            frame_children.sort_unstable();
+        }
+        let mut roots: Vec<&str> = children
+            .keys()
+            .copied()
+            .filter(|frame_id| self.transforms.parent(frame_id).is_none())
+            .collect();
+        roots.sort_unstable();
+        let now = timestamp_now();
+        let mut text_buffer = String::from("frames:\n");
+        let mut visited = HashSet::new();
+        let mut stack: Vec<(&str, usize)> = roots.into_iter().rev().map(|root| (root, 0)).collect();
+        while let Some((frame_id, depth)) = stack.pop() {
+            if !visited.insert(frame_id) {
+                continue;
+            }
+            let age = self
+                .transforms
+                .latest_stamp(frame_id)
+                .map(|stamp| format!(" {:.1}s", now - stamp))
+                .unwrap_or_default();
+            text_buffer.push_str(&format!("{}{}{} \n", "  ".repeat(depth), frame_id, age));
+            if let Some(frame_children) = children.get(frame_id) {
+                stack.extend(frame_children.iter().rev().map(|child| (*child, depth + 1)));
+            }
+        }
+        // frames in a cycle are not reachable from any root
+        let mut cyclic: Vec<&str> = frames
+            .iter()
+            .map(|(child, _)| *child)
+            .filter(|frame_id| !visited.contains(frame_id))
+            .collect();
+        cyclic.sort_unstable();
+        for frame_id in cyclic {
+            text_buffer.push_str(&format!("{} (cycle) \n", frame_id));
+        }
         text_buffer
     }
 
     fn draw_lines(&self, window: &mut Window) {
         for object in self.objects.values() {
             if let Shape::Line(end) = object.current_shape {
+                let parent_transform = self.parent_transform(&object.parent_frame_id, object.stamp);
                 let rgb = object.last_color.to_rgb();
                 window.draw_line(
-                    &convert_coordinate_system(object.last_pose).into(),
-                    &convert_coordinate_system(end).into(),
+                    &(parent_transform
+                        * na::Point3::from(convert_coordinate_system(object.last_pose))),
+                    &(parent_transform * na::Point3::from(convert_coordinate_system(end))),
                     &na::Point3::new(rgb.0, rgb.1, rgb.2),
                 );
             }
         }
     }
 
-    fn draw_point_clouds(&self, window: &mut Window) {
-        for point_cloud in self.point_clouds.values() {
-            let (root_point, root_rotation) =
-                if let Some(parent_frame_id) = point_cloud.point_cloud().parent_frame_id() {
-                    self.objects
-                        .get(parent_frame_id)
-                        .map(|node| (node.last_pose, node.last_rotation))
-                        .unwrap_or_else(|| ((0., 0., 0.01), (0., 0., 0., 1.)))
+    fn toggle_axes(&mut self) {
+        self.settings.axes = !self.settings.axes;
+    }
+
+    fn draw_axes(&self, window: &mut Window) {
+        for object in self.objects.values() {
+            let length = match object.current_shape {
+                Shape::Axes(length) => length,
+                _ if self.settings.axes => DEFAULT_AXES_LENGTH,
+                _ => continue,
+            };
+            let transform = self.parent_transform(&object.parent_frame_id, object.stamp)
+                * object.local_transform();
+            let origin = na::Point3::from(transform.translation.vector);
+            let rotation = transform.rotation;
+            let (scale_x, scale_y, scale_z) = object.last_scale;
+            for (direction, color) in &[
+                ((length * scale_x, 0., 0.), na::Point3::new(1., 0., 0.)),
+                ((0., length * scale_y, 0.), na::Point3::new(0., 1., 0.)),
+                ((0., 0., length * scale_z), na::Point3::new(0., 0., 1.)),
+            ] {
+                draw_arrow(&origin, &rotation, *direction, color, window);
+            }
+        }
+    }
+
+    /// Filled translucent with opaque outlines
+    fn draw_covariances(&self, window: &mut Window, translucent: &mut TranslucentRenderer) {
+        for object in self.objects.values() {
+            if let Some(covariance) = &object.last_covariance {
+                let rgb = object.last_color.to_rgb();
+                let color = na::Point3::new(rgb.0, rgb.1, rgb.2);
+                let (x, y, z) = object.last_pose;
+                let center = na::Vector3::new(x, y, z);
+                let parent_transform = self.parent_transform(&object.parent_frame_id, object.stamp);
+                draw_covariance_ellipsoid(
+                    &parent_transform,
+                    &center,
+                    covariance,
+                    self.settings.covariance_sigma,
+                    &color,
+                    window,
+                    translucent,
+                );
+                let (_, _, yaw) = na::UnitQuaternion::new_normalize(na::Quaternion::new(
+                    object.last_rotation.3,
+                    object.last_rotation.0,
+                    object.last_rotation.1,
+                    object.last_rotation.2,
+                ))
+                .euler_angles();
+                if let Some(yaw_variance) = covariance.yaw_variance() {
+                    draw_yaw_wedge(
+                        &parent_transform,
+                        &center,
+                        yaw,
+                        yaw_variance.max(0.0).sqrt() * self.settings.covariance_sigma,
+                        &color,
+                        window,
+                        translucent,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Transform from `frame_id` to the fixed frame in viewer coordinates
+    ///
+    /// Trees not connected to the fixed frame are drawn with their root at the fixed frame
+    ///
+    /// Frames are looked up at `stamp` if transforms for that time are buffered and latest otherwise
+    fn resolve_frame(
+        &self,
+        frame_id: &str,
+        stamp: Option<f64>,
+    ) -> Result<na::Isometry3<f32>, TransformError> {
+        let lookup = |time: Option<f64>| match self.transforms.lookup_transform(
+            &self.settings.fixed_frame,
+            frame_id,
+            time,
+        ) {
+            Err(TransformError::NotConnected(_, _)) | Err(TransformError::UnknownFrame(_))
+                if self.transforms.has_frame(frame_id) =>
+            {
+                self.transforms.transform_to_root(frame_id, time)
+            }
+            transform => transform,
+        };
+        let transform = match lookup(stamp) {
+            Err(TransformError::Extrapolation(_, _)) if stamp.is_some() => lookup(None),
+            transform => transform,
+        }?;
+        Ok(na::Isometry3::from_parts(
+            na::Translation3::from(convert_coordinate_system(transform.translation())),
+            convert_rotation_coordinate_system(transform.rotation()),
+        ))
+    }
+
+    /// Transform of the frame objects are expressed in. Falls back to world on errors
+    fn parent_transform(
+        &self,
+        parent_frame_id: &Option<String>,
+        stamp: Option<f64>,
+    ) -> na::Isometry3<f32> {
+        parent_frame_id
+            .as_deref()
+            .and_then(|frame_id| self.resolve_frame(frame_id, stamp).ok())
+            .unwrap_or_else(na::Isometry3::identity)
+    }
+
+    /// Transform of the frame clouds, paths and grids are expressed in
+    ///
+    /// Entities without a known parent are lifted slightly above the ground plane
+    fn frame_transform(
+        &self,
+        parent_frame_id: &Option<String>,
+        stamp: Option<f64>,
+    ) -> na::Isometry3<f32> {
+        parent_frame_id
+            .as_deref()
+            .and_then(|frame_id| self.resolve_frame(frame_id, stamp).ok())
+            .unwrap_or_else(|| {
+                na::Isometry3::from_parts(
+                    na::Translation3::from(convert_coordinate_system((0., 0., 0.01))),
+                    na::UnitQuaternion::identity(),
+                )
+            })
+    }
+
+    fn draw_point_clouds(&mut self, window: &mut Window, points: &mut PointBatches) {
+        let clouds = self
+            .point_clouds
+            .iter()
+            .map(|entry| (PointSource::Cloud, entry));
+        let clouds_3d = self
+            .point_clouds_3d
+            .iter()
+            .map(|entry| (PointSource::Cloud3d, entry));
+        let scans = self
+            .laser_scans
+            .iter()
+            .map(|entry| (PointSource::LaserScan, entry));
+        let transforms: Vec<_> = clouds
+            .chain(clouds_3d)
+            .chain(scans)
+            .map(|(source, (id, point_cloud))| {
+                (
+                    source,
+                    id.clone(),
+                    self.frame_transform(point_cloud.parent_frame_id(), point_cloud.stamp),
+                )
+            })
+            .collect();
+        for (source, id, root_translation) in transforms {
+            let point_cloud = match source {
+                PointSource::Cloud => self.point_clouds.get_mut(&id),
+                PointSource::Cloud3d => self.point_clouds_3d.get_mut(&id),
+                PointSource::LaserScan => self.laser_scans.get_mut(&id),
+            };
+            if let Some(point_cloud) = point_cloud {
+                if self.settings.laser_rays && point_cloud.rays {
+                    let origin = root_translation * na::Point3::origin();
+                    for (point, color) in point_cloud.points() {
+                        window.draw_line(&origin, &(root_translation * point), &(color * 0.5));
+                    }
+                }
+                if let Some(node) = &mut point_cloud.node {
+                    node.set_local_transformation(root_translation);
                 } else {
-                    ((0., 0., 0.01), (0., 0., 0., 1.))
-                };
-            let rgb = point_cloud.point_cloud().color().to_rgb();
-            let color = na::Point3::new(rgb.0, rgb.1, rgb.2);
-            let root_translation = na::Isometry3::from_parts(
-                na::Translation3::from(convert_coordinate_system(root_point)),
-                convert_rotation_coordinate_system(root_rotation),
-            );
-            for point in point_cloud.point_cloud().points() {
-                let point3 = root_translation
-                    * na::Point3::from(convert_coordinate_system((point.0, point.1, 0.0)));
-                window.draw_point(&point3, &color);
+                    let size = self.settings.point_render.size(point_cloud.point_size);
+                    for (point, color) in point_cloud.points() {
+                        points.draw_point(size, &(root_translation * point), color);
+                    }
+                }
             }
         }
     }
+
+    fn draw_occupancy_grids(&mut self) {
+        let transforms: Vec<_> = self
+            .occupancy_grids
+            .iter()
+            .map(|(id, grid)| {
+                (
+                    id.clone(),
+                    self.frame_transform(
+                        grid.grid().info().parent_frame_id(),
+                        header_stamp(grid.grid().info().header()),
+                    ),
+                )
+            })
+            .collect();
+        for (id, root_translation) in transforms {
+            if let Some(grid) = self.occupancy_grids.get_mut(&id) {
+                grid.draw(&root_translation, self.settings.grid_color_scheme);
+            }
+        }
+    }
+
+    fn draw_paths(&mut self, window: &mut Window) {
+        let transforms: Vec<_> = self
+            .paths
+            .iter()
+            .map(|(id, path)| {
+                (
+                    id.clone(),
+                    self.frame_transform(
+                        path.path().parent_frame_id(),
+                        header_stamp(path.path().header()),
+                    ),
+                )
+            })
+            .collect();
+        for (id, root_translation) in transforms {
+            if let Some(path) = self.paths.get_mut(&id) {
+                path.draw(&root_translation, window);
+            }
+        }
+    }
+}
+
+/// Scale of `shape` relative to `base` if the node built for `base` can be reused
+fn shape_scale(base: Shape, shape: Shape) -> Option<(f32, f32, f32)> {
+    match (base, shape) {
+        (Shape::Sphere(base_radius), Shape::Sphere(radius)) if base_radius > 0.0 => {
+            let scale = radius / base_radius;
+            Some((scale, scale, scale))
+        }
+        (Shape::Cube(base_x, base_y, base_z), Shape::Cube(x, y, z))
+            if base_x > 0.0 && base_y > 0.0 && base_z > 0.0 =>
+        {
+            Some((x / base_x, y / base_y, z / base_z))
+        }
+        (Shape::Line(_), Shape::Line(_)) | (Shape::Axes(_), Shape::Axes(_)) => Some((1., 1., 1.)),
+        _ => None,
+    }
 }
 
 struct VisualizerObject {
+    namespace: String,
+    parent_frame_id: Option<String>,
+    /// Capture time of the last update
+    stamp: Option<f64>,
     node: Option<SceneNode>,
+    /// Shape the scene node was created with
+    node_shape: Shape,
     current_shape: Shape,
-    last_update: Instant,
-    timeout: Duration,
+    expiry: Expiry,
     last_pose: (f32, f32, f32),
     last_rotation: (f32, f32, f32, f32),
     last_color: Color,
+    last_covariance: Option<Covariance>,
+    last_scale: (f32, f32, f32),
 }
 
 impl VisualizerObject {
-    fn new(object_info: &ObjectPose, window: &mut Window) -> Self {
+    fn new(object_info: &ObjectPose, header: &Header, now: Instant, window: &mut Window) -> Self {
         let scene_node = attach_node_type(object_info.shape, window);
         let mut object = Self {
+            namespace: object_info.namespace.clone(),
+            parent_frame_id: object_info
+                .parent_frame_id
+                .clone()
+                .or_else(|| header.frame_id.clone()),
+            stamp: header_stamp(header),
             node: scene_node,
-            timeout: Duration::from_secs_f32(object_info.timeout),
-            last_update: Instant::now(),
+            node_shape: object_info.shape,
+            expiry: Expiry::new(object_info.effective_lifetime(), now),
             current_shape: object_info.shape,
             last_pose: object_info.pose,
             last_rotation: object_info.rotation,
             last_color: object_info.color,
+            last_covariance: object_info.covariance.clone(),
+            last_scale: object_info.scale.unwrap_or((1., 1., 1.)),
         };
-        object.update_pose(object_info.pose);
-        object.update_color(object_info.color);
+        object.apply_to_node();
         object
     }
 
-    fn update(&mut self, update: &ObjectPose, window: &mut Window) {
-        self.touch();
-        self.update_pose(update.pose);
-        self.update_rotation(update.rotation);
+    fn update(&mut self, update: &ObjectPose, header: &Header, now: Instant, window: &mut Window) {
+        self.expiry.touch(update.effective_lifetime(), now);
+        self.namespace = update.namespace.clone();
+        self.parent_frame_id = update
+            .parent_frame_id
+            .clone()
+            .or_else(|| header.frame_id.clone());
+        self.stamp = header_stamp(header);
+        self.last_covariance = update.covariance.clone();
+        self.last_pose = update.pose;
+        self.last_rotation = update.rotation;
+        self.update_shape(update.shape, window);
         self.update_color(update.color);
-        self.timeout = Duration::from_secs_f32(update.timeout);
-        self.update_shape(update.shape, window)
+        self.update_scale(update.scale.unwrap_or((1., 1., 1.)));
+    }
+
+    /// Push all cached state to a freshly created scene node
+    ///
+    /// Node transform is set every frame by [`VisualizerObject::place`]
+    fn apply_to_node(&mut self) {
+        self.update_color(self.last_color);
+        self.update_scale(self.last_scale);
     }
 
-    fn update_pose(&mut self, pose: (f32, f32, f32)) {
-        self.last_pose = pose;
+    /// Object scale combined with size change of the shape since the node was created
+    fn update_scale(&mut self, scale: (f32, f32, f32)) {
+        self.last_scale = scale;
+        let shape_scale = shape_scale(self.node_shape, self.current_shape).unwrap_or((1., 1., 1.));
+        let total_scale = convert_coordinate_system((
+            scale.0 * shape_scale.0,
+            scale.1 * shape_scale.1,
+            scale.2 * shape_scale.2,
+        ));
         if let Some(node) = &mut self.node {
-            node.set_local_translation(na::Translation3::from(convert_coordinate_system(pose)));
+            node.set_local_scale(total_scale.x, total_scale.y, total_scale.z);
         }
     }
 
-    fn update_rotation(&mut self, rotation: (f32, f32, f32, f32)) {
-        self.last_rotation = rotation;
+    /// Pose relative to the parent frame
+    fn local_transform(&self) -> na::Isometry3<f32> {
+        na::Isometry3::from_parts(
+            na::Translation3::from(convert_coordinate_system(self.last_pose)),
+            convert_rotation_coordinate_system(self.last_rotation),
+        )
+    }
+
+    fn place(&mut self, parent_transform: &na::Isometry3<f32>) {
+        let transform = parent_transform * self.local_transform();
         if let Some(node) = &mut self.node {
-            node.set_local_rotation(convert_rotation_coordinate_system(rotation));
+            node.set_local_transformation(transform);
         }
     }
 
@@ -210,22 +950,24 @@ impl VisualizerObject {
         }
     }
 
+    /// Resizing a shape only rescales the node. Node is recreated if the kind of shape changes
     fn update_shape(&mut self, shape: Shape, window: &mut Window) {
-        if self.current_shape != shape {
-            self.current_shape = shape;
+        if self.current_shape == shape {
+            return;
+        }
+        self.current_shape = shape;
+        if shape_scale(self.node_shape, shape).is_none() {
             if let Some(scene_node) = &mut self.node {
                 scene_node.unlink()
             }
             self.node = attach_node_type(shape, window);
+            self.node_shape = shape;
+            self.apply_to_node();
         }
     }
 
-    fn touch(&mut self) {
-        self.last_update = Instant::now();
-    }
-
-    fn is_timed_out(&self) -> bool {
-        self.last_update.elapsed() > self.timeout
+    fn is_timed_out(&self, publishers: &PublisherTracker, now: Instant) -> bool {
+        self.expiry.is_expired(publishers, now)
     }
 }
 
@@ -237,28 +979,328 @@ impl Drop for VisualizerObject {
     }
 }
 
+/// Point cloud resolved into viewer coordinates and colors
 struct PointCloudContainer {
-    point_cloud: PointCloud2,
-    last_touched: Instant,
+    id: String,
+    namespace: String,
+    parent_frame_id: Option<String>,
+    /// Capture time used to look up the parent frame
+    stamp: Option<f64>,
+    points: Vec<(na::Point3<f32>, na::Point3<f32>)>,
+    point_size: f32,
+    style: PointStyle,
+    node: Option<SceneNode>,
+    /// Draw rays from frame origin to points
+    rays: bool,
+    expiry: Expiry,
 }
 
 impl PointCloudContainer {
-    fn new(point_cloud: PointCloud2) -> Self {
+    fn from_point_cloud_2(point_cloud: PointCloud2, now: Instant) -> Self {
+        let rgb = point_cloud.color().to_rgb();
+        let color = na::Point3::new(rgb.0, rgb.1, rgb.2);
+        let points = point_cloud
+            .points()
+            .iter()
+            .map(|point| {
+                (
+                    na::Point3::from(convert_coordinate_system((point.0, point.1, 0.0))),
+                    color,
+                )
+            })
+            .collect();
         Self {
-            point_cloud,
-            last_touched: Instant::now(),
+            id: point_cloud.id().to_owned(),
+            namespace: point_cloud.namespace().to_owned(),
+            parent_frame_id: point_cloud.parent_frame_id().clone(),
+            stamp: header_stamp(point_cloud.header()),
+            points,
+            point_size: point_cloud.point_size(),
+            style: *point_cloud.style(),
+            node: None,
+            rays: false,
+            expiry: Expiry::new(point_cloud.lifetime().clone(), now),
         }
     }
 
-    fn point_cloud(&self) -> &PointCloud2 {
-        &self.point_cloud
+    fn from_point_cloud_3(point_cloud: PointCloud3, now: Instant) -> Self {
+        let points = point_cloud
+            .points()
+            .iter()
+            .zip(point_cloud.point_colors())
+            .map(|(point, rgb)| {
+                (
+                    na::Point3::from(convert_coordinate_system(*point)),
+                    na::Point3::new(rgb.0, rgb.1, rgb.2),
+                )
+            })
+            .collect();
+        Self {
+            id: point_cloud.id().to_owned(),
+            namespace: point_cloud.namespace().to_owned(),
+            parent_frame_id: point_cloud.parent_frame_id().clone(),
+            stamp: header_stamp(point_cloud.header()),
+            points,
+            point_size: point_cloud.point_size(),
+            style: *point_cloud.style(),
+            node: None,
+            rays: false,
+            expiry: Expiry::new(point_cloud.lifetime().clone(), now),
+        }
     }
 
-    fn is_timed_out(&self) -> bool {
-        self.last_touched.elapsed() > Duration::from_secs_f32(self.point_cloud.timeout())
+    fn from_laser_scan(laser_scan: LaserScan, now: Instant) -> Self {
+        let mut container = Self::from_point_cloud_3(laser_scan.to_point_cloud(), now);
+        container.rays = true;
+        container
+    }
+
+    /// (Re)build scene node for styles that are not drawn as plain points
+    fn build_node(&mut self, settings: &PointRenderSettings, window: &mut Window) {
+        if let Some(node) = &mut self.node {
+            node.unlink();
+        }
+        self.node = point_cloud_mesh::build_point_cloud_node(
+            &self.points,
+            settings.style(self.style),
+            settings.size(self.point_size),
+            window,
+        );
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn parent_frame_id(&self) -> &Option<String> {
+        &self.parent_frame_id
+    }
+
+    fn points(&self) -> &Vec<(na::Point3<f32>, na::Point3<f32>)> {
+        &self.points
+    }
+
+    fn is_timed_out(&self, publishers: &PublisherTracker, now: Instant) -> bool {
+        self.expiry.is_expired(publishers, now)
+    }
+}
+
+impl Drop for PointCloudContainer {
+    fn drop(&mut self) {
+        if let Some(scene_node) = &mut self.node {
+            scene_node.unlink()
+        }
+    }
+}
+
+struct PathContainer {
+    path: Path,
+    ribbon: Option<SceneNode>,
+    expiry: Expiry,
+}
+
+impl PathContainer {
+    fn new(path: Path, now: Instant, window: &mut Window) -> Self {
+        let ribbon = build_path_ribbon(&path, window);
+        let expiry = Expiry::new(path.lifetime().clone(), now);
+        Self {
+            path,
+            ribbon,
+            expiry,
+        }
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn draw(&mut self, root_translation: &na::Isometry3<f32>, window: &mut Window) {
+        if let Some(ribbon) = &mut self.ribbon {
+            ribbon.set_local_transformation(*root_translation);
+        }
+        let rgb = self.path.color().to_rgb();
+        let color = na::Point3::new(rgb.0, rgb.1, rgb.2);
+        let points: Vec<_> = self
+            .path
+            .points()
+            .iter()
+            .map(|point| {
+                root_translation * na::Point3::from(convert_coordinate_system(point.position()))
+            })
+            .collect();
+        for segment in points.windows(2) {
+            window.draw_line(&segment[0], &segment[1], &color);
+        }
+        for (path_point, point) in self.path.points().iter().zip(&points) {
+            if let Some(orientation) = path_point.orientation() {
+                let rotation =
+                    root_translation.rotation * convert_rotation_coordinate_system(orientation);
+                draw_arrow(
+                    point,
+                    &rotation,
+                    (PATH_ARROW_LENGTH, 0., 0.),
+                    &color,
+                    window,
+                );
+            }
+        }
+    }
+
+    fn is_timed_out(&self, publishers: &PublisherTracker, now: Instant) -> bool {
+        self.expiry.is_expired(publishers, now)
     }
 }
 
+impl Drop for PathContainer {
+    fn drop(&mut self) {
+        if let Some(scene_node) = &mut self.ribbon {
+            scene_node.unlink()
+        }
+    }
+}
+
+/// Flat strip along the path in its parent frame. Paths without width are drawn as lines only
+fn build_path_ribbon(path: &Path, window: &mut Window) -> Option<SceneNode> {
+    let points: Vec<_> = path
+        .points()
+        .iter()
+        .map(|point| {
+            let (x, y, z) = point.position();
+            na::Vector3::new(x, y, z)
+        })
+        .collect();
+    if path.width() <= 0.0 || points.len() < 2 || points.len() * 2 > u16::MAX as usize {
+        return None;
+    }
+    let half_width = path.width() / 2.0;
+    let mut coords = vec![];
+    for (i, point) in points.iter().enumerate() {
+        let previous = points[i.saturating_sub(1)];
+        let next = points[(i + 1).min(points.len() - 1)];
+        let side = (next - previous)
+            .cross(&na::Vector3::z())
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(na::Vector3::y);
+        for edge in &[point + side * half_width, point - side * half_width] {
+            coords.push(na::Point3::from(convert_coordinate_system((
+                edge.x, edge.y, edge.z,
+            ))));
+        }
+    }
+    let mut faces = vec![];
+    for i in 0..points.len() as u16 - 1 {
+        let base = i * 2;
+        faces.push(na::Point3::new(base, base + 1, base + 2));
+        faces.push(na::Point3::new(base + 1, base + 3, base + 2));
+    }
+    let mesh = Mesh::new(coords, faces, None, None, false);
+    let mut node = window.add_mesh(Rc::new(RefCell::new(mesh)), na::Vector3::new(1.0, 1.0, 1.0));
+    let rgb = path.color().to_rgb();
+    node.set_color(rgb.0, rgb.1, rgb.2);
+    node.enable_backface_culling(false);
+    Some(node)
+}
+
+/// Ellipses in the principal planes of the position covariance
+fn draw_covariance_ellipsoid(
+    frame: &na::Isometry3<f32>,
+    center: &na::Vector3<f32>,
+    covariance: &Covariance,
+    sigma: f32,
+    color: &na::Point3<f32>,
+    window: &mut Window,
+    translucent: &mut TranslucentRenderer,
+) {
+    let position = match covariance.position() {
+        Some(position) => position,
+        None => return,
+    };
+    let eigen = na::Matrix3::from_row_slice(&position).symmetric_eigen();
+    let axes: Vec<na::Vector3<f32>> = (0..3)
+        .map(|i| eigen.eigenvectors.column(i) * eigen.eigenvalues[i].max(0.0).sqrt() * sigma)
+        .collect();
+    for (a, b) in &[(0, 1), (1, 2), (0, 2)] {
+        let (major, minor) = (axes[*a], axes[*b]);
+        // degenerate axes, for example z of planar covariance
+        if major.norm() < f32::EPSILON || minor.norm() < f32::EPSILON {
+            continue;
+        }
+        let ellipse_point = |i: usize| {
+            let angle = i as f32 / COVARIANCE_ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+            let point = center + major * angle.cos() + minor * angle.sin();
+            frame * na::Point3::from(convert_coordinate_system((point.x, point.y, point.z)))
+        };
+        let center =
+            frame * na::Point3::from(convert_coordinate_system((center.x, center.y, center.z)));
+        for i in 0..COVARIANCE_ELLIPSE_SEGMENTS {
+            let (start, end) = (ellipse_point(i), ellipse_point(i + 1));
+            window.draw_line(&start, &end, color);
+            translucent.draw_triangle([&center, &start, &end], color);
+        }
+    }
+}
+
+/// Arc spanning yaw +- yaw_deviation in the ground plane
+fn draw_yaw_wedge(
+    frame: &na::Isometry3<f32>,
+    center: &na::Vector3<f32>,
+    yaw: f32,
+    yaw_deviation: f32,
+    color: &na::Point3<f32>,
+    window: &mut Window,
+    translucent: &mut TranslucentRenderer,
+) {
+    let yaw_deviation = yaw_deviation.min(std::f32::consts::PI);
+    let wedge_point = |angle: f32| {
+        frame
+            * na::Point3::from(convert_coordinate_system((
+                center.x + angle.cos() * COVARIANCE_WEDGE_RADIUS,
+                center.y + angle.sin() * COVARIANCE_WEDGE_RADIUS,
+                center.z,
+            )))
+    };
+    let origin =
+        frame * na::Point3::from(convert_coordinate_system((center.x, center.y, center.z)));
+    let start = yaw - yaw_deviation;
+    let step = yaw_deviation * 2.0 / COVARIANCE_ELLIPSE_SEGMENTS as f32;
+    window.draw_line(&origin, &wedge_point(start), color);
+    window.draw_line(&origin, &wedge_point(yaw + yaw_deviation), color);
+    for i in 0..COVARIANCE_ELLIPSE_SEGMENTS {
+        let arc_start = wedge_point(start + step * i as f32);
+        let arc_end = wedge_point(start + step * (i + 1) as f32);
+        window.draw_line(&arc_start, &arc_end, color);
+        translucent.draw_triangle([&origin, &arc_start, &arc_end], color);
+    }
+}
+
+/// Arrow along `direction` given in the local frame of `rotation`
+fn draw_arrow(
+    origin: &na::Point3<f32>,
+    rotation: &na::UnitQuaternion<f32>,
+    direction: (f32, f32, f32),
+    color: &na::Point3<f32>,
+    window: &mut Window,
+) {
+    let local_direction = na::Vector3::new(direction.0, direction.1, direction.2);
+    let reference = if local_direction.z.abs() < local_direction.norm() * 0.9 {
+        na::Vector3::z()
+    } else {
+        na::Vector3::x()
+    };
+    let local_side = local_direction
+        .cross(&reference)
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(na::Vector3::y)
+        * local_direction.norm()
+        * 0.3;
+    let forward = rotation * convert_coordinate_system(direction);
+    let side = rotation * convert_coordinate_system((local_side.x, local_side.y, local_side.z));
+    let tip = origin + forward;
+    window.draw_line(origin, &tip, color);
+    window.draw_line(&tip, &(tip - forward * 0.3 + side), color);
+    window.draw_line(&tip, &(tip - forward * 0.3 - side), color);
+}
+
 #[derive(Clap)]
 #[clap()]
 struct Args {
@@ -268,14 +1310,112 @@ struct Args {
     point_cloud_address: SocketAddrV4,
     #[clap(long, default_value = "239.0.0.22:7076")]
     command_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7083")]
+    command_status_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7077")]
+    path_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7078")]
+    point_cloud_3d_address: SocketAddrV4,
+    /// Override point size of all point clouds
+    #[clap(long)]
+    point_size: Option<f32>,
+    /// Override point style of all point clouds [points, squares, cubes, spheres]
+    #[clap(long)]
+    point_style: Option<PointStyle>,
+    #[clap(long, default_value = "239.0.0.22:7079")]
+    laser_scan_address: SocketAddrV4,
+    /// Draw rays of laser scans. Toggle with R
+    #[clap(long)]
+    laser_rays: bool,
+    #[clap(long, default_value = "239.0.0.22:7080")]
+    occupancy_grid_address: SocketAddrV4,
+    /// Color scheme of occupancy grids [map, costmap]. Cycle with M
+    #[clap(long, default_value = "map")]
+    grid_color_scheme: GridColorScheme,
+    /// Number of standard deviations drawn by covariance ellipses
+    #[clap(long, default_value = "1.0")]
+    covariance_sigma: f32,
+    /// Draw coordinate axes on every object. Toggle with X
+    #[clap(long)]
+    axes: bool,
+    #[clap(long, default_value = "239.0.0.22:7081")]
+    heartbeat_address: SocketAddrV4,
+    #[clap(long, default_value = "239.0.0.22:7082")]
+    transform_address: SocketAddrV4,
+    /// Frame everything is drawn in
+    #[clap(long, default_value = "world")]
+    fixed_frame: String,
+    /// Show tree of known frames. Toggle with F
+    #[clap(long)]
+    frame_tree: bool,
+    /// Command created by right click [goal, initial_pose, point, waypoints]. Select with 1 -> 4
+    #[clap(long, default_value = "goal")]
+    command_tool: CommandTool,
+    /// Drive with WASD or arrow keys, strafe with Q and E. Toggle with V
+    #[clap(long)]
+    teleop: bool,
+    /// m/s
+    #[clap(long, default_value = "0.5")]
+    max_linear_speed: f32,
+    /// rad/s
+    #[clap(long, default_value = "1.0")]
+    max_angular_speed: f32,
+    /// Velocity commands per second while driving
+    #[clap(long, default_value = "10.0")]
+    teleop_rate: f32,
+    /// Hold data back until all topics caught up to its stamp. Toggle with T
+    #[clap(long)]
+    sync: bool,
+    /// Seconds of history kept for scrubbing. Pause with Space, scrub with [ and ]
+    #[clap(long, default_value = "30.0")]
+    history: f32,
+    /// Directory for screenshots taken with P and recordings toggled with Shift+P
+    #[clap(long, default_value = ".")]
+    capture_directory: PathBuf,
+    /// Frames per second saved while recording. Has to be positive
+    #[clap(long, default_value = "10.0")]
+    record_rate: f32,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    if args.record_rate <= 0. {
+        return Err(anyhow!("record rate has to be positive"));
+    }
     let pose_subscriber = PoseSubscriber::new(args.address).unwrap();
     let point_cloud_subscriber = PointCloudSubscriber::new(args.point_cloud_address).unwrap();
     let command_publisher = CommandPublisher::new(args.command_address).unwrap();
-    let mut object_container = ObjectContainer::new();
+    let command_status_subscriber =
+        CommandStatusSubscriber::new(args.command_status_address).unwrap();
+    let path_subscriber = PathSubscriber::new(args.path_address).unwrap();
+    let point_cloud_3d_subscriber =
+        PointCloud3Subscriber::new(args.point_cloud_3d_address).unwrap();
+    let laser_scan_subscriber = LaserScanSubscriber::new(args.laser_scan_address).unwrap();
+    let occupancy_grid_subscriber =
+        OccupancyGridSubscriber::new(args.occupancy_grid_address).unwrap();
+    let heartbeat_subscriber = HeartbeatSubscriber::new(args.heartbeat_address).unwrap();
+    let transform_subscriber = TransformSubscriber::new(args.transform_address).unwrap();
+    let mut object_container = ObjectContainer::new(ViewSettings {
+        point_render: PointRenderSettings {
+            size_override: args.point_size,
+            style_override: args.point_style,
+            scale: 1.0,
+        },
+        laser_rays: args.laser_rays,
+        grid_color_scheme: args.grid_color_scheme,
+        covariance_sigma: args.covariance_sigma,
+        axes: args.axes,
+        fixed_frame: args.fixed_frame,
+        frame_tree: args.frame_tree,
+    });
+    let mut synchronizer = TimeSynchronizer::new(args.sync);
+    let mut timeline = Timeline::new(Duration::from_secs_f32(args.history.max(0.0)));
+    // scene before the oldest message in the timeline
+    let mut snapshot = SceneSnapshot::new();
+    // scene rebuilt from history while scrubbing, live scene is hidden meanwhile
+    let mut history_container: Option<ObjectContainer> = None;
+    let mut timeline_changed = false;
+    let mut cursor_position = na::Point2::new(0.0_f32, 0.0);
     let mut window = Window::new("rustviz");
 
     window.set_background_color(0.1, 0.1, 0.1);
@@ -287,50 +1427,212 @@ fn main() -> Result<()> {
         na::Point3::new(0.0, 0.0, 0.0),
     );
     camera.set_dist_step(4.0);
+    // Enter sends waypoints
+    camera.rebind_reset_key(Some(Key::Home));
+    let mut render_state = RenderState::new(camera);
 
     let mut last_projected_point: Option<na::Point3<f32>> = None;
     let mut last_button_down_pose: Option<na::Point3<f32>> = None;
+    let mut selected_tool = args.command_tool;
+    let mut drag_tool = selected_tool;
+    let mut waypoints: Vec<na::Point3<f32>> = vec![];
+    // shared by every kind of command so that ids stay unique on the command topic
     let mut command_id = 0_u32;
     let mut click_indicator = window.add_sphere(0.02);
     click_indicator.set_color(1.0, 1.0, 0.0);
     let mut release_indicator = window.add_sphere(0.01);
     release_indicator.set_color(0.0, 1.0, 1.0);
 
-    let mut last_command = None;
+    let mut last_command: Option<PendingCommand> = None;
+    let mut teleop = Teleop::new(
+        args.teleop,
+        args.max_linear_speed,
+        args.max_angular_speed,
+        args.teleop_rate,
+    );
+    let mut capture = Capture::new(args.capture_directory, args.record_rate);
 
     while !window.should_close() {
         // process window events
         for event in window.events().iter() {
+            if let WindowEvent::Key(key, action, _) = event.value {
+                if teleop.handle_key(key, action) {
+                    continue;
+                }
+            }
             match event.value {
-                WindowEvent::MouseButton(MouseButton::Button3, Action::Press, _modif) => {
+                WindowEvent::MouseButton(MouseButton::Button3, Action::Press, modif) => {
                     if let Some(last_pose) = last_projected_point {
                         click_indicator.set_visible(true);
                         release_indicator.set_visible(false);
-                        last_button_down_pose = Some(last_pose);
                         click_indicator.set_local_translation(last_pose.into());
+                        match selected_tool.with_modifiers(modif) {
+                            CommandTool::Point => {
+                                let point = inverse_convert_coordinate_system(&last_pose.coords);
+                                last_command = Some(PendingCommand::new(
+                                    Command::clicked_point(command_id, point)
+                                        .with_frame_id(&object_container.settings.fixed_frame),
+                                    last_pose,
+                                ));
+                                command_id += 1;
+                            }
+                            CommandTool::Waypoints => waypoints.push(last_pose),
+                            tool => {
+                                drag_tool = tool;
+                                last_button_down_pose = Some(last_pose);
+                            }
+                        }
                     }
                 }
                 WindowEvent::MouseButton(MouseButton::Button3, Action::Release, _modif) => {
                     if let Some(last_pose) = last_projected_point {
-                        click_indicator.set_visible(true);
-                        release_indicator.set_visible(true);
                         if let Some(last_button_down) = &last_button_down_pose {
+                            release_indicator.set_visible(true);
+                            release_indicator.set_local_translation(last_pose.into());
                             let command = command_from_ground_plane_poses(
                                 last_button_down,
                                 &last_pose,
                                 command_id,
-                            );
-                            last_command = Some(command);
+                                drag_tool,
+                            )
+                            .with_frame_id(&object_container.settings.fixed_frame);
+                            last_command = Some(PendingCommand::new(command, *last_button_down));
                             command_id += 1;
                         }
                         last_button_down_pose = None;
-                        release_indicator.set_local_translation(last_pose.into());
+                    }
+                }
+                WindowEvent::Key(Key::Return, Action::Press, _) if !waypoints.is_empty() => {
+                    let marker = waypoints[0];
+                    let points = waypoints
+                        .drain(..)
+                        .map(|waypoint| {
+                            let (x, y, _) = inverse_convert_coordinate_system(&waypoint.coords);
+                            (x, y)
+                        })
+                        .collect();
+                    last_command = Some(PendingCommand::new(
+                        Command::waypoints(command_id, points)
+                            .with_frame_id(&object_container.settings.fixed_frame),
+                        marker,
+                    ));
+                    command_id += 1;
+                }
+                WindowEvent::Key(Key::Back, Action::Press, _) => {
+                    waypoints.clear();
+                }
+                WindowEvent::Key(key, Action::Press, _) if CommandTool::from_key(key).is_some() => {
+                    if let Some(tool) = CommandTool::from_key(key) {
+                        selected_tool = tool;
+                    }
+                }
+                WindowEvent::Key(Key::Equals, Action::Press, _)
+                | WindowEvent::Key(Key::Add, Action::Press, _) => {
+                    for scene in scenes(&mut object_container, &mut history_container) {
+                        scene.scale_point_size(POINT_SCALE_STEP, &mut window);
+                    }
+                }
+                WindowEvent::Key(Key::Minus, Action::Press, _)
+                | WindowEvent::Key(Key::Subtract, Action::Press, _) => {
+                    for scene in scenes(&mut object_container, &mut history_container) {
+                        scene.scale_point_size(1.0 / POINT_SCALE_STEP, &mut window);
+                    }
+                }
+                WindowEvent::Key(Key::R, Action::Press, _) => {
+                    for scene in scenes(&mut object_container, &mut history_container) {
+                        scene.toggle_laser_rays();
+                    }
+                }
+                WindowEvent::Key(Key::X, Action::Press, _) => {
+                    for scene in scenes(&mut object_container, &mut history_container) {
+                        scene.toggle_axes();
+                    }
+                }
+                WindowEvent::Key(Key::Space, Action::Press, _) => {
+                    if timeline.is_paused() {
+                        history_container = None;
+                        object_container.set_visible(true);
+                        match timeline.resume() {
+                            Some(messages) => {
+                                for (applied, message) in messages {
+                                    object_container.set_time(applied);
+                                    object_container.apply_message(message, &mut window);
+                                }
+                            }
+                            None => {
+                                let settings = object_container.settings.clone();
+                                // unlink nodes of the stale live scene before rebuilding it
+                                drop(object_container);
+                                object_container = ObjectContainer::from_history(
+                                    settings,
+                                    &snapshot,
+                                    &timeline,
+                                    Instant::now(),
+                                    &mut window,
+                                );
+                            }
+                        }
+                    } else {
+                        timeline.pause(Instant::now());
+                    }
+                }
+                WindowEvent::Key(Key::LBracket, Action::Press, modif) => {
+                    let step = if modif.contains(Modifiers::Shift) {
+                        TIMELINE_LARGE_STEP
+                    } else {
+                        TIMELINE_STEP
+                    };
+                    timeline_changed |= timeline.scrub(Instant::now(), -step);
+                }
+                WindowEvent::Key(Key::RBracket, Action::Press, modif) => {
+                    let step = if modif.contains(Modifiers::Shift) {
+                        TIMELINE_LARGE_STEP
+                    } else {
+                        TIMELINE_STEP
+                    };
+                    timeline_changed |= timeline.scrub(Instant::now(), step);
+                }
+                WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _)
+                    if timeline.is_paused() =>
+                {
+                    let window_size: na::Vector2<f32> = na::convert(window.size());
+                    if let Some(fraction) = timeline_slider_fraction(&cursor_position, &window_size)
+                    {
+                        timeline_changed |= timeline.seek_fraction(Instant::now(), fraction);
+                    }
+                }
+                WindowEvent::Key(Key::V, Action::Press, _) => {
+                    teleop.toggle();
+                }
+                WindowEvent::Focus(false) => {
+                    teleop.release_all();
+                }
+                WindowEvent::Key(Key::T, Action::Press, _) => {
+                    synchronizer.toggle();
+                }
+                WindowEvent::Key(Key::F, Action::Press, _) => {
+                    for scene in scenes(&mut object_container, &mut history_container) {
+                        scene.toggle_frame_tree();
+                    }
+                }
+                WindowEvent::Key(Key::P, Action::Press, modif) => {
+                    if modif.contains(Modifiers::Shift) {
+                        capture.toggle_recording();
+                    } else {
+                        capture.request_screenshot();
+                    }
+                }
+                WindowEvent::Key(Key::M, Action::Press, _) => {
+                    for scene in scenes(&mut object_container, &mut history_container) {
+                        scene.cycle_grid_color_scheme();
                     }
                 }
                 WindowEvent::CursorPos(x, y, _modif) => {
                     let mouse_position = na::Point2::new(x as f32, y as f32);
+                    cursor_position = mouse_position;
                     let window_size: na::Vector2<f32> = na::convert(window.size());
-                    let (point, vector) = camera.unproject(&mouse_position, &window_size);
+                    let (point, vector) =
+                        render_state.camera.unproject(&mouse_position, &window_size);
                     last_projected_point =
                         project_to_ground_plane_with_height(&point, &vector, 0.01);
                 }
@@ -338,7 +1640,41 @@ fn main() -> Result<()> {
             }
         }
 
-        if let Some(command) = &last_command {
+        if timeline_changed {
+            timeline_changed = false;
+            // unlink nodes of the previous history scene before building the next one
+            drop(history_container.take());
+            history_container = timeline.position().map(|position| {
+                ObjectContainer::from_history(
+                    object_container.settings.clone(),
+                    &snapshot,
+                    &timeline,
+                    position,
+                    &mut window,
+                )
+            });
+            object_container.set_visible(history_container.is_none());
+        }
+
+        if let Some((linear, angular)) = teleop.next_velocity(Instant::now()) {
+            if command_publisher
+                .publish(&Command::velocity(command_id, linear, angular))
+                .is_err()
+            {
+                eprintln!("Failed to publish velocity");
+            }
+            command_id += 1;
+        }
+
+        while let Ok(status) = command_status_subscriber.next() {
+            if let Some(pending_command) = &mut last_command {
+                pending_command.update_status(status);
+            }
+        }
+        if let Some(command) = last_command
+            .as_mut()
+            .and_then(|pending_command| pending_command.transmission(Instant::now()))
+        {
             if command_publisher.publish(command).is_err() {
                 eprintln!("Failed to publish command");
             }
@@ -346,38 +1682,173 @@ fn main() -> Result<()> {
 
         if let Some(last_button_down) = &last_button_down_pose {
             if let Some(last_project) = &last_projected_point {
-                window.draw_line(
-                    last_button_down,
-                    last_project,
-                    &na::Point3::new(1.0, 0.0, 1.0),
-                )
+                window.draw_line(last_button_down, last_project, &drag_tool.color())
             }
         }
+        let waypoint_color = CommandTool::Waypoints.color();
+        for segment in waypoints.windows(2) {
+            window.draw_line(&segment[0], &segment[1], &waypoint_color);
+        }
+        if let (Some(last_waypoint), Some(last_project)) = (waypoints.last(), &last_projected_point)
+        {
+            window.draw_line(last_waypoint, last_project, &(waypoint_color * 0.5));
+        }
 
         while let Ok(update) = pose_subscriber.next() {
-            for object_update in update.updates() {
-                object_container.update_object(object_update, &mut window);
-            }
-            for delete_id in update.deletions() {
-                object_container.delete_object(delete_id);
-            }
+            let header = update.header().clone();
+            synchronizer.push("pose", &header, Message::Pose(update));
         }
         while let Ok(point_cloud_update) = point_cloud_subscriber.next() {
-            object_container.update_point_clouds(point_cloud_update);
+            let header = point_cloud_update.header().clone();
+            synchronizer.push(
+                "point_cloud",
+                &header,
+                Message::PointCloud(point_cloud_update),
+            );
+        }
+        while let Ok(point_cloud_update) = point_cloud_3d_subscriber.next() {
+            let header = point_cloud_update.header().clone();
+            synchronizer.push(
+                "point_cloud_3d",
+                &header,
+                Message::PointCloud3(point_cloud_update),
+            );
+        }
+        while let Ok(laser_scan_update) = laser_scan_subscriber.next() {
+            let header = laser_scan_update.header().clone();
+            synchronizer.push("laser_scan", &header, Message::LaserScan(laser_scan_update));
         }
-        object_container.remove_timed_out();
-        object_container.draw_lines(&mut window);
-        object_container.draw_point_clouds(&mut window);
+        while let Ok(occupancy_grid_update) = occupancy_grid_subscriber.next() {
+            let header = occupancy_grid_update.header().clone();
+            synchronizer.push(
+                "occupancy_grid",
+                &header,
+                Message::OccupancyGrid(occupancy_grid_update),
+            );
+        }
+        while let Ok(path_update) = path_subscriber.next() {
+            let header = path_update.header().clone();
+            synchronizer.push("path", &header, Message::Path(path_update));
+        }
+        let mut messages = vec![];
+        // transforms are buffered by the tree so they are applied right away
+        while let Ok(transform_update) = transform_subscriber.next() {
+            synchronizer.observe("transform", transform_update.header());
+            messages.push(Message::Transform(transform_update));
+        }
+        messages.extend(synchronizer.ready());
+        while let Ok(heartbeat) = heartbeat_subscriber.next() {
+            messages.push(Message::Heartbeat(heartbeat));
+        }
+        let now = Instant::now();
+        if timeline.is_paused() {
+            // live scene catches up on resume
+            for message in messages {
+                for (applied, dropped) in timeline.record(now, message) {
+                    snapshot.apply(applied, dropped);
+                    snapshot.remove_timed_out(applied);
+                }
+            }
+        } else {
+            object_container.set_time(now);
+            for message in messages {
+                for (applied, dropped) in timeline.record(now, message.clone()) {
+                    snapshot.apply(applied, dropped);
+                    snapshot.remove_timed_out(applied);
+                }
+                object_container.apply_message(message, &mut window);
+            }
+            object_container.remove_timed_out();
+        }
+
+        let scene = history_container.as_mut().unwrap_or(&mut object_container);
+        scene.place_objects();
+        scene.draw_lines(&mut window);
+        scene.draw_covariances(&mut window, &mut render_state.renderers.translucent);
+        scene.draw_axes(&mut window);
+        scene.draw_point_clouds(&mut window, &mut render_state.renderers.points);
+        scene.draw_paths(&mut window);
+        scene.draw_occupancy_grids();
+        let teleop_message = if teleop.is_enabled() {
+            let ((linear_x, linear_y), angular) = teleop.velocity();
+            format!("teleop [{:.2} {:.2}] {:.2} \n", linear_x, linear_y, angular)
+        } else {
+            String::new()
+        };
+        let sync_message = match (synchronizer.is_enabled(), synchronizer.common_time()) {
+            (true, Some(common_time)) => format!(
+                "synchronized {:.2}s behind \n",
+                (timestamp_now() - common_time).max(0.0)
+            ),
+            (true, None) => "synchronized \n".to_owned(),
+            (false, _) => String::new(),
+        };
+        let recording_message = match capture.recorded_frames() {
+            Some(frames) => format!("recording {} frames (Shift+P to stop) \n", frames),
+            None => String::new(),
+        };
+        let timeline_message = if timeline.is_paused() {
+            format!(
+                "paused {:.1}s back of {:.1}s history (Space to resume, [ ] to scrub) \n",
+                timeline.offset(),
+                timeline.buffered(now)
+            )
+        } else {
+            String::new()
+        };
         window.draw_text(
-            &object_container.display_message(),
+            &format!(
+                "tool: {}{} \n{}{}{}{}{}",
+                selected_tool.name(),
+                if waypoints.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({} waypoints, Enter to send)", waypoints.len())
+                },
+                teleop_message,
+                recording_message,
+                timeline_message,
+                sync_message,
+                scene.display_message()
+            ),
             &na::Point2::new(1.0, 1.0),
             50.0,
             &kiss3d::text::Font::default(),
             &na::Point3::new(1.0, 1.0, 1.0),
         );
-        window.render_with_camera(&mut camera);
+        if let Some(pending_command) = &last_command {
+            // projection has origin at the bottom while text coordinates start at the top
+            // and span twice the window size
+            let window_size: na::Vector2<f32> = na::convert(window.size());
+            let marker = render_state
+                .camera
+                .project(pending_command.marker(), &window_size);
+            window.draw_text(
+                &pending_command.description(Instant::now()),
+                &na::Point2::new(marker.x * 2.0 + 20.0, (window_size.y - marker.y) * 2.0),
+                40.0,
+                &kiss3d::text::Font::default(),
+                &na::Point3::new(1.0, 1.0, 0.0),
+            );
+        }
+        if scene.settings.frame_tree {
+            // text coordinates span twice the window width
+            window.draw_text(
+                &scene.frame_tree_message(),
+                &na::Point2::new(window.width() as f32 * 1.4, 1.0),
+                50.0,
+                &kiss3d::text::Font::default(),
+                &na::Point3::new(1.0, 1.0, 1.0),
+            );
+        }
+        if timeline.is_paused() {
+            draw_timeline_slider(timeline.fraction(), &mut window);
+        }
+        window.render_with_state(&mut render_state);
+        capture.capture(&window);
     }
     window.close();
+    capture.finish();
     Ok(())
 }
 
@@ -406,21 +1877,78 @@ fn project_to_ground_plane_with_height(
         .map(|point| na::Point3::new(point.x, point.y + height, point.z))
 }
 
+/// Goal or initial pose at origin facing target
 fn command_from_ground_plane_poses(
     origin: &na::Point3<f32>,
     target: &na::Point3<f32>,
     command_id: u32,
+    tool: CommandTool,
 ) -> Command {
     let origin_right_hand = inverse_convert_coordinate_system(&origin.coords);
     let target_right_hand = inverse_convert_coordinate_system(&target.coords);
 
     let transform_x = origin_right_hand.0 - target_right_hand.0;
     let transform_y = origin_right_hand.1 - target_right_hand.1;
-    Command::new(
-        command_id,
-        (origin_right_hand.0, origin_right_hand.1),
-        -transform_y.atan2(-transform_x),
-        na::distance(origin, target),
+    let point = (origin_right_hand.0, origin_right_hand.1);
+    let angle = -transform_y.atan2(-transform_x);
+    match tool {
+        CommandTool::InitialPose => Command::initial_pose(command_id, point, angle),
+        _ => Command::goal(command_id, point, angle),
+    }
+}
+
+/// Live scene and scene rebuilt from history if scrubbing
+fn scenes<'a>(
+    live: &'a mut ObjectContainer,
+    history: &'a mut Option<ObjectContainer>,
+) -> impl Iterator<Item = &'a mut ObjectContainer> {
+    std::iter::once(live).chain(history.as_mut())
+}
+
+/// Slider spans the bottom of the window between margins given as fraction of width
+const TIMELINE_SLIDER_MARGIN: f32 = 0.05;
+/// Pixels from bottom edge
+const TIMELINE_SLIDER_HEIGHT: f32 = 30.0;
+
+/// Bar along the bottom of the window with a marker at the shown point in history
+fn draw_timeline_slider(fraction: f32, window: &mut Window) {
+    // planar coordinates have origin in the center and are not scaled by dpi
+    let scale = window.scale_factor() as f32;
+    let half_width = window.width() as f32 / scale / 2.0;
+    let half_height = window.height() as f32 / scale / 2.0;
+    let y = -half_height + TIMELINE_SLIDER_HEIGHT;
+    let start = -half_width * (1.0 - 2.0 * TIMELINE_SLIDER_MARGIN);
+    let end = -start;
+    let gray = na::Point3::new(0.6, 0.6, 0.6);
+    window.draw_planar_line(&na::Point2::new(start, y), &na::Point2::new(end, y), &gray);
+    for tick in &[start, end] {
+        window.draw_planar_line(
+            &na::Point2::new(*tick, y - 5.0),
+            &na::Point2::new(*tick, y + 5.0),
+            &gray,
+        );
+    }
+    let marker = start + (end - start) * fraction;
+    window.draw_planar_line(
+        &na::Point2::new(marker, y - 10.0),
+        &na::Point2::new(marker, y + 10.0),
+        &na::Point3::new(1.0, 1.0, 0.0),
+    );
+}
+
+/// Position along the slider of a click in window coordinates
+fn timeline_slider_fraction(
+    cursor: &na::Point2<f32>,
+    window_size: &na::Vector2<f32>,
+) -> Option<f32> {
+    let slider_height = window_size.y - TIMELINE_SLIDER_HEIGHT * 2.0;
+    if cursor.y < slider_height {
+        return None;
+    }
+    let fraction = cursor.x / window_size.x;
+    Some(
+        ((fraction - TIMELINE_SLIDER_MARGIN) / (1.0 - 2.0 * TIMELINE_SLIDER_MARGIN))
+            .clamp(0.0, 1.0),
     )
 }
 
@@ -447,3 +1975,110 @@ fn add_ground_plane(window: &mut Window) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deletions_match_id_namespace_and_pattern() {
+        let mut paths: HashMap<String, Path> = vec![
+            Path::from_points("plan", vec![]),
+            Path::from_points("trajectory", vec![]).with_namespace("robot"),
+            Path::from_points("lane/1", vec![]),
+            Path::from_points("lane/2", vec![]),
+            Path::from_points("route", vec![]),
+        ]
+        .into_iter()
+        .map(|path| (path.id().to_owned(), path))
+        .collect();
+        let mut update = PoseClientUpdate::new();
+        update.delete("plan");
+        update.delete_namespace("robot");
+        update.delete_matching("lane/*");
+        retain_undeleted(&mut paths, &update, |path| path.namespace());
+        assert_eq!(paths.keys().collect::<Vec<_>>(), vec!["route"]);
+
+        let mut update = PoseClientUpdate::new();
+        update.delete_all();
+        retain_undeleted(&mut paths, &update, |path| path.namespace());
+        assert!(paths.is_empty());
+    }
+
+    #[test]
+    fn spheres_scale_uniformly() {
+        assert_eq!(
+            shape_scale(Shape::Sphere(0.5), Shape::Sphere(1.0)),
+            Some((2., 2., 2.))
+        );
+    }
+
+    #[test]
+    fn cubes_scale_per_axis() {
+        assert_eq!(
+            shape_scale(Shape::Cube(1., 2., 4.), Shape::Cube(2., 1., 1.)),
+            Some((2., 0.5, 0.25))
+        );
+    }
+
+    #[test]
+    fn lines_reuse_node_without_scaling() {
+        assert_eq!(
+            shape_scale(Shape::Line((0., 0., 0.)), Shape::Line((1., 1., 1.))),
+            Some((1., 1., 1.))
+        );
+    }
+
+    #[test]
+    fn other_shapes_need_new_node() {
+        assert_eq!(
+            shape_scale(Shape::Sphere(0.5), Shape::Cube(1., 1., 1.)),
+            None
+        );
+        assert_eq!(shape_scale(Shape::Sphere(0.0), Shape::Sphere(1.0)), None);
+        assert_eq!(
+            shape_scale(Shape::Cube(1., 0., 1.), Shape::Cube(1., 1., 1.)),
+            None
+        );
+    }
+
+    fn container() -> ObjectContainer {
+        ObjectContainer::new(ViewSettings {
+            point_render: PointRenderSettings {
+                size_override: None,
+                style_override: None,
+                scale: 1.0,
+            },
+            laser_rays: false,
+            grid_color_scheme: GridColorScheme::Map,
+            covariance_sigma: 1.,
+            axes: false,
+            fixed_frame: "map".to_owned(),
+            frame_tree: false,
+        })
+    }
+
+    #[test]
+    fn transform_frames_expire_by_arrival_not_by_stamp() {
+        let mut container = container();
+        let start = Instant::now();
+        container.set_time(start);
+        let mut update = TransformUpdate::new();
+        // publisher clock far behind the viewer
+        update.add(Transform::new("map", "robot", (0., 0., 0.), (0., 0., 0., 1.)).with_stamp(1.));
+        update.add(Transform::new_static(
+            "robot",
+            "lidar",
+            (0., 0., 0.),
+            (0., 0., 0., 1.),
+        ));
+        container.update_transforms(&update);
+        container.remove_timed_out();
+        assert_eq!(container.transforms.parent("robot"), Some("map"));
+
+        container.set_time(start + TRANSFORM_TIMEOUT);
+        container.remove_timed_out();
+        assert_eq!(container.transforms.parent("robot"), None);
+        assert_eq!(container.transforms.parent("lidar"), Some("robot"));
+    }
+}
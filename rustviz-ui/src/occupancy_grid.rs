@@ -0,0 +1,220 @@
+use crate::{
+    convert_coordinate_system, convert_rotation_coordinate_system,
+    lifetime::{Expiry, PublisherTracker},
+};
+use kiss3d::{
+    context::{Context, Texture},
+    resource::Mesh,
+    scene::SceneNode,
+    window::Window,
+};
+use nalgebra as na;
+use pose_publisher::{
+    occupancy_grid::{OccupancyGridInfo, OccupancyGridPatch},
+    point_cloud::Colormap,
+    OccupancyGrid, PosePublisherError,
+};
+use std::{cell::RefCell, rc::Rc, str::FromStr, time::Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridColorScheme {
+    /// Free is white, occupied is black
+    Map,
+    /// Cost gradient with highlighted lethal and inscribed cells
+    Costmap,
+}
+
+impl GridColorScheme {
+    pub fn next(&self) -> Self {
+        match self {
+            GridColorScheme::Map => GridColorScheme::Costmap,
+            GridColorScheme::Costmap => GridColorScheme::Map,
+        }
+    }
+
+    fn to_rgba(self, value: i8) -> [u8; 4] {
+        match self {
+            GridColorScheme::Map => match value {
+                0..=100 => {
+                    let shade = 255 - (value as f32 * 2.55) as u8;
+                    [shade, shade, shade, 255]
+                }
+                _ => [112, 137, 134, 255],
+            },
+            GridColorScheme::Costmap => match value {
+                0 => [0, 0, 0, 255],
+                1..=98 => {
+                    let (r, g, b) = Colormap::Jet.to_rgb(value as f32 / 98.0);
+                    [(r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8, 255]
+                }
+                99 => [0, 255, 255, 255],
+                100 => [255, 0, 255, 255],
+                _ => [40, 40, 60, 255],
+            },
+        }
+    }
+}
+
+impl FromStr for GridColorScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "map" => Ok(GridColorScheme::Map),
+            "costmap" => Ok(GridColorScheme::Costmap),
+            _ => Err(format!("unknown color scheme {}", s)),
+        }
+    }
+}
+
+/// Grid drawn as a textured plane in its origin frame
+pub struct OccupancyGridContainer {
+    grid: OccupancyGrid,
+    node: SceneNode,
+    texture: Rc<Texture>,
+    texture_outdated: bool,
+    expiry: Expiry,
+}
+
+impl OccupancyGridContainer {
+    pub fn new(
+        info: OccupancyGridInfo,
+        now: Instant,
+        window: &mut Window,
+    ) -> Result<Self, PosePublisherError> {
+        let expiry = Expiry::new(info.lifetime().clone(), now);
+        let grid = OccupancyGrid::from_info(info)?;
+        let texture = Texture::new();
+        let node = build_grid_plane(grid.info(), texture.clone(), window);
+        Ok(Self {
+            grid,
+            node,
+            texture,
+            texture_outdated: true,
+            expiry,
+        })
+    }
+
+    pub fn grid(&self) -> &OccupancyGrid {
+        &self.grid
+    }
+
+    pub fn update_info(
+        &mut self,
+        info: OccupancyGridInfo,
+        now: Instant,
+        window: &mut Window,
+    ) -> Result<(), PosePublisherError> {
+        let layout_changed = !self.grid.info().same_layout(&info);
+        self.grid.set_info(info)?;
+        self.expiry.touch(self.grid.info().lifetime().clone(), now);
+        if layout_changed {
+            self.node.unlink();
+            self.node = build_grid_plane(self.grid.info(), self.texture.clone(), window);
+        }
+        self.texture_outdated = true;
+        Ok(())
+    }
+
+    pub fn apply_patch(
+        &mut self,
+        patch: &OccupancyGridPatch,
+        now: Instant,
+    ) -> Result<(), PosePublisherError> {
+        self.grid.apply_patch(patch)?;
+        self.expiry.touch(self.grid.info().lifetime().clone(), now);
+        self.texture_outdated = true;
+        Ok(())
+    }
+
+    pub fn invalidate_texture(&mut self) {
+        self.texture_outdated = true;
+    }
+
+    /// Place plane under `root_translation` and refresh texture if cells changed
+    pub fn draw(&mut self, root_translation: &na::Isometry3<f32>, color_scheme: GridColorScheme) {
+        let info = self.grid.info();
+        let origin = na::Isometry3::from_parts(
+            na::Translation3::from(convert_coordinate_system(info.origin())),
+            convert_rotation_coordinate_system(info.origin_rotation()),
+        );
+        self.node
+            .set_local_transformation(root_translation * origin);
+        if self.texture_outdated {
+            self.upload_texture(color_scheme);
+            self.texture_outdated = false;
+        }
+    }
+
+    fn upload_texture(&self, color_scheme: GridColorScheme) {
+        let pixels: Vec<u8> = self
+            .grid
+            .data()
+            .iter()
+            .flat_map(|value| color_scheme.to_rgba(*value))
+            .collect();
+        let ctxt = Context::get();
+        ctxt.active_texture(Context::TEXTURE0);
+        ctxt.bind_texture(Context::TEXTURE_2D, Some(&*self.texture));
+        ctxt.tex_image2d(
+            Context::TEXTURE_2D,
+            0,
+            Context::RGBA as i32,
+            self.grid.info().width() as i32,
+            self.grid.info().height() as i32,
+            0,
+            Context::RGBA,
+            Some(&pixels),
+        );
+        for (parameter, value) in &[
+            (Context::TEXTURE_WRAP_S, Context::CLAMP_TO_EDGE),
+            (Context::TEXTURE_WRAP_T, Context::CLAMP_TO_EDGE),
+            (Context::TEXTURE_MIN_FILTER, Context::NEAREST),
+            (Context::TEXTURE_MAG_FILTER, Context::NEAREST),
+        ] {
+            ctxt.tex_parameteri(Context::TEXTURE_2D, *parameter, *value as i32);
+        }
+    }
+
+    pub fn is_timed_out(&self, publishers: &PublisherTracker, now: Instant) -> bool {
+        self.expiry.is_expired(publishers, now)
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.node.set_visible(visible);
+    }
+}
+
+impl Drop for OccupancyGridContainer {
+    fn drop(&mut self) {
+        self.node.unlink()
+    }
+}
+
+/// Quad spanning the grid with cell (0, 0) at origin
+fn build_grid_plane(
+    info: &OccupancyGridInfo,
+    texture: Rc<Texture>,
+    window: &mut Window,
+) -> SceneNode {
+    let width = info.width() as f32 * info.resolution();
+    let height = info.height() as f32 * info.resolution();
+    let coords = vec![
+        na::Point3::from(convert_coordinate_system((0., 0., 0.))),
+        na::Point3::from(convert_coordinate_system((width, 0., 0.))),
+        na::Point3::from(convert_coordinate_system((width, height, 0.))),
+        na::Point3::from(convert_coordinate_system((0., height, 0.))),
+    ];
+    let uvs = vec![
+        na::Point2::new(0., 0.),
+        na::Point2::new(1., 0.),
+        na::Point2::new(1., 1.),
+        na::Point2::new(0., 1.),
+    ];
+    let faces = vec![na::Point3::new(0, 1, 2), na::Point3::new(0, 2, 3)];
+    let mesh = Mesh::new(coords, faces, None, Some(uvs), false);
+    let mut node = window.add_mesh(Rc::new(RefCell::new(mesh)), na::Vector3::new(1.0, 1.0, 1.0));
+    node.set_texture(texture);
+    node.enable_backface_culling(false);
+    node
+}
@@ -0,0 +1,46 @@
+use kiss3d::{
+    camera::Camera,
+    renderer::{PointRenderer, Renderer},
+};
+use nalgebra as na;
+
+/// Screen space points grouped by size
+///
+/// A point renderer draws all of its points at one size so every size gets its own renderer
+#[derive(Default)]
+pub struct PointBatches {
+    batches: Vec<(f32, PointRenderer)>,
+}
+
+impl PointBatches {
+    /// Size in pixels
+    pub fn draw_point(&mut self, size: f32, point: &na::Point3<f32>, color: &na::Point3<f32>) {
+        let index = match self
+            .batches
+            .iter()
+            .position(|(batch_size, _)| *batch_size == size)
+        {
+            Some(index) => index,
+            None => {
+                let mut renderer = PointRenderer::new();
+                renderer.set_point_size(size);
+                self.batches.push((size, renderer));
+                self.batches.len() - 1
+            }
+        };
+        self.batches[index].1.draw_point(*point, *color);
+    }
+}
+
+impl Renderer for PointBatches {
+    fn render(&mut self, pass: usize, camera: &mut dyn Camera) {
+        // sizes no longer drawn are dropped so that changing sizes don't pile up renderers
+        if pass == 0 {
+            self.batches
+                .retain(|(_, renderer)| renderer.needs_rendering());
+        }
+        for (_, renderer) in &mut self.batches {
+            renderer.render(pass, camera);
+        }
+    }
+}
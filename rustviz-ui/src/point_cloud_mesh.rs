@@ -0,0 +1,131 @@
+use kiss3d::{resource::Mesh, scene::SceneNode, window::Window};
+use nalgebra as na;
+use pose_publisher::point_cloud::PointStyle;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// Meshes are indexed with u16
+const MAX_MESH_VERTICES: usize = u16::MAX as usize;
+/// Kiss3d meshes have a single color so points are grouped by quantized color
+const COLOR_QUANTIZATION_LEVELS: f32 = 15.0;
+
+/// Vertices and faces of a single point centered on origin
+fn point_template(style: PointStyle, size: f32) -> (Vec<na::Vector3<f32>>, Vec<[u16; 3]>) {
+    let h = size / 2.0;
+    match style {
+        // squares lie in the x-z plane which is the ground plane in viewer coordinates
+        PointStyle::Points | PointStyle::Squares => (
+            vec![
+                na::Vector3::new(-h, 0.0, -h),
+                na::Vector3::new(h, 0.0, -h),
+                na::Vector3::new(h, 0.0, h),
+                na::Vector3::new(-h, 0.0, h),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        ),
+        PointStyle::Cubes => (
+            vec![
+                na::Vector3::new(-h, -h, -h),
+                na::Vector3::new(h, -h, -h),
+                na::Vector3::new(h, h, -h),
+                na::Vector3::new(-h, h, -h),
+                na::Vector3::new(-h, -h, h),
+                na::Vector3::new(h, -h, h),
+                na::Vector3::new(h, h, h),
+                na::Vector3::new(-h, h, h),
+            ],
+            vec![
+                [0, 2, 1],
+                [0, 3, 2],
+                [4, 5, 6],
+                [4, 6, 7],
+                [0, 1, 5],
+                [0, 5, 4],
+                [3, 6, 2],
+                [3, 7, 6],
+                [0, 4, 7],
+                [0, 7, 3],
+                [1, 2, 6],
+                [1, 6, 5],
+            ],
+        ),
+        // octahedron is round enough at the sizes points are drawn at
+        PointStyle::Spheres => (
+            vec![
+                na::Vector3::new(h, 0.0, 0.0),
+                na::Vector3::new(-h, 0.0, 0.0),
+                na::Vector3::new(0.0, h, 0.0),
+                na::Vector3::new(0.0, -h, 0.0),
+                na::Vector3::new(0.0, 0.0, h),
+                na::Vector3::new(0.0, 0.0, -h),
+            ],
+            vec![
+                [0, 2, 4],
+                [2, 1, 4],
+                [1, 3, 4],
+                [3, 0, 4],
+                [2, 0, 5],
+                [1, 2, 5],
+                [3, 1, 5],
+                [0, 3, 5],
+            ],
+        ),
+    }
+}
+
+fn quantize_color(color: &na::Point3<f32>) -> (u8, u8, u8) {
+    let quantize =
+        |channel: f32| (channel.clamp(0.0, 1.0) * COLOR_QUANTIZATION_LEVELS).round() as u8;
+    (quantize(color.x), quantize(color.y), quantize(color.z))
+}
+
+/// Build a group node holding merged meshes for all points of a cloud
+///
+/// Points are expected in viewer coordinates as (position, color).
+/// [`PointStyle::Points`] are drawn directly each frame and get no node
+pub fn build_point_cloud_node(
+    points: &[(na::Point3<f32>, na::Point3<f32>)],
+    style: PointStyle,
+    size: f32,
+    window: &mut Window,
+) -> Option<SceneNode> {
+    if style == PointStyle::Points {
+        return None;
+    }
+    let mut group = window.add_group();
+    let (template_coords, template_faces) = point_template(style, size);
+    let points_per_mesh = MAX_MESH_VERTICES / template_coords.len();
+
+    let mut color_groups: HashMap<(u8, u8, u8), Vec<na::Point3<f32>>> = HashMap::new();
+    for (position, color) in points {
+        color_groups
+            .entry(quantize_color(color))
+            .or_default()
+            .push(*position);
+    }
+
+    for ((r, g, b), positions) in color_groups {
+        for chunk in positions.chunks(points_per_mesh) {
+            let mut coords = Vec::with_capacity(chunk.len() * template_coords.len());
+            let mut faces = Vec::with_capacity(chunk.len() * template_faces.len());
+            for (index, position) in chunk.iter().enumerate() {
+                let base = (index * template_coords.len()) as u16;
+                coords.extend(template_coords.iter().map(|offset| position + offset));
+                faces.extend(
+                    template_faces
+                        .iter()
+                        .map(|[a, b, c]| na::Point3::new(base + a, base + b, base + c)),
+                );
+            }
+            let mesh = Mesh::new(coords, faces, None, None, false);
+            let mut node =
+                group.add_mesh(Rc::new(RefCell::new(mesh)), na::Vector3::new(1.0, 1.0, 1.0));
+            node.set_color(
+                r as f32 / COLOR_QUANTIZATION_LEVELS,
+                g as f32 / COLOR_QUANTIZATION_LEVELS,
+                b as f32 / COLOR_QUANTIZATION_LEVELS,
+            );
+            node.enable_backface_culling(false);
+        }
+    }
+    Some(group)
+}
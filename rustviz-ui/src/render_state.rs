@@ -0,0 +1,64 @@
+use crate::{point_batches::PointBatches, translucent::TranslucentRenderer};
+use kiss3d::{
+    camera::{ArcBall, Camera},
+    planar_camera::PlanarCamera,
+    post_processing::PostProcessingEffect,
+    renderer::Renderer,
+    window::{State, Window},
+};
+
+/// Opacity of covariance ellipses
+const TRANSLUCENT_OPACITY: f32 = 0.3;
+
+/// Renderers drawn after the scene nodes
+pub struct SceneRenderers {
+    pub points: PointBatches,
+    /// Drawn last so that it blends over everything else
+    pub translucent: TranslucentRenderer,
+}
+
+impl Renderer for SceneRenderers {
+    fn render(&mut self, pass: usize, camera: &mut dyn Camera) {
+        self.points.render(pass, camera);
+        self.translucent.render(pass, camera);
+    }
+}
+
+/// Camera and custom renderers used for rendering a frame
+pub struct RenderState {
+    pub camera: ArcBall,
+    pub renderers: SceneRenderers,
+}
+
+impl RenderState {
+    /// Needs the window to exist since renderers compile shaders
+    pub fn new(camera: ArcBall) -> Self {
+        Self {
+            camera,
+            renderers: SceneRenderers {
+                points: PointBatches::default(),
+                translucent: TranslucentRenderer::new(TRANSLUCENT_OPACITY),
+            },
+        }
+    }
+}
+
+impl State for RenderState {
+    fn step(&mut self, _window: &mut Window) {}
+
+    fn cameras_and_effect_and_renderer(
+        &mut self,
+    ) -> (
+        Option<&mut dyn Camera>,
+        Option<&mut dyn PlanarCamera>,
+        Option<&mut dyn Renderer>,
+        Option<&mut dyn PostProcessingEffect>,
+    ) {
+        (
+            Some(&mut self.camera),
+            None,
+            Some(&mut self.renderers),
+            None,
+        )
+    }
+}
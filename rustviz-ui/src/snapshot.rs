@@ -0,0 +1,313 @@
+use crate::{
+    lifetime::{Expiry, PublisherTracker},
+    retain_undeleted, Message,
+};
+use pose_publisher::{
+    Header, Heartbeat, LaserScan, Lifetime, ObjectPose, OccupancyGrid, OccupancyGridMessage, Path,
+    PointCloud2, PointCloud3, PoseClientUpdate, Transform, TransformUpdate,
+};
+use std::{collections::HashMap, time::Instant};
+
+/// Latest state of an entity and when it was applied
+struct Entry<T> {
+    applied: Instant,
+    expiry: Expiry,
+    state: T,
+}
+
+impl<T> Entry<T> {
+    fn new(applied: Instant, lifetime: Lifetime, state: T) -> Self {
+        Self {
+            applied,
+            expiry: Expiry::new(lifetime, applied),
+            state,
+        }
+    }
+}
+
+/// Scene as it was before the oldest message in the timeline
+///
+/// Messages dropped from the timeline are folded in so that history can be rebuilt
+/// with entities that were last updated before the buffered range
+#[derive(Default)]
+pub struct SceneSnapshot {
+    /// Object with the header of its last update
+    objects: HashMap<String, Entry<(Header, ObjectPose)>>,
+    point_clouds: HashMap<String, Entry<PointCloud2>>,
+    point_clouds_3d: HashMap<String, Entry<PointCloud3>>,
+    laser_scans: HashMap<String, Entry<LaserScan>>,
+    paths: HashMap<String, Entry<Path>>,
+    occupancy_grids: HashMap<String, Entry<OccupancyGrid>>,
+    /// Latest transform of every child frame
+    transforms: HashMap<String, (Instant, Transform)>,
+    heartbeats: HashMap<String, (Instant, Heartbeat)>,
+    publishers: PublisherTracker,
+}
+
+impl SceneSnapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply(&mut self, applied: Instant, message: Message) {
+        match message {
+            Message::Pose(update) => {
+                for object in update.updates() {
+                    self.objects.insert(
+                        object.id.clone(),
+                        Entry::new(
+                            applied,
+                            object.effective_lifetime(),
+                            (update.header().clone(), object.clone()),
+                        ),
+                    );
+                }
+                if update.has_deletions() {
+                    retain_undeleted(&mut self.objects, &update, |entry| &entry.state.1.namespace);
+                    retain_undeleted(&mut self.point_clouds, &update, |entry| {
+                        entry.state.namespace()
+                    });
+                    retain_undeleted(&mut self.point_clouds_3d, &update, |entry| {
+                        entry.state.namespace()
+                    });
+                    retain_undeleted(&mut self.laser_scans, &update, |entry| {
+                        entry.state.namespace()
+                    });
+                    retain_undeleted(&mut self.paths, &update, |entry| entry.state.namespace());
+                    retain_undeleted(&mut self.occupancy_grids, &update, |_| "");
+                }
+            }
+            Message::PointCloud(point_cloud) => {
+                self.point_clouds.insert(
+                    point_cloud.id().to_owned(),
+                    Entry::new(applied, point_cloud.lifetime().clone(), point_cloud),
+                );
+            }
+            Message::PointCloud3(point_cloud) => {
+                self.point_clouds_3d.insert(
+                    point_cloud.id().to_owned(),
+                    Entry::new(applied, point_cloud.lifetime().clone(), point_cloud),
+                );
+            }
+            Message::LaserScan(laser_scan) => {
+                self.laser_scans.insert(
+                    laser_scan.id().to_owned(),
+                    Entry::new(applied, laser_scan.lifetime().clone(), laser_scan),
+                );
+            }
+            Message::Path(path) => {
+                self.paths.insert(
+                    path.id().to_owned(),
+                    Entry::new(applied, path.lifetime().clone(), path),
+                );
+            }
+            Message::OccupancyGrid(OccupancyGridMessage::Info(info)) => {
+                let lifetime = info.lifetime().clone();
+                let id = info.id().to_owned();
+                match self.occupancy_grids.get_mut(&id) {
+                    Some(entry) => {
+                        if entry.state.set_info(info).is_err() {
+                            self.occupancy_grids.remove(&id);
+                            return;
+                        }
+                        entry.applied = applied;
+                        entry.expiry.touch(lifetime, applied);
+                    }
+                    None => {
+                        if let Ok(grid) = OccupancyGrid::from_info(info) {
+                            self.occupancy_grids
+                                .insert(id, Entry::new(applied, lifetime, grid));
+                        }
+                    }
+                }
+            }
+            Message::OccupancyGrid(OccupancyGridMessage::Patch(patch)) => {
+                if let Some(entry) = self.occupancy_grids.get_mut(patch.id()) {
+                    if entry.state.apply_patch(&patch).is_ok() {
+                        let lifetime = entry.state.info().lifetime().clone();
+                        entry.applied = applied;
+                        entry.expiry.touch(lifetime, applied);
+                    }
+                }
+            }
+            Message::Transform(update) => {
+                for transform in update.transforms() {
+                    self.transforms.insert(
+                        transform.child_frame_id().to_owned(),
+                        (applied, transform.clone()),
+                    );
+                }
+            }
+            Message::Heartbeat(heartbeat) => {
+                self.publishers.record(&heartbeat, applied);
+                self.heartbeats
+                    .insert(heartbeat.publisher_id().to_owned(), (applied, heartbeat));
+            }
+        }
+    }
+
+    /// Forget entities that expired by scene time `now`
+    pub fn remove_timed_out(&mut self, now: Instant) {
+        let publishers = &self.publishers;
+        self.objects
+            .retain(|_, entry| !entry.expiry.is_expired(publishers, now));
+        self.point_clouds
+            .retain(|_, entry| !entry.expiry.is_expired(publishers, now));
+        self.point_clouds_3d
+            .retain(|_, entry| !entry.expiry.is_expired(publishers, now));
+        self.laser_scans
+            .retain(|_, entry| !entry.expiry.is_expired(publishers, now));
+        self.paths
+            .retain(|_, entry| !entry.expiry.is_expired(publishers, now));
+        self.occupancy_grids
+            .retain(|_, entry| !entry.expiry.is_expired(publishers, now));
+        self.publishers.remove_gone(now);
+        let publishers = &self.publishers;
+        self.heartbeats
+            .retain(|publisher_id, _| publishers.is_alive(publisher_id, now).is_some());
+    }
+
+    /// Messages that rebuild the snapshot with the time they were applied, oldest first
+    pub fn messages(&self) -> Vec<(Instant, Message)> {
+        let mut messages = vec![];
+        for (applied, heartbeat) in self.heartbeats.values() {
+            messages.push((*applied, Message::Heartbeat(heartbeat.clone())));
+        }
+        for (applied, transform) in self.transforms.values() {
+            let mut update = TransformUpdate::new();
+            update.add(transform.clone());
+            messages.push((*applied, Message::Transform(update)));
+        }
+        for entry in self.objects.values() {
+            let (header, object) = &entry.state;
+            let mut update = PoseClientUpdate::new();
+            update.set_stamp(header.stamp);
+            if let Some(frame_id) = &header.frame_id {
+                update.set_frame_id(frame_id);
+            }
+            *update.add(&object.id, object.pose) = object.clone();
+            messages.push((entry.applied, Message::Pose(update)));
+        }
+        for entry in self.point_clouds.values() {
+            messages.push((entry.applied, Message::PointCloud(entry.state.clone())));
+        }
+        for entry in self.point_clouds_3d.values() {
+            messages.push((entry.applied, Message::PointCloud3(entry.state.clone())));
+        }
+        for entry in self.laser_scans.values() {
+            messages.push((entry.applied, Message::LaserScan(entry.state.clone())));
+        }
+        for entry in self.paths.values() {
+            messages.push((entry.applied, Message::Path(entry.state.clone())));
+        }
+        for entry in self.occupancy_grids.values() {
+            messages.extend(
+                entry
+                    .state
+                    .to_messages()
+                    .into_iter()
+                    .map(|message| (entry.applied, Message::OccupancyGrid(message))),
+            );
+        }
+        // stable so that grid info stays ahead of its patches
+        messages.sort_by_key(|(applied, _)| *applied);
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(snapshot: &SceneSnapshot) -> Vec<String> {
+        let mut ids: Vec<_> = snapshot
+            .messages()
+            .into_iter()
+            .filter_map(|(_, message)| match message {
+                Message::Pose(update) => Some(update.updates()[0].id.clone()),
+                Message::PointCloud(point_cloud) => Some(point_cloud.id().to_owned()),
+                Message::PointCloud3(point_cloud) => Some(point_cloud.id().to_owned()),
+                Message::LaserScan(laser_scan) => Some(laser_scan.id().to_owned()),
+                Message::Path(path) => Some(path.id().to_owned()),
+                Message::OccupancyGrid(OccupancyGridMessage::Info(info)) => {
+                    Some(info.id().to_owned())
+                }
+                _ => None,
+            })
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn snapshot_with_every_entity(namespace: &str) -> SceneSnapshot {
+        let now = Instant::now();
+        let mut snapshot = SceneSnapshot::new();
+        let mut update = PoseClientUpdate::new();
+        update.add("object", (0., 0., 0.)).with_namespace(namespace);
+        snapshot.apply(now, Message::Pose(update));
+        snapshot.apply(
+            now,
+            Message::PointCloud(
+                PointCloud2::from_points("cloud", vec![(0., 0.)]).with_namespace(namespace),
+            ),
+        );
+        snapshot.apply(
+            now,
+            Message::PointCloud3(
+                PointCloud3::from_points("cloud_3d", vec![(0., 0., 0.)]).with_namespace(namespace),
+            ),
+        );
+        snapshot.apply(
+            now,
+            Message::LaserScan(
+                LaserScan::new("scan", 0., 0.1, 0., 10., vec![1.]).with_namespace(namespace),
+            ),
+        );
+        snapshot.apply(
+            now,
+            Message::Path(Path::from_points("path", vec![]).with_namespace(namespace)),
+        );
+        for message in OccupancyGrid::new("grid", 0.1, 2, 2).to_messages() {
+            snapshot.apply(now, Message::OccupancyGrid(message));
+        }
+        snapshot
+    }
+
+    #[test]
+    fn delete_all_removes_every_entity() {
+        let mut snapshot = snapshot_with_every_entity("");
+        assert_eq!(
+            ids(&snapshot),
+            vec!["cloud", "cloud_3d", "grid", "object", "path", "scan"]
+        );
+        let mut update = PoseClientUpdate::new();
+        update.delete_all();
+        snapshot.apply(Instant::now(), Message::Pose(update));
+        assert!(ids(&snapshot).is_empty());
+    }
+
+    #[test]
+    fn deletions_by_id_and_namespace_reach_paths_and_grids() {
+        let mut snapshot = snapshot_with_every_entity("robot");
+        let mut update = PoseClientUpdate::new();
+        update.delete_namespace("robot");
+        update.delete("grid");
+        snapshot.apply(Instant::now(), Message::Pose(update));
+        assert!(ids(&snapshot).is_empty());
+    }
+
+    #[test]
+    fn clouds_in_2d_and_3d_can_share_an_id() {
+        let now = Instant::now();
+        let mut snapshot = SceneSnapshot::new();
+        snapshot.apply(
+            now,
+            Message::PointCloud(PointCloud2::from_points("cloud", vec![(0., 0.)])),
+        );
+        snapshot.apply(
+            now,
+            Message::PointCloud3(PointCloud3::from_points("cloud", vec![(0., 0., 0.)])),
+        );
+        assert_eq!(ids(&snapshot), vec!["cloud", "cloud"]);
+    }
+}
@@ -0,0 +1,210 @@
+use kiss3d::event::{Action, Key};
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+/// Zero velocity is repeated a few times after release in case a datagram is lost
+const STOP_REPEATS: u32 = 3;
+
+/// Drive a robot with WASD or arrow keys. Q and E strafe
+///
+/// Velocity is only sent while keys are held and the window has focus
+pub struct Teleop {
+    enabled: bool,
+    pressed: HashSet<Key>,
+    max_linear_speed: f32,
+    max_angular_speed: f32,
+    period: Duration,
+    last_sent: Option<Instant>,
+    stops_remaining: u32,
+}
+
+impl Teleop {
+    /// Speeds in m/s and rad/s, rate in Hz
+    pub fn new(enabled: bool, max_linear_speed: f32, max_angular_speed: f32, rate: f32) -> Self {
+        Self {
+            enabled,
+            pressed: HashSet::new(),
+            max_linear_speed,
+            max_angular_speed,
+            period: Duration::from_secs_f32(1.0 / rate.max(0.1)),
+            last_sent: None,
+            stops_remaining: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.release_all();
+    }
+
+    fn is_teleop_key(key: Key) -> bool {
+        matches!(
+            key,
+            Key::W
+                | Key::A
+                | Key::S
+                | Key::D
+                | Key::Q
+                | Key::E
+                | Key::Up
+                | Key::Down
+                | Key::Left
+                | Key::Right
+        )
+    }
+
+    /// Returns true if the key was used for driving
+    pub fn handle_key(&mut self, key: Key, action: Action) -> bool {
+        if !self.enabled || !Self::is_teleop_key(key) {
+            return false;
+        }
+        match action {
+            Action::Press => {
+                self.pressed.insert(key);
+            }
+            Action::Release => {
+                self.pressed.remove(&key);
+            }
+        }
+        true
+    }
+
+    /// Deadman behaviour when keys can no longer be tracked, for example on focus loss
+    pub fn release_all(&mut self) {
+        if !self.pressed.is_empty() {
+            self.pressed.clear();
+            self.stops_remaining = STOP_REPEATS;
+        }
+    }
+
+    fn axis(&self, positive: &[Key], negative: &[Key]) -> f32 {
+        let held = |keys: &[Key]| keys.iter().any(|key| self.pressed.contains(key));
+        match (held(positive), held(negative)) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Linear (x, y) and angular velocity from held keys
+    pub fn velocity(&self) -> ((f32, f32), f32) {
+        let forward = self.axis(&[Key::W, Key::Up], &[Key::S, Key::Down]);
+        let left = self.axis(&[Key::Q], &[Key::E]);
+        let turn = self.axis(&[Key::A, Key::Left], &[Key::D, Key::Right]);
+        (
+            (
+                forward * self.max_linear_speed,
+                left * self.max_linear_speed,
+            ),
+            turn * self.max_angular_speed,
+        )
+    }
+
+    /// Velocity to publish at `now`, limited to the configured rate
+    pub fn next_velocity(&mut self, now: Instant) -> Option<((f32, f32), f32)> {
+        // keys are only tracked while enabled so stops are still sent after disabling
+        if let Some(last_sent) = self.last_sent {
+            if now.saturating_duration_since(last_sent) < self.period {
+                return None;
+            }
+        }
+        if self.pressed.is_empty() {
+            if self.stops_remaining == 0 {
+                return None;
+            }
+            self.stops_remaining -= 1;
+        } else {
+            self.stops_remaining = STOP_REPEATS;
+        }
+        self.last_sent = Some(now);
+        Some(self.velocity())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn teleop() -> Teleop {
+        Teleop::new(true, 0.5, 1.0, 4.)
+    }
+
+    fn velocity_with(keys: &[Key]) -> ((f32, f32), f32) {
+        let mut teleop = teleop();
+        for key in keys {
+            assert!(teleop.handle_key(*key, Action::Press));
+        }
+        teleop.velocity()
+    }
+
+    #[test]
+    fn keys_map_to_scaled_velocities() {
+        assert_eq!(velocity_with(&[]), ((0., 0.), 0.));
+        assert_eq!(velocity_with(&[Key::W]), ((0.5, 0.), 0.));
+        assert_eq!(velocity_with(&[Key::Down]), ((-0.5, 0.), 0.));
+        assert_eq!(velocity_with(&[Key::Q]), ((0., 0.5), 0.));
+        assert_eq!(velocity_with(&[Key::E]), ((0., -0.5), 0.));
+        assert_eq!(velocity_with(&[Key::Left]), ((0., 0.), 1.));
+        assert_eq!(velocity_with(&[Key::D]), ((0., 0.), -1.));
+        assert_eq!(velocity_with(&[Key::Up, Key::A]), ((0.5, 0.), 1.));
+    }
+
+    #[test]
+    fn opposite_keys_cancel() {
+        assert_eq!(velocity_with(&[Key::W, Key::S]), ((0., 0.), 0.));
+        assert_eq!(velocity_with(&[Key::A, Key::Right]), ((0., 0.), 0.));
+    }
+
+    #[test]
+    fn other_keys_and_disabled_teleop_are_ignored() {
+        let mut teleop = teleop();
+        assert!(!teleop.handle_key(Key::R, Action::Press));
+        let mut disabled = Teleop::new(false, 0.5, 1.0, 10.);
+        assert!(!disabled.handle_key(Key::W, Action::Press));
+        assert_eq!(disabled.velocity(), ((0., 0.), 0.));
+        assert_eq!(disabled.next_velocity(Instant::now()), None);
+    }
+
+    #[test]
+    fn velocity_is_limited_to_rate() {
+        let mut teleop = teleop();
+        let now = Instant::now();
+        teleop.handle_key(Key::W, Action::Press);
+        assert_eq!(teleop.next_velocity(now), Some(((0.5, 0.), 0.)));
+        assert_eq!(teleop.next_velocity(now + Duration::from_millis(200)), None);
+        assert_eq!(
+            teleop.next_velocity(now + Duration::from_millis(250)),
+            Some(((0.5, 0.), 0.))
+        );
+    }
+
+    #[test]
+    fn release_sends_a_few_stops() {
+        let mut teleop = teleop();
+        let mut now = Instant::now();
+        teleop.handle_key(Key::W, Action::Press);
+        assert!(teleop.next_velocity(now).is_some());
+        teleop.handle_key(Key::W, Action::Release);
+        for _ in 0..STOP_REPEATS {
+            now += Duration::from_millis(250);
+            assert_eq!(teleop.next_velocity(now), Some(((0., 0.), 0.)));
+        }
+        now += Duration::from_millis(250);
+        assert_eq!(teleop.next_velocity(now), None);
+    }
+
+    #[test]
+    fn disabling_releases_held_keys() {
+        let mut teleop = teleop();
+        teleop.handle_key(Key::W, Action::Press);
+        teleop.toggle();
+        assert!(!teleop.is_enabled());
+        assert_eq!(teleop.next_velocity(Instant::now()), Some(((0., 0.), 0.)));
+    }
+}
@@ -0,0 +1,138 @@
+use pose_publisher::Header;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Topics silent for longer than this stop holding back the others
+const TOPIC_TIMEOUT: Duration = Duration::from_secs(1);
+/// Messages are released after waiting this long even if other topics lag behind
+const MAX_WAIT: Duration = Duration::from_secs(2);
+/// Oldest messages are released once more than this many are held back
+const MAX_PENDING: usize = 1000;
+
+/// Holds messages back until every active topic has data up to their stamp
+///
+/// Messages without a stamp are released immediately. Messages are held back for at most
+/// [`MAX_WAIT`] and at most [`MAX_PENDING`] of them at a time
+pub struct TimeSynchronizer<T> {
+    enabled: bool,
+    /// (stamp, arrival, message)
+    pending: Vec<(f64, Instant, T)>,
+    /// Newest stamp of each topic and when it arrived
+    topics: HashMap<&'static str, (f64, Instant)>,
+}
+
+impl<T> TimeSynchronizer<T> {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            pending: vec![],
+            topics: HashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    /// Record stamp of a message that is applied without waiting
+    pub fn observe(&mut self, topic: &'static str, header: &Header) {
+        if header.has_stamp() {
+            let entry = self
+                .topics
+                .entry(topic)
+                .or_insert((header.stamp, Instant::now()));
+            *entry = (entry.0.max(header.stamp), Instant::now());
+        }
+    }
+
+    pub fn push(&mut self, topic: &'static str, header: &Header, message: T) {
+        self.observe(topic, header);
+        let stamp = if header.has_stamp() { header.stamp } else { 0. };
+        self.pending.push((stamp, Instant::now(), message));
+    }
+
+    /// Latest time all active topics have data for
+    pub fn common_time(&self) -> Option<f64> {
+        self.topics
+            .values()
+            .filter(|(_, arrived)| arrived.elapsed() < TOPIC_TIMEOUT)
+            .map(|(stamp, _)| *stamp)
+            .fold(None, |oldest: Option<f64>, stamp| {
+                Some(oldest.map_or(stamp, |oldest| oldest.min(stamp)))
+            })
+    }
+
+    /// Messages that can be shown, ordered by stamp if synchronization is enabled
+    pub fn ready(&mut self) -> Vec<T> {
+        if !self.enabled {
+            return self
+                .pending
+                .drain(..)
+                .map(|(_, _, message)| message)
+                .collect();
+        }
+        let common_time = self.common_time();
+        let (mut ready, mut pending): (Vec<_>, Vec<_>) =
+            self.pending.drain(..).partition(|(stamp, arrived, _)| {
+                *stamp <= 0.
+                    || arrived.elapsed() >= MAX_WAIT
+                    || common_time.is_none_or(|common_time| *stamp <= common_time)
+            });
+        if pending.len() > MAX_PENDING {
+            pending.sort_by(|(a, _, _), (b, _, _)| a.total_cmp(b));
+            let excess = pending.len() - MAX_PENDING;
+            ready.extend(pending.drain(..excess));
+        }
+        self.pending = pending;
+        ready.sort_by(|(a, _, _), (b, _, _)| a.total_cmp(b));
+        ready.into_iter().map(|(_, _, message)| message).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamped(stamp: f64) -> Header {
+        Header {
+            stamp,
+            frame_id: None,
+        }
+    }
+
+    #[test]
+    fn messages_wait_for_other_topics() {
+        let mut synchronizer = TimeSynchronizer::new(true);
+        synchronizer.push("pose", &stamped(2.), 2);
+        synchronizer.push("path", &stamped(1.), 1);
+        assert_eq!(synchronizer.ready(), vec![1]);
+        synchronizer.push("path", &stamped(3.), 3);
+        assert_eq!(synchronizer.ready(), vec![2]);
+    }
+
+    #[test]
+    fn unstamped_messages_are_released() {
+        let mut synchronizer = TimeSynchronizer::new(true);
+        synchronizer.push("pose", &stamped(2.), 2);
+        synchronizer.push("path", &stamped(1.), 1);
+        synchronizer.push("path", &stamped(0.), 0);
+        assert_eq!(synchronizer.ready(), vec![0, 1]);
+    }
+
+    #[test]
+    fn oldest_messages_are_released_when_too_many_are_pending() {
+        let mut synchronizer = TimeSynchronizer::new(true);
+        synchronizer.push("path", &stamped(1.), 0);
+        for index in 1..=MAX_PENDING + 2 {
+            synchronizer.push("pose", &stamped(1. + index as f64), index);
+        }
+        assert_eq!(synchronizer.ready(), vec![0, 1, 2]);
+        assert_eq!(synchronizer.pending.len(), MAX_PENDING);
+    }
+}
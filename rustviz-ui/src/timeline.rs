@@ -0,0 +1,219 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Upper bound of buffered messages so that a long pause can't exhaust memory
+const MAX_ENTRIES: usize = 10_000;
+
+/// Recently applied messages with the time they were applied
+///
+/// While paused new messages are kept until resumed so the live scene can catch up
+pub struct Timeline<T> {
+    entries: VecDeque<(Instant, T)>,
+    length: Duration,
+    paused_at: Option<Instant>,
+    /// Messages that arrived while paused were dropped before the live scene caught up
+    dropped_while_paused: bool,
+    /// Point in history being shown. None shows the scene as it was when paused
+    position: Option<Instant>,
+}
+
+impl<T: Clone> Timeline<T> {
+    pub fn new(length: Duration) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            length,
+            paused_at: None,
+            dropped_while_paused: false,
+            position: None,
+        }
+    }
+
+    /// Returns messages that dropped out of the buffered range, oldest first
+    pub fn record(&mut self, now: Instant, message: T) -> Vec<(Instant, T)> {
+        self.entries.push_back((now, message));
+        let newest_needed = self.paused_at.unwrap_or(now);
+        let oldest_kept = newest_needed.checked_sub(self.length);
+        let mut dropped = vec![];
+        while let Some((applied, _)) = self.entries.front() {
+            let is_old = oldest_kept.is_some_and(|oldest_kept| *applied < oldest_kept);
+            if !is_old && self.entries.len() <= MAX_ENTRIES {
+                break;
+            }
+            if self
+                .paused_at
+                .is_some_and(|paused_at| *applied >= paused_at)
+            {
+                self.dropped_while_paused = true;
+            }
+            dropped.extend(self.entries.pop_front());
+        }
+        dropped
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn pause(&mut self, now: Instant) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(now);
+        }
+    }
+
+    /// Messages that arrived while paused, in order
+    ///
+    /// None if some of them were dropped, in which case the live scene has to be rebuilt
+    pub fn resume(&mut self) -> Option<Vec<(Instant, T)>> {
+        let paused_at = self.paused_at.take()?;
+        self.position = None;
+        if std::mem::take(&mut self.dropped_while_paused) {
+            return None;
+        }
+        Some(
+            self.entries
+                .iter()
+                .filter(|(applied, _)| *applied >= paused_at)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Oldest buffered time
+    pub fn start(&self) -> Option<Instant> {
+        self.entries.front().map(|(applied, _)| *applied)
+    }
+
+    /// Shown point in history while scrubbing
+    pub fn position(&self) -> Option<Instant> {
+        self.position
+    }
+
+    /// Move shown point by seconds, clamped to buffered history. Pauses if live
+    ///
+    /// Returns true if the shown point changed
+    pub fn scrub(&mut self, now: Instant, seconds: f32) -> bool {
+        self.pause(now);
+        let paused_at = self.paused_at.unwrap_or(now);
+        let current = self.position.unwrap_or(paused_at);
+        let target = if seconds < 0. {
+            current
+                .checked_sub(Duration::from_secs_f32(-seconds))
+                .unwrap_or(current)
+        } else {
+            current + Duration::from_secs_f32(seconds)
+        };
+        self.seek(target)
+    }
+
+    /// Show history at fraction of the buffered range, 0 being oldest
+    pub fn seek_fraction(&mut self, now: Instant, fraction: f32) -> bool {
+        self.pause(now);
+        let paused_at = self.paused_at.unwrap_or(now);
+        let start = self.start().unwrap_or(paused_at);
+        let range = paused_at.saturating_duration_since(start);
+        self.seek(start + range.mul_f32(fraction.clamp(0., 1.)))
+    }
+
+    fn seek(&mut self, target: Instant) -> bool {
+        let paused_at = match self.paused_at {
+            Some(paused_at) => paused_at,
+            None => return false,
+        };
+        let start = self.start().unwrap_or(paused_at);
+        let position = if target >= paused_at {
+            None
+        } else {
+            Some(target.max(start))
+        };
+        let changed = position != self.position;
+        self.position = position;
+        changed
+    }
+
+    /// Messages applied up to time
+    pub fn until(&self, time: Instant) -> impl Iterator<Item = &(Instant, T)> {
+        self.entries
+            .iter()
+            .take_while(move |(applied, _)| *applied <= time)
+    }
+
+    /// Fraction of the buffered range at which the shown point is
+    pub fn fraction(&self) -> f32 {
+        let (paused_at, position) = match (self.paused_at, self.position) {
+            (Some(paused_at), Some(position)) => (paused_at, position),
+            _ => return 1.,
+        };
+        let start = self.start().unwrap_or(paused_at);
+        let range = paused_at.saturating_duration_since(start).as_secs_f32();
+        if range <= 0. {
+            return 1.;
+        }
+        position.saturating_duration_since(start).as_secs_f32() / range
+    }
+
+    /// Seconds between shown point and the moment of pausing
+    pub fn offset(&self) -> f32 {
+        match (self.paused_at, self.position) {
+            (Some(paused_at), Some(position)) => {
+                paused_at.saturating_duration_since(position).as_secs_f32()
+            }
+            _ => 0.,
+        }
+    }
+
+    /// Seconds of buffered history before the moment of pausing or now
+    pub fn buffered(&self, now: Instant) -> f32 {
+        let end = self.paused_at.unwrap_or(now);
+        self.start()
+            .map(|start| end.saturating_duration_since(start).as_secs_f32())
+            .unwrap_or(0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_messages_are_dropped_while_live() {
+        let start = Instant::now();
+        let mut timeline = Timeline::new(Duration::from_secs(10));
+        assert!(timeline.record(start, 0).is_empty());
+        let dropped = timeline.record(start + Duration::from_secs(11), 1);
+        assert_eq!(dropped, vec![(start, 0)]);
+        assert_eq!(timeline.start(), Some(start + Duration::from_secs(11)));
+    }
+
+    #[test]
+    fn messages_are_kept_until_resumed() {
+        let start = Instant::now();
+        let mut timeline = Timeline::new(Duration::from_secs(10));
+        timeline.record(start, 0);
+        timeline.pause(start + Duration::from_secs(1));
+        timeline.record(start + Duration::from_secs(20), 1);
+        assert_eq!(timeline.start(), Some(start));
+        assert_eq!(
+            timeline.resume(),
+            Some(vec![(start + Duration::from_secs(20), 1)])
+        );
+    }
+
+    #[test]
+    fn long_pause_is_capped() {
+        let start = Instant::now();
+        let mut timeline = Timeline::new(Duration::from_secs(10));
+        timeline.pause(start);
+        let mut dropped = vec![];
+        for index in 0..=MAX_ENTRIES {
+            dropped.extend(timeline.record(start, index));
+        }
+        assert_eq!(dropped, vec![(start, 0)]);
+        assert_eq!(timeline.entries.len(), MAX_ENTRIES);
+        // live scene missed a message so it has to be rebuilt
+        assert_eq!(timeline.resume(), None);
+        timeline.pause(start + Duration::from_secs(1));
+        assert_eq!(timeline.resume(), Some(vec![]));
+    }
+}
@@ -0,0 +1,107 @@
+use kiss3d::{
+    camera::Camera,
+    context::Context,
+    renderer::Renderer,
+    resource::{AllocationType, BufferType, Effect, GPUVec, ShaderAttribute, ShaderUniform},
+};
+use nalgebra as na;
+
+/// Batched triangles blended over the scene with a fixed opacity
+///
+/// kiss3d materials are opaque so translucent surfaces are drawn by this renderer
+/// after the scene. Like lines and points, triangles only last for one frame
+pub struct TranslucentRenderer {
+    shader: Effect,
+    position: ShaderAttribute<na::Point3<f32>>,
+    color: ShaderAttribute<na::Point3<f32>>,
+    alpha: ShaderUniform<f32>,
+    proj: ShaderUniform<na::Matrix4<f32>>,
+    view: ShaderUniform<na::Matrix4<f32>>,
+    /// Interleaved position and color of every vertex
+    vertices: GPUVec<na::Point3<f32>>,
+    opacity: f32,
+}
+
+impl TranslucentRenderer {
+    /// Opacity in range 0.0 -> 1.0
+    pub fn new(opacity: f32) -> Self {
+        let mut shader = Effect::new_from_str(VERTEX_SHADER, FRAGMENT_SHADER);
+        shader.use_program();
+        Self {
+            position: shader.get_attrib("position").unwrap(),
+            color: shader.get_attrib("color").unwrap(),
+            alpha: shader.get_uniform("alpha").unwrap(),
+            proj: shader.get_uniform("proj").unwrap(),
+            view: shader.get_uniform("view").unwrap(),
+            shader,
+            vertices: GPUVec::new(vec![], BufferType::Array, AllocationType::StreamDraw),
+            opacity,
+        }
+    }
+
+    pub fn draw_triangle(&mut self, corners: [&na::Point3<f32>; 3], color: &na::Point3<f32>) {
+        if let Some(vertices) = self.vertices.data_mut() {
+            for corner in &corners {
+                vertices.push(**corner);
+                vertices.push(*color);
+            }
+        }
+    }
+}
+
+impl Renderer for TranslucentRenderer {
+    fn render(&mut self, pass: usize, camera: &mut dyn Camera) {
+        if self.vertices.len() == 0 {
+            return;
+        }
+        self.shader.use_program();
+        self.position.enable();
+        self.color.enable();
+        camera.upload(pass, &mut self.proj, &mut self.view);
+        self.alpha.upload(&self.opacity);
+        self.color.bind_sub_buffer(&mut self.vertices, 1, 1);
+        self.position.bind_sub_buffer(&mut self.vertices, 1, 0);
+
+        let context = Context::get();
+        context.enable(Context::BLEND);
+        context.blend_func_separate(
+            Context::SRC_ALPHA,
+            Context::ONE_MINUS_SRC_ALPHA,
+            Context::ONE,
+            Context::ONE_MINUS_SRC_ALPHA,
+        );
+        // surfaces are seen from both sides
+        context.disable(Context::CULL_FACE);
+        context.draw_arrays(Context::TRIANGLES, 0, (self.vertices.len() / 2) as i32);
+        context.disable(Context::BLEND);
+
+        self.position.disable();
+        self.color.disable();
+        if let Some(vertices) = self.vertices.data_mut() {
+            vertices.clear();
+        }
+    }
+}
+
+const VERTEX_SHADER: &str = "#version 100
+    attribute vec3 position;
+    attribute vec3 color;
+    varying vec3 vertex_color;
+    uniform mat4 proj;
+    uniform mat4 view;
+    void main() {
+        gl_Position = proj * view * vec4(position, 1.0);
+        vertex_color = color;
+    }";
+
+const FRAGMENT_SHADER: &str = "#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+    precision highp float;
+#else
+    precision mediump float;
+#endif
+    varying vec3 vertex_color;
+    uniform float alpha;
+    void main() {
+        gl_FragColor = vec4(vertex_color, alpha);
+    }";